@@ -0,0 +1,30 @@
+#!/usr/bin/env bash
+# Writes fixtures/fees/mainnet_<height>.json from a synced mainnet node: the raw block, the
+# outputs it spends and the fee fields of its getblockstats, for the fee verification test.
+# Needs bitcoin-cli (Bitcoin Core 23 or later for getblock verbosity 3) and jq.
+#
+#   scripts/fetch_fee_fixture.sh 840000
+
+set -euo pipefail
+
+HEIGHT="${1:?usage: $0 <height>}"
+CLI="${BITCOIN_CLI:=bitcoin-cli}"
+OUT="$(dirname "$0")/../fixtures/fees/mainnet_${HEIGHT}.json"
+
+HASH=$($CLI getblockhash "$HEIGHT")
+BLOCK=$($CLI getblock "$HASH" 0)
+PREVOUTS=$($CLI getblock "$HASH" 3 | jq '[.tx[1:][] | .vin[] | {
+    txid, vout,
+    value: (.prevout.value * 100000000 | round),
+    script_pubkey: .prevout.scriptPubKey.hex
+}]')
+STATS=$($CLI getblockstats "$HASH" '["totalfee","minfeerate","maxfeerate","feerate_percentiles","medianfee"]')
+
+jq -n \
+  --argjson height "$HEIGHT" \
+  --arg hash "$HASH" \
+  --arg block "$BLOCK" \
+  --argjson prevouts "$PREVOUTS" \
+  --argjson stats "$STATS" \
+  '{height: $height, hash: $hash, block: $block, prevouts: $prevouts, getblockstats: $stats}' > "$OUT"
+echo "Wrote $OUT"
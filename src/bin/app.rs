@@ -5,7 +5,9 @@ use bitcoin_explorer::constant::CONFIG;
 use bitcoin_explorer::error::AppResult;
 use bitcoin_explorer::server::AppServer;
 use bitcoin_explorer::server::bitcoin_indexer::BitcoinIndexer;
+use bitcoin_explorer::server::mempool_tracker::MempoolTracker;
 use bitcoin_explorer::server::worker::MessengerTask;
+use bitcoin_explorer::server::zmq_subscriber::ZmqSubscriber;
 
 #[tokio::main]
 async fn main() -> AppResult<()> {
@@ -17,6 +19,8 @@ async fn main() -> AppResult<()> {
     let server = AppServer::new(config).await?;
     info!("Create a new messenger task.");
     let messenger = MessengerTask::new(server.state.clone());
+    let zmq = ZmqSubscriber::new(&server.state);
+    let mempool = MempoolTracker::new(server.state.clone());
     info!("Run the server.");
     let indexer_result = BitcoinIndexer::new(server.state.clone());
 
@@ -26,6 +30,8 @@ async fn main() -> AppResult<()> {
                 (true, server.run().boxed()),
                 (true, indexer.run().boxed()),
                 (true, messenger.run().boxed()),
+                (false, zmq.run().boxed()),
+                (true, mempool.run().boxed()),
             ];
             util::task::join_all(tasks).await?;
         }
@@ -1,10 +1,200 @@
 use std::future::Future;
-use bitcoincore_rpc::{Auth, Client};
-use log::info;
-use crate::configure::bitcoin::BitcoinConfig;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bitcoincore_rpc::bitcoin::Network;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use log::{info, warn};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use tokio::sync::{RwLock, Semaphore};
+
+use crate::configure::bitcoin::{BitcoinConfig, RpcAuth};
 use crate::error::AppResult;
 
-pub type BitcoinClient = Client;
+/// `RPC_INVALID_ADDRESS_OR_KEY`, returned by bitcoind for unknown transactions and blocks.
+const RPC_INVALID_ADDRESS_OR_KEY: i32 = -5;
+
+/// `RPC_IN_WARMUP`, returned while bitcoind is still loading its indexes.
+const RPC_IN_WARMUP: i32 = -28;
+
+/// bitcoind's default `-rpcport` on `network`, used when the host has none.
+fn default_rpc_port(network: Network) -> u16 {
+    match network {
+        Network::Bitcoin => 8332,
+        Network::Testnet => 18332,
+        Network::Signet => 38332,
+        Network::Regtest => 18443,
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BitcoinRpcError {
+    #[error("bitcoind returned error {code}: {message}")]
+    Rpc { code: i32, message: String },
+    #[error("bitcoind answered with HTTP status {status}: {body}")]
+    Http { status: u16, body: String },
+    #[error("bitcoind did not answer within {0:?}")]
+    Timeout(Duration),
+    #[error("cannot reach bitcoind: {0}")]
+    Transport(String),
+    #[error("invalid response from bitcoind: {0}")]
+    InvalidResponse(String),
+    #[error("invalid bitcoind host {0}")]
+    InvalidHost(String),
+    #[error("cannot read the cookie file {path}: {source}")]
+    Cookie { path: String, source: std::io::Error },
+}
+
+impl BitcoinRpcError {
+    /// Whether bitcoind reports that the requested object does not exist.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Self::Rpc { code, .. } if *code == RPC_INVALID_ADDRESS_OR_KEY)
+    }
+
+    /// Whether the same call is expected to succeed a little later: the node is still
+    /// warming up or its work queue is full.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Rpc { code, .. } => *code == RPC_IN_WARMUP,
+            Self::Http { status, .. } => *status == 503,
+            _ => false,
+        }
+    }
+
+    pub fn code(&self) -> Option<i32> {
+        match self {
+            Self::Rpc { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+}
+
+pub type RpcResult<T> = Result<T, BitcoinRpcError>;
+
+/// A transport error with its causes, hyper's own messages alone are terse.
+fn transport(error: &dyn std::error::Error) -> BitcoinRpcError {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(&format!(": {cause}"));
+        source = cause.source();
+    }
+    BitcoinRpcError::Transport(message)
+}
+
+/// Keep-alive HTTP/1.1 connections to bitcoind, shared by the JSON-RPC client and the REST
+/// source. At most `max_connections` requests are in flight.
+#[derive(Debug)]
+pub struct NodeHttp {
+    client: Client<HttpConnector, Full<Bytes>>,
+    base: String,
+    permits: Semaphore,
+}
+
+impl NodeHttp {
+    /// `authority` is `host:port`.
+    pub fn new(authority: &str, max_connections: usize) -> Self {
+        let max_connections = max_connections.max(1);
+        Self {
+            client: Client::builder(TokioExecutor::new())
+                .pool_max_idle_per_host(max_connections)
+                .build_http(),
+            base: format!("http://{authority}"),
+            permits: Semaphore::new(max_connections),
+        }
+    }
+
+    /// Sends a request with `body` and reads the whole response.
+    pub async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> RpcResult<Response<Bytes>> {
+        let _permit = self.permits.acquire().await.map_err(|e| transport(&e))?;
+        let mut request = Request::builder().method(method).uri(format!("{}{path}", self.base));
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let request = request.body(Full::new(Bytes::from(body))).map_err(|e| transport(&e))?;
+        let (parts, body) = self.client.request(request).await.map_err(|e| transport(&e))?.into_parts();
+        let body = body.collect().await.map_err(|e| transport(&e))?.to_bytes();
+        Ok(Response::from_parts(parts, body))
+    }
+}
+
+/// The `Authorization` header sent with every call.
+#[derive(Debug)]
+enum Credentials {
+    None,
+    Basic(String),
+    /// Read from the cookie file on the first call and again after the node rejected it.
+    Cookie { path: PathBuf, header: RwLock<Option<String>> },
+}
+
+impl Credentials {
+    fn new(auth: RpcAuth) -> Self {
+        match auth {
+            RpcAuth::None => Self::None,
+            RpcAuth::UserPass { username, password } => Self::Basic(basic(&format!("{username}:{password}"))),
+            RpcAuth::CookieFile(path) => Self::Cookie {
+                path,
+                header: RwLock::new(None),
+            },
+        }
+    }
+
+    /// The header to send, reading the cookie file again when `reload` is set.
+    async fn header(&self, reload: bool) -> RpcResult<Option<String>> {
+        let (path, header) = match self {
+            Self::None => return Ok(None),
+            Self::Basic(header) => return Ok(Some(header.clone())),
+            Self::Cookie { path, header } => (path, header),
+        };
+        if !reload {
+            if let Some(header) = header.read().await.as_ref() {
+                return Ok(Some(header.clone()));
+            }
+        }
+        let cookie = tokio::fs::read_to_string(path).await.map_err(|source| BitcoinRpcError::Cookie {
+            path: path.display().to_string(),
+            source,
+        })?;
+        let fresh = basic(cookie.trim());
+        *header.write().await = Some(fresh.clone());
+        Ok(Some(fresh))
+    }
+}
+
+fn basic(credentials: &str) -> String {
+    format!("Basic {}", STANDARD.encode(credentials))
+}
+
+/// An asynchronous JSON-RPC client for bitcoind over [`NodeHttp`].
+///
+/// Every call is bounded by `rpc_timeout_secs`. Calls failing with a transient error are
+/// retried up to `rpc_retries` times, waiting `rpc_retry_backoff_ms` and doubling the wait
+/// after every attempt.
+#[derive(Debug)]
+pub struct BitcoinClient {
+    http: NodeHttp,
+    credentials: Credentials,
+    timeout: Duration,
+    retries: u32,
+    retry_backoff: Duration,
+    next_id: AtomicU64,
+}
 
 pub trait BitcoinClientExt: Sized {
     fn build_from_config(config: &BitcoinConfig) -> impl Future<Output=AppResult<Self>>;
@@ -12,8 +202,233 @@ pub trait BitcoinClientExt: Sized {
 
 impl BitcoinClientExt for BitcoinClient {
     async fn build_from_config(config: &BitcoinConfig) -> AppResult<Self> {
-        info!("get host {}", &config.get_host());
-        let rpc = Client::new(&config.get_host(), Auth::UserPass(config.username.to_string(), config.password.to_string())).unwrap();
-        Ok(rpc)
+        let authority = authority(&config.get_host(), config.network)?;
+        info!("get host {authority}");
+        Ok(Self {
+            http: NodeHttp::new(&authority, config.rpc_connections),
+            credentials: Credentials::new(config.auth()?),
+            timeout: Duration::from_secs(config.rpc_timeout_secs),
+            retries: config.rpc_retries,
+            retry_backoff: Duration::from_millis(config.rpc_retry_backoff_ms),
+            next_id: AtomicU64::new(0),
+        })
+    }
+}
+
+impl BitcoinClient {
+    pub async fn call<T: DeserializeOwned>(&self, method: &str, params: &[Value]) -> RpcResult<T> {
+        let request = self.request(method, params);
+        let response = self.send(&request, in_warmup).await?;
+        let result = response_result(response)?;
+        serde_json::from_value(result).map_err(|e| BitcoinRpcError::InvalidResponse(format!("{method}: {e}")))
+    }
+
+    /// Calls `method` once per entry of `params` in a single JSON-RPC batch. Every call
+    /// succeeds or fails on its own, only the transport failing fails the whole batch.
+    pub async fn batch<T: DeserializeOwned>(&self, method: &str, params: &[Vec<Value>]) -> RpcResult<Vec<RpcResult<T>>> {
+        if params.is_empty() {
+            return Ok(vec![]);
+        }
+        let requests: Vec<_> = params.iter().map(|params| self.request(method, params)).collect();
+        let first_id = requests[0]["id"].as_u64().unwrap_or_default();
+        let response = self
+            .send(&Value::Array(requests), |response| {
+                response.as_array().is_some_and(|responses| responses.iter().any(in_warmup))
+            })
+            .await?;
+        let Value::Array(responses) = response else {
+            return Err(BitcoinRpcError::InvalidResponse(format!("{method}: the batch answer is not a list")));
+        };
+        let mut results: Vec<Option<RpcResult<T>>> = params.iter().map(|_| None).collect();
+        for response in responses {
+            let index = response["id"]
+                .as_u64()
+                .and_then(|id| id.checked_sub(first_id))
+                .and_then(|index| results.get_mut(index as usize))
+                .ok_or_else(|| BitcoinRpcError::InvalidResponse(format!("{method}: unexpected batch id")))?;
+            *index = Some(response_result(response).and_then(|result| {
+                serde_json::from_value(result).map_err(|e| BitcoinRpcError::InvalidResponse(format!("{method}: {e}")))
+            }));
+        }
+        results
+            .into_iter()
+            .map(|result| result.ok_or_else(|| BitcoinRpcError::InvalidResponse(format!("{method}: missing batch answer"))))
+            .collect()
+    }
+
+    fn request(&self, method: &str, params: &[Value]) -> Value {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        json!({ "jsonrpc": "1.0", "id": id, "method": method, "params": params })
+    }
+
+    /// Posts `request` until neither the transport nor `in_warmup` find the answer
+    /// transient, or the retries run out.
+    async fn send<F>(&self, request: &Value, in_warmup: F) -> RpcResult<Value>
+    where
+        F: Fn(&Value) -> bool,
+    {
+        let body = serde_json::to_vec(request).map_err(|e| BitcoinRpcError::InvalidResponse(e.to_string()))?;
+        let mut backoff = self.retry_backoff;
+        let mut attempt = 0;
+        loop {
+            let result = match tokio::time::timeout(self.timeout, self.post(&body)).await {
+                Ok(result) => result,
+                Err(_) => Err(BitcoinRpcError::Timeout(self.timeout)),
+            };
+            let retry = match &result {
+                Ok(response) => in_warmup(response),
+                Err(e) => e.is_transient(),
+            };
+            if !retry || attempt >= self.retries {
+                return result;
+            }
+            match &result {
+                Ok(_) => warn!("bitcoind is warming up, retrying in {backoff:?}"),
+                Err(e) => warn!("bitcoind is busy ({e}), retrying in {backoff:?}"),
+            }
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+            attempt += 1;
+        }
+    }
+
+    async fn post(&self, body: &[u8]) -> RpcResult<Value> {
+        let authorization = self.credentials.header(false).await?;
+        let mut response = self.post_with(body, authorization.as_deref()).await?;
+        if response.status() == StatusCode::UNAUTHORIZED && matches!(self.credentials, Credentials::Cookie { .. }) {
+            // bitcoind writes a new cookie every time it starts.
+            let reloaded = self.credentials.header(true).await?;
+            if reloaded != authorization {
+                info!("bitcoind rejected the cookie, retrying with the new one");
+                response = self.post_with(body, reloaded.as_deref()).await?;
+            }
+        }
+        // bitcoind answers RPC errors with a JSON body and 404 or 500, anything else
+        // without one, like 401 for wrong credentials or 503 for a full work queue.
+        serde_json::from_slice(response.body()).map_err(|_| BitcoinRpcError::Http {
+            status: response.status().as_u16(),
+            body: String::from_utf8_lossy(response.body()).trim().to_string(),
+        })
+    }
+
+    async fn post_with(&self, body: &[u8], authorization: Option<&str>) -> RpcResult<Response<Bytes>> {
+        let mut headers = vec![(CONTENT_TYPE.as_str(), "application/json")];
+        headers.extend(authorization.map(|authorization| (AUTHORIZATION.as_str(), authorization)));
+        self.http.request(Method::POST, "/", &headers, body.to_vec()).await
+    }
+}
+
+fn in_warmup(response: &Value) -> bool {
+    response["error"]["code"].as_i64() == Some(RPC_IN_WARMUP.into())
+}
+
+/// The result of a single JSON-RPC response or the error it carries.
+fn response_result(mut response: Value) -> RpcResult<Value> {
+    let error = response["error"].take();
+    if !error.is_null() {
+        return Err(BitcoinRpcError::Rpc {
+            code: error["code"].as_i64().unwrap_or_default() as i32,
+            message: error["message"].as_str().unwrap_or_default().to_string(),
+        });
+    }
+    Ok(response["result"].take())
+}
+
+/// `host:port` of a configured host, which may carry an `http://` scheme and leave the
+/// port of `network` out.
+pub fn authority(host: &str, network: Network) -> RpcResult<String> {
+    let invalid = || BitcoinRpcError::InvalidHost(host.to_string());
+    let stripped = host.strip_prefix("http://").unwrap_or(host);
+    if stripped.contains("://") {
+        return Err(invalid());
+    }
+    let authority = stripped.split('/').next().unwrap_or_default();
+    let (name, port) = match authority.rsplit_once(':') {
+        Some((name, port)) if !name.ends_with(':') => (name, Some(port)),
+        _ => (authority, None),
+    };
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(invalid());
+    }
+    match port {
+        Some(port) => {
+            port.parse::<u16>().map_err(|_| invalid())?;
+            Ok(authority.to_string())
+        }
+        None => Ok(format!("{authority}:{}", default_rpc_port(network))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoincore_rpc::bitcoin::BlockHash;
+    use serde_json::json;
+
+    use super::*;
+    use crate::client::mock::{MockBitcoind, RPC_IN_WARMUP, RPC_INVALID_PARAMETER};
+
+    #[test]
+    fn test_authority() {
+        assert_eq!(authority("localhost:8332", Network::Bitcoin).unwrap(), "localhost:8332");
+        assert_eq!(authority("http://127.0.0.1:18443/", Network::Bitcoin).unwrap(), "127.0.0.1:18443");
+        assert_eq!(authority("127.0.0.1", Network::Bitcoin).unwrap(), "127.0.0.1:8332");
+        assert_eq!(authority("127.0.0.1", Network::Signet).unwrap(), "127.0.0.1:38332");
+        assert!(authority("https://node:8332", Network::Bitcoin).is_err());
+        assert!(authority("node:port", Network::Bitcoin).is_err());
+        assert!(authority("", Network::Bitcoin).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_retries_batches_and_times_out() {
+        let mock = MockBitcoind::from_fixture("mainnet").await;
+        let mut config = mock.config();
+        config.rpc_retry_backoff_ms = 1;
+        config.rpc_timeout_secs = 1;
+        let client = BitcoinClient::build_from_config(&config).await.unwrap();
+
+        mock.fail("getblockcount", RPC_IN_WARMUP, "Loading block index...", 2);
+        mock.overload(1);
+        assert_eq!(client.call::<u64>("getblockcount", &[]).await.unwrap(), 2);
+        assert_eq!(mock.calls("getblockcount"), 3);
+        mock.fail("getblockcount", RPC_IN_WARMUP, "Loading block index...", 10);
+        let error = client.call::<u64>("getblockcount", &[]).await.unwrap_err();
+        assert_eq!(error.code(), Some(RPC_IN_WARMUP));
+
+        let hashes = client
+            .batch::<BlockHash>("getblockhash", &[vec![json!(0)], vec![json!(2)], vec![json!(9)]])
+            .await
+            .unwrap();
+        assert_eq!(hashes[0].as_ref().unwrap(), &mock.block(0).block_hash());
+        assert_eq!(hashes[1].as_ref().unwrap(), &mock.block(2).block_hash());
+        assert_eq!(hashes[2].as_ref().unwrap_err().code(), Some(RPC_INVALID_PARAMETER));
+
+        mock.delay("getbestblockhash", Duration::from_millis(1500));
+        let error = client.call::<BlockHash>("getbestblockhash", &[]).await.unwrap_err();
+        assert!(matches!(error, BitcoinRpcError::Timeout(_)));
+    }
+
+    #[tokio::test]
+    async fn test_rereads_rotated_cookie() {
+        let mock = MockBitcoind::from_fixture("mainnet").await;
+        let dir = tempfile::tempdir().unwrap();
+        let cookie = dir.path().join(".cookie");
+        let mut config = mock.config();
+        (config.username, config.password) = (None, None);
+        config.cookie_file = Some(cookie.display().to_string());
+        let client = BitcoinClient::build_from_config(&config).await.unwrap();
+
+        let error = client.call::<u64>("getblockcount", &[]).await.unwrap_err();
+        assert!(matches!(error, BitcoinRpcError::Cookie { .. }));
+        std::fs::write(&cookie, "__cookie__:first").unwrap();
+        mock.require_auth("__cookie__:first");
+        assert_eq!(client.call::<u64>("getblockcount", &[]).await.unwrap(), 2);
+
+        // A restarted node writes a new cookie and rejects the old one.
+        std::fs::write(&cookie, "__cookie__:second").unwrap();
+        mock.require_auth("__cookie__:second");
+        assert_eq!(client.call::<u64>("getblockcount", &[]).await.unwrap(), 2);
+        mock.require_auth("__cookie__:third");
+        let error = client.call::<u64>("getblockcount", &[]).await.unwrap_err();
+        assert!(matches!(error, BitcoinRpcError::Http { status: 401, .. }));
     }
 }
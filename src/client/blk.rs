@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use bitcoincore_rpc::bitcoin::block::Header;
+use bitcoincore_rpc::bitcoin::consensus::encode;
+use bitcoincore_rpc::bitcoin::hashes::Hash;
+use bitcoincore_rpc::bitcoin::p2p::Magic;
+use bitcoincore_rpc::bitcoin::pow::Work;
+use bitcoincore_rpc::bitcoin::{Block, BlockHash};
+use tracing::{info, warn};
+
+use crate::constant::BlockHeight;
+use crate::error::{AppError, AppResult};
+
+/// Key file written by Bitcoin Core 28+ to obfuscate the block files. Older data
+/// directories have no key file, which is the same as an all-zero key.
+const XOR_KEY_FILE: &str = "xor.dat";
+
+/// Every block record starts with the network magic followed by the block size.
+const RECORD_HEADER_LEN: u64 = 8;
+
+const BLOCK_HEADER_LEN: usize = 80;
+
+/// Position of one block inside the `blk*.dat` files.
+#[derive(Debug, Clone, Copy)]
+struct BlockLocation {
+    file: u32,
+    offset: u64,
+    len: u32,
+}
+
+/// Best chain found in Bitcoin Core's `blocks/blk*.dat` files.
+///
+/// The files store blocks in the order they were downloaded, so the chain is rebuilt from
+/// the headers: every block is linked to its parent and the tip with the most cumulative
+/// work wins. Stale blocks and blocks whose ancestry is incomplete are ignored.
+#[derive(Debug)]
+pub struct BlkIndex {
+    dir: PathBuf,
+    xor_key: [u8; 8],
+    chain: Vec<(BlockHash, BlockLocation)>,
+}
+
+impl BlkIndex {
+    /// Scans the block files in `dir`. Only the record and block headers are read, the
+    /// block bodies are skipped.
+    pub fn build(dir: impl AsRef<Path>, magic: Magic) -> AppResult<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        let xor_key = read_xor_key(&dir)?;
+        let mut headers = HashMap::new();
+        for (file, path) in block_files(&dir)? {
+            scan_file(&path, file, &xor_key, magic, &mut headers)?;
+        }
+        let chain = best_chain(&headers);
+        info!(
+            "Scanned {} blocks in {}, best chain has {} blocks.",
+            headers.len(),
+            dir.display(),
+            chain.len()
+        );
+        Ok(Self {
+            dir,
+            xor_key,
+            chain,
+        })
+    }
+
+    /// Height of the last block of the best chain, or `None` when no chain was found.
+    pub fn tip_height(&self) -> Option<BlockHeight> {
+        self.chain.len().checked_sub(1).map(|height| height as BlockHeight)
+    }
+
+    pub fn hash(&self, height: BlockHeight) -> Option<BlockHash> {
+        self.chain.get(height as usize).map(|(hash, _)| *hash)
+    }
+
+    /// Drops every block above `height`, for example when the node's active chain
+    /// diverges from the chain found in the files.
+    pub fn truncate(&mut self, height: Option<BlockHeight>) {
+        self.chain.truncate(height.map_or(0, |height| height as usize + 1));
+    }
+
+    /// Reads and decodes the block at `height`. This does blocking file IO.
+    pub fn read_block(&self, height: BlockHeight) -> AppResult<Block> {
+        let (hash, location) = self.chain.get(height as usize).ok_or_else(|| {
+            AppError::UnknownError(anyhow::anyhow!("no block at height {height} in the block files"))
+        })?;
+        let mut file = File::open(self.dir.join(file_name(location.file)))?;
+        let start = location.offset + RECORD_HEADER_LEN;
+        file.seek(SeekFrom::Start(start))?;
+        let mut bytes = vec![0; location.len as usize];
+        file.read_exact(&mut bytes)?;
+        unxor(&mut bytes, &self.xor_key, start);
+        let block: Block = encode::deserialize(&bytes).map_err(|e| AppError::UnknownError(e.into()))?;
+        if block.block_hash() != *hash {
+            return Err(AppError::UnknownError(anyhow::anyhow!(
+                "block at height {height} changed on disk, expected {hash}"
+            )));
+        }
+        Ok(block)
+    }
+}
+
+fn read_xor_key(dir: &Path) -> AppResult<[u8; 8]> {
+    match std::fs::read(dir.join(XOR_KEY_FILE)) {
+        Ok(bytes) => bytes.try_into().map_err(|bytes: Vec<u8>| {
+            AppError::UnknownError(anyhow::anyhow!(
+                "{XOR_KEY_FILE} must hold 8 bytes, found {}",
+                bytes.len()
+            ))
+        }),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok([0; 8]),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn file_name(file: u32) -> String {
+    format!("blk{file:05}.dat")
+}
+
+/// Lists the `blkNNNNN.dat` files in `dir` ordered by their number.
+fn block_files(dir: &Path) -> AppResult<Vec<(u32, PathBuf)>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let number = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("blk")?.strip_suffix(".dat")?.parse().ok());
+        if let Some(number) = number {
+            files.push((number, path));
+        }
+    }
+    files.sort_unstable_by_key(|(number, _)| *number);
+    Ok(files)
+}
+
+/// The obfuscation key is applied by file position, so `position` is the offset of
+/// `bytes[0]` inside the file.
+fn unxor(bytes: &mut [u8], key: &[u8; 8], position: u64) {
+    if *key == [0; 8] {
+        return;
+    }
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte ^= key[((position + i as u64) % 8) as usize];
+    }
+}
+
+fn scan_file(
+    path: &Path,
+    file_number: u32,
+    xor_key: &[u8; 8],
+    magic: Magic,
+    headers: &mut HashMap<BlockHash, (Header, BlockLocation)>,
+) -> AppResult {
+    let mut file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let mut offset = 0;
+    while offset + RECORD_HEADER_LEN + BLOCK_HEADER_LEN as u64 <= file_len {
+        let mut record = [0; RECORD_HEADER_LEN as usize + BLOCK_HEADER_LEN];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut record)?;
+        // Core preallocates the files with plain zeros, the obfuscation key is only applied
+        // to the written blocks, so the end is found before un-XORing.
+        if record[..4] == [0; 4] {
+            break;
+        }
+        unxor(&mut record, xor_key, offset);
+        if record[..4] != magic.to_bytes() {
+            warn!("Unexpected magic in {} at offset {offset}, skipping the rest of the file.", path.display());
+            break;
+        }
+        let len = u32::from_le_bytes([record[4], record[5], record[6], record[7]]);
+        if offset + RECORD_HEADER_LEN + u64::from(len) > file_len {
+            warn!("Truncated block in {} at offset {offset}.", path.display());
+            break;
+        }
+        let header: Header = encode::deserialize(&record[RECORD_HEADER_LEN as usize..])
+            .map_err(|e| AppError::UnknownError(e.into()))?;
+        let location = BlockLocation {
+            file: file_number,
+            offset,
+            len,
+        };
+        headers.entry(header.block_hash()).or_insert((header, location));
+        offset += RECORD_HEADER_LEN + u64::from(len);
+    }
+    Ok(())
+}
+
+/// Links every header to its parent starting from the genesis block and returns the
+/// chain leading to the tip with the most cumulative work, genesis first.
+fn best_chain(
+    headers: &HashMap<BlockHash, (Header, BlockLocation)>,
+) -> Vec<(BlockHash, BlockLocation)> {
+    let mut children: HashMap<BlockHash, Vec<BlockHash>> = HashMap::new();
+    for (hash, (header, _)) in headers {
+        children.entry(header.prev_blockhash).or_default().push(*hash);
+    }
+    let mut work: HashMap<BlockHash, Work> = HashMap::new();
+    let mut best: Option<(Work, BlockHash)> = None;
+    let mut pending: Vec<(BlockHash, Work)> = children
+        .get(&BlockHash::all_zeros())
+        .into_iter()
+        .flatten()
+        .map(|hash| (*hash, headers[hash].0.work()))
+        .collect();
+    while let Some((hash, total)) = pending.pop() {
+        work.insert(hash, total);
+        if best.is_none_or(|(best_work, _)| total > best_work) {
+            best = Some((total, hash));
+        }
+        for child in children.get(&hash).into_iter().flatten() {
+            pending.push((*child, total + headers[child].0.work()));
+        }
+    }
+
+    let mut chain = Vec::new();
+    let mut next = best.map(|(_, hash)| hash);
+    while let Some(hash) = next {
+        let (header, location) = headers[&hash];
+        chain.push((hash, location));
+        next = work.contains_key(&header.prev_blockhash).then_some(header.prev_blockhash);
+    }
+    chain.reverse();
+    chain
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoincore_rpc::bitcoin::blockdata::constants::genesis_block;
+    use bitcoincore_rpc::bitcoin::Network;
+
+    use super::*;
+
+    /// `fixtures/blk/mainnet` holds mainnet blocks 0-2 obfuscated with an `xor.dat` key, out
+    /// of order across two files, with a stale sibling of block 1 (the genesis block with
+    /// another nonce) and an unobfuscated zero tail. `fixtures/blk/legacy` holds blocks 0-1
+    /// without a key file.
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/blk").join(name)
+    }
+
+    #[test]
+    fn test_build_follows_header_chain_across_files() {
+        let index = BlkIndex::build(fixture("mainnet"), Network::Bitcoin.magic()).unwrap();
+        assert_eq!(index.tip_height(), Some(2));
+        let genesis = genesis_block(Network::Bitcoin);
+        assert_eq!(index.read_block(0).unwrap(), genesis);
+        let mut parent = genesis.block_hash();
+        for height in 1..=2 {
+            let block = index.read_block(height).unwrap();
+            assert_eq!(block.header.prev_blockhash, parent);
+            assert!(block.check_merkle_root());
+            assert_eq!(index.hash(height), Some(block.block_hash()));
+            parent = block.block_hash();
+        }
+        assert_eq!(
+            parent.to_string(),
+            "000000006a625f06636b8bb6ac7b960a8d03705d1ace08b1a19da3fdcc99ddbd"
+        );
+    }
+
+    #[test]
+    fn test_build_without_key_file() {
+        let mut index = BlkIndex::build(fixture("legacy"), Network::Bitcoin.magic()).unwrap();
+        assert_eq!(index.tip_height(), Some(1));
+        assert_eq!(index.read_block(0).unwrap(), genesis_block(Network::Bitcoin));
+        index.truncate(None);
+        assert_eq!(index.tip_height(), None);
+    }
+}
@@ -0,0 +1,985 @@
+//! An in-process stand-in for bitcoind that speaks its JSON-RPC protocol over HTTP, for tests.
+//!
+//! The mock serves a chain loaded from `fixtures/bitcoind` or built by the test, and the
+//! test scripts what happens next: new blocks, reorgs, mempool transactions, RPC errors and
+//! slow responses. [`MockBitcoind::config`] points a [`BitcoinConfig`] at it.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use axum::extract::{Path as UrlPath, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Json;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bitcoincore_rpc::bitcoin::block::{Header, Version};
+use bitcoincore_rpc::bitcoin::consensus::encode::{deserialize, serialize};
+use bitcoincore_rpc::bitcoin::hashes::Hash;
+use bitcoincore_rpc::bitcoin::hex::{DisplayHex, FromHex};
+use bitcoincore_rpc::bitcoin::script::{Builder, PushBytesBuf};
+use bitcoincore_rpc::bitcoin::{
+    absolute, transaction, Amount, Block, BlockHash, OutPoint, ScriptBuf, Sequence, Transaction,
+    TxIn, TxOut, Txid, Witness,
+};
+use bitcoincore_rpc::json::{
+    EstimateSmartFeeResult, FeeRatePercentiles, GetBlockHeaderResult, GetBlockStatsResult,
+    GetMempoolEntryResult, GetMempoolEntryResultFees, GetMempoolInfoResult, GetRawTransactionResult,
+    GetRawTransactionResultVin, GetRawTransactionResultVinScriptSig, GetRawTransactionResultVout,
+    GetRawTransactionResultVoutScriptPubKey,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::task::JoinHandle;
+
+use crate::client::bitcoin::{BitcoinClient, BitcoinClientExt};
+use crate::client::source::{BlockSource, RpcSource};
+use crate::configure::bitcoin::BitcoinConfig;
+use crate::constant::BlockHeight;
+use crate::service::ingest::BlockFees;
+
+/// bitcoind's `RPC_METHOD_NOT_FOUND`.
+pub const RPC_METHOD_NOT_FOUND: i32 = -32601;
+/// bitcoind's `RPC_INVALID_PARAMETER`.
+pub const RPC_INVALID_PARAMETER: i32 = -8;
+/// bitcoind's `RPC_INVALID_ADDRESS_OR_KEY`, returned for unknown blocks and transactions.
+pub const RPC_INVALID_ADDRESS_OR_KEY: i32 = -5;
+/// bitcoind's `RPC_IN_WARMUP`, returned while the node is still loading.
+pub const RPC_IN_WARMUP: i32 = -28;
+
+/// Blocks mined by the mock pay their coinbase here.
+const MOCK_PAYOUT: [u8; 22] = [
+    0x00, 0x14, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+];
+
+/// An error returned in place of a result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcError {
+    pub code: i32,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// bitcoind answers unknown methods with 404 and every other error with 500.
+    fn status(&self) -> StatusCode {
+        match self.code {
+            RPC_METHOD_NOT_FOUND => StatusCode::NOT_FOUND,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Fixture file layout: the blocks of a chain from the genesis block up, hex encoded.
+#[derive(Debug, Deserialize)]
+struct Fixture {
+    blocks: Vec<String>,
+}
+
+struct MempoolTx {
+    tx: Transaction,
+    fee: Amount,
+    time: u64,
+}
+
+#[derive(Default)]
+struct Node {
+    /// The active chain, indexed by height.
+    chain: Vec<Block>,
+    /// Blocks reorged out of the active chain, still served by hash like bitcoind does.
+    stale: Vec<Block>,
+    /// In arrival order.
+    mempool: Vec<MempoolTx>,
+    /// BTC/kvB, `None` answers `estimatesmartfee` with insufficient data.
+    fee_estimate: Option<Amount>,
+    /// Errors returned by the next calls of a method, one per call.
+    failures: HashMap<String, VecDeque<RpcError>>,
+    delays: HashMap<String, Duration>,
+    calls: HashMap<String, usize>,
+    /// Requests answered with a full work queue before any is processed.
+    overloads: usize,
+    /// The `user:password` RPC requests must authenticate with, anything goes when `None`.
+    credentials: Option<String>,
+    /// Blocks mined so far, keeps the coinbases of competing blocks apart.
+    mined: u32,
+}
+
+/// A bitcoind double listening on a local port until dropped.
+pub struct MockBitcoind {
+    addr: SocketAddr,
+    node: Arc<Mutex<Node>>,
+    server: JoinHandle<()>,
+}
+
+impl MockBitcoind {
+    /// Serves `blocks` as the active chain, the genesis block first.
+    pub async fn start(blocks: Vec<Block>) -> Self {
+        let node = Arc::new(Mutex::new(Node {
+            chain: blocks,
+            ..Default::default()
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind the mock bitcoind");
+        let addr = listener.local_addr().expect("mock bitcoind address");
+        let app = axum::Router::new()
+            .route("/rest/*path", get(rest))
+            .fallback(handle)
+            .with_state(node.clone());
+        let server = tokio::spawn(async move {
+            axum::serve(listener, app).await.expect("serve the mock bitcoind");
+        });
+        Self { addr, node, server }
+    }
+
+    /// Serves the chain of `fixtures/bitcoind/<name>.json`.
+    pub async fn from_fixture(name: &str) -> Self {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures/bitcoind")
+            .join(format!("{name}.json"));
+        let fixture: Fixture = serde_json::from_slice(&std::fs::read(&path).expect("read the fixture"))
+            .expect("parse the fixture");
+        let blocks = fixture
+            .blocks
+            .iter()
+            .map(|raw| deserialize(&Vec::<u8>::from_hex(raw).expect("fixture hex")).expect("fixture block"))
+            .collect();
+        Self::start(blocks).await
+    }
+
+    /// A node configuration pointing at the mock.
+    pub fn config(&self) -> BitcoinConfig {
+        serde_json::from_value(json!({
+            "host": self.addr.to_string(),
+            "username": "mock",
+            "password": "mock",
+        }))
+        .expect("mock bitcoin config")
+    }
+
+    pub async fn client(&self) -> Arc<BitcoinClient> {
+        Arc::new(BitcoinClient::build_from_config(&self.config()).await.expect("mock bitcoin client"))
+    }
+
+    /// A JSON-RPC block source reading from the mock.
+    pub async fn source(&self) -> Arc<dyn BlockSource> {
+        Arc::new(RpcSource::new(self.client().await))
+    }
+
+    pub fn tip(&self) -> (BlockHeight, BlockHash) {
+        let node = self.node();
+        (node.tip_height(), node.tip().block_hash())
+    }
+
+    /// The block at `height` of the active chain.
+    pub fn block(&self, height: BlockHeight) -> Block {
+        self.node().chain[height as usize].clone()
+    }
+
+    /// Mines a block with `txs` on top of the tip, taking them out of the mempool.
+    pub fn mine(&self, txs: Vec<Transaction>) -> Block {
+        self.node().mine(txs)
+    }
+
+    /// Mines a block with everything in the mempool.
+    pub fn mine_mempool(&self) -> Block {
+        let mut node = self.node();
+        let txs = node.mempool.iter().map(|entry| entry.tx.clone()).collect();
+        node.mine(txs)
+    }
+
+    /// Replaces the top `depth` blocks with `length` empty blocks. The transactions of the
+    /// replaced blocks go back to the mempool.
+    pub fn reorg(&self, depth: usize, length: usize) -> Vec<Block> {
+        let mut node = self.node();
+        let fork = node.chain.len() - depth;
+        let orphaned: Vec<_> = node.chain.drain(fork..).collect();
+        for block in &orphaned {
+            for tx in block.txdata.iter().skip(1) {
+                node.add_to_mempool(tx.clone());
+            }
+        }
+        node.stale.extend(orphaned);
+        (0..length).map(|_| node.mine(vec![])).collect()
+    }
+
+    /// Accepts `tx` into the mempool. Its prevouts must be known to the mock.
+    pub fn add_to_mempool(&self, tx: Transaction) {
+        self.node().add_to_mempool(tx);
+    }
+
+    /// Sets the feerate `estimatesmartfee` answers with, per kvB.
+    pub fn set_fee_estimate(&self, feerate: Option<Amount>) {
+        self.node().fee_estimate = feerate;
+    }
+
+    /// Fails the next `times` calls of `method` with `code`.
+    pub fn fail(&self, method: &str, code: i32, message: &str, times: usize) {
+        self.node()
+            .failures
+            .entry(method.to_string())
+            .or_default()
+            .extend((0..times).map(|_| RpcError::new(code, message)));
+    }
+
+    /// Answers the next `times` HTTP requests like bitcoind does when its work queue is
+    /// full, whatever they ask for.
+    pub fn overload(&self, times: usize) {
+        self.node().overloads += times;
+    }
+
+    /// Answers RPC requests not authenticating as `credentials`, `user:password` like the
+    /// content of a cookie file, with 401 from now on.
+    pub fn require_auth(&self, credentials: &str) {
+        self.node().credentials = Some(credentials.to_string());
+    }
+
+    /// Delays every answer to `method`.
+    pub fn delay(&self, method: &str, delay: Duration) {
+        self.node().delays.insert(method.to_string(), delay);
+    }
+
+    /// Number of calls of `method` received so far, failed ones included.
+    pub fn calls(&self, method: &str) -> usize {
+        self.node().calls.get(method).copied().unwrap_or_default()
+    }
+
+    fn node(&self) -> MutexGuard<'_, Node> {
+        self.node.lock().expect("mock bitcoind lock")
+    }
+}
+
+impl Drop for MockBitcoind {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// An unsigned transaction spending `inputs`, enough for an index that does not check scripts.
+pub fn spend(inputs: &[OutPoint], outputs: Vec<TxOut>) -> Transaction {
+    Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: inputs
+            .iter()
+            .map(|outpoint| TxIn {
+                previous_output: *outpoint,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            })
+            .collect(),
+        output: outputs,
+    }
+}
+
+async fn handle(State(node): State<Arc<Mutex<Node>>>, headers: HeaderMap, Json(body): Json<Value>) -> Response {
+    {
+        let mut node = node.lock().expect("mock bitcoind lock");
+        if let Some(credentials) = &node.credentials {
+            let expected = format!("Basic {}", STANDARD.encode(credentials));
+            if headers.get(header::AUTHORIZATION).is_none_or(|authorization| authorization != expected.as_str()) {
+                return StatusCode::UNAUTHORIZED.into_response();
+            }
+        }
+        if node.overloads > 0 {
+            node.overloads -= 1;
+            return (StatusCode::SERVICE_UNAVAILABLE, "Work queue depth exceeded").into_response();
+        }
+    }
+    match body {
+        Value::Array(requests) => {
+            let mut responses = Vec::with_capacity(requests.len());
+            for request in requests {
+                responses.push(call(&node, request).await.1);
+            }
+            (StatusCode::OK, Json(Value::Array(responses))).into_response()
+        }
+        request => {
+            let (status, response) = call(&node, request).await;
+            (status, Json(response)).into_response()
+        }
+    }
+}
+
+/// Serves the REST endpoints the explorer reads by answering the matching RPC call, so
+/// failures and delays scripted for the RPC method apply to them too.
+async fn rest(
+    State(node): State<Arc<Mutex<Node>>>,
+    UrlPath(path): UrlPath<String>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let (resource, format) = path.rsplit_once('.').unwrap_or((&path, ""));
+    let (kind, argument) = resource.split_once('/').unwrap_or((resource, ""));
+    let verbose = query.get("verbose").is_none_or(|verbose| verbose != "false");
+    if let ("getutxos", Some(outpoints)) = (kind, argument.strip_prefix("checkmempool/")) {
+        let mut bitmap = String::new();
+        for outpoint in outpoints.split('/') {
+            let (txid, vout) = outpoint.split_once('-').unwrap_or((outpoint, ""));
+            let request = json!({ "method": "gettxout", "params": [txid, vout.parse::<u32>().unwrap_or(u32::MAX)] });
+            let (_, response) = call(&node, request).await;
+            bitmap.push(if response["result"].is_null() { '0' } else { '1' });
+        }
+        return Json(json!({ "bitmap": bitmap })).into_response();
+    }
+    let (method, params) = match (kind, argument) {
+        ("chaininfo", _) => ("getblockchaininfo", json!([])),
+        ("blockhashbyheight", height) => ("getblockhash", json!([height.parse::<u64>().unwrap_or(u64::MAX)])),
+        ("headers", hash) => ("getblockheader", json!([hash, true])),
+        ("block", hash) => ("getblock", json!([hash, 0])),
+        ("tx", txid) => ("getrawtransaction", json!([txid, format == "json"])),
+        ("mempool", "contents") => ("getrawmempool", json!([verbose])),
+        ("mempool", "info") => ("getmempoolinfo", json!([])),
+        _ => return (StatusCode::NOT_FOUND, "Not found").into_response(),
+    };
+    let (_, response) = call(&node, json!({ "method": method, "params": params })).await;
+    let result = response["result"].clone();
+    if let Some(message) = response["error"]["message"].as_str() {
+        let status = match response["error"]["code"].as_i64().map(|code| code as i32) {
+            Some(RPC_INVALID_ADDRESS_OR_KEY | RPC_INVALID_PARAMETER) => StatusCode::NOT_FOUND,
+            Some(RPC_IN_WARMUP) => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        // bitcoind answers an unknown header with an empty list.
+        if kind == "headers" && status == StatusCode::NOT_FOUND {
+            return Json(json!([])).into_response();
+        }
+        return (status, message.to_string()).into_response();
+    }
+    match (kind, format) {
+        ("headers", _) => Json(json!([result])).into_response(),
+        ("blockhashbyheight", "hex") => format!("{}\n", result.as_str().unwrap_or_default()).into_response(),
+        (_, "bin") => Vec::<u8>::from_hex(result.as_str().unwrap_or_default())
+            .unwrap_or_default()
+            .into_response(),
+        _ => Json(result).into_response(),
+    }
+}
+
+async fn call(node: &Mutex<Node>, request: Value) -> (StatusCode, Value) {
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let method = request.get("method").and_then(Value::as_str).unwrap_or_default();
+    let params = match request.get("params") {
+        Some(Value::Array(params)) => params.clone(),
+        _ => vec![],
+    };
+    let (delay, failure) = {
+        let mut node = node.lock().expect("mock bitcoind lock");
+        *node.calls.entry(method.to_string()).or_default() += 1;
+        let failure = node.failures.get_mut(method).and_then(VecDeque::pop_front);
+        (node.delays.get(method).copied(), failure)
+    };
+    if let Some(delay) = delay {
+        tokio::time::sleep(delay).await;
+    }
+    let result = match failure {
+        Some(error) => Err(error),
+        None => node.lock().expect("mock bitcoind lock").dispatch(method, &params),
+    };
+    match result {
+        Ok(result) => (StatusCode::OK, json!({ "result": result, "error": null, "id": id })),
+        Err(error) => (
+            error.status(),
+            json!({
+                "result": null,
+                "error": { "code": error.code, "message": error.message },
+                "id": id,
+            }),
+        ),
+    }
+}
+
+impl Node {
+    fn tip(&self) -> &Block {
+        self.chain.last().expect("the mock chain has a genesis block")
+    }
+
+    fn tip_height(&self) -> BlockHeight {
+        (self.chain.len() - 1) as BlockHeight
+    }
+
+    fn dispatch(&self, method: &str, params: &[Value]) -> Result<Value, RpcError> {
+        let result = match method {
+            "getblockcount" => json!(self.tip_height()),
+            "getblockchaininfo" => {
+                let height = self.chain.len() - 1;
+                let tip = self.header_info(height, self.tip());
+                json!({
+                    "chain": "main",
+                    "blocks": height,
+                    "headers": height,
+                    "bestblockhash": tip.hash,
+                    "difficulty": tip.difficulty,
+                    "time": tip.time,
+                    "mediantime": tip.median_time,
+                    "verificationprogress": 1.0,
+                    "initialblockdownload": false,
+                    "chainwork": tip.chainwork.to_lower_hex_string(),
+                    "size_on_disk": 0,
+                    "pruned": false,
+                    "warnings": "",
+                })
+            }
+            "getbestblockhash" => json!(self.tip().block_hash()),
+            "getblockhash" => {
+                let height = param::<usize>(params, 0)?;
+                let block = self
+                    .chain
+                    .get(height)
+                    .ok_or_else(|| RpcError::new(RPC_INVALID_PARAMETER, "Block height out of range"))?;
+                json!(block.block_hash())
+            }
+            "getblock" => {
+                let (_, block) = self.find_block(&param(params, 0)?)?;
+                match optional_param::<u8>(params, 1)?.unwrap_or(1) {
+                    0 => json!(serialize(block).to_lower_hex_string()),
+                    _ => return Err(RpcError::new(RPC_INVALID_PARAMETER, "the mock only serves raw blocks")),
+                }
+            }
+            "getblockheader" => {
+                let (height, block) = self.find_block(&param(params, 0)?)?;
+                match optional_param::<bool>(params, 1)?.unwrap_or(true) {
+                    true => json!(self.header_info(height, block)),
+                    false => json!(serialize(&block.header).to_lower_hex_string()),
+                }
+            }
+            "getblockstats" => {
+                let height = param::<usize>(params, 0)?;
+                let block = self
+                    .chain
+                    .get(height)
+                    .ok_or_else(|| RpcError::new(RPC_INVALID_PARAMETER, "Target block height after current tip"))?;
+                json!(self.block_stats(height, block))
+            }
+            "getrawtransaction" => {
+                let txid = param::<Txid>(params, 0)?;
+                let verbose = optional_param::<bool>(params, 1)?.unwrap_or(false);
+                let (tx, block) = self.find_transaction(&txid).ok_or_else(|| {
+                    RpcError::new(
+                        RPC_INVALID_ADDRESS_OR_KEY,
+                        "No such mempool or blockchain transaction. Use gettransaction for wallet transactions.",
+                    )
+                })?;
+                match verbose {
+                    true => {
+                        // bitcoind leaves out the witness of inputs without one rather than
+                        // sending null, which the client does not accept.
+                        let mut info = json!(self.transaction_info(tx, block));
+                        let inputs = info["vin"].as_array_mut().into_iter().flatten();
+                        for input in inputs.filter_map(Value::as_object_mut) {
+                            if input.get("txinwitness") == Some(&Value::Null) {
+                                input.remove("txinwitness");
+                            }
+                        }
+                        info
+                    }
+                    false => json!(serialize(tx).to_lower_hex_string()),
+                }
+            }
+            "gettxout" => {
+                let outpoint = OutPoint::new(param(params, 0)?, param(params, 1)?);
+                let include_mempool = optional_param::<bool>(params, 2)?.unwrap_or(true);
+                match self.utxo(&outpoint, include_mempool) {
+                    Some((output, confirmations)) => json!({
+                        "bestblock": self.tip().block_hash(),
+                        "confirmations": confirmations,
+                        "value": output.value.to_btc(),
+                    }),
+                    None => Value::Null,
+                }
+            }
+            "getrawmempool" => match optional_param::<bool>(params, 0)?.unwrap_or(false) {
+                true => {
+                    let entries: HashMap<_, _> = self
+                        .mempool
+                        .iter()
+                        .map(|entry| (entry.tx.txid(), self.mempool_entry(entry)))
+                        .collect();
+                    json!(entries)
+                }
+                false => json!(self.mempool.iter().map(|entry| entry.tx.txid()).collect::<Vec<_>>()),
+            },
+            "getmempoolentry" => {
+                let txid = param::<Txid>(params, 0)?;
+                let entry = self
+                    .mempool
+                    .iter()
+                    .find(|entry| entry.tx.txid() == txid)
+                    .ok_or_else(|| RpcError::new(RPC_INVALID_ADDRESS_OR_KEY, "Transaction not in mempool"))?;
+                json!(self.mempool_entry(entry))
+            }
+            "getmempoolinfo" => json!(GetMempoolInfoResult {
+                loaded: Some(true),
+                size: self.mempool.len(),
+                bytes: self.mempool.iter().map(|entry| entry.tx.vsize()).sum(),
+                usage: self.mempool.iter().map(|entry| entry.tx.total_size()).sum(),
+                total_fee: Some(self.mempool.iter().map(|entry| entry.fee).sum()),
+                max_mempool: 300_000_000,
+                mempool_min_fee: Amount::from_sat(1000),
+                min_relay_tx_fee: Amount::from_sat(1000),
+                incremental_relay_fee: Some(Amount::from_sat(1000)),
+                unbroadcast_count: Some(0),
+                full_rbf: Some(false),
+            }),
+            "estimatesmartfee" => {
+                let blocks = param::<i64>(params, 0)?;
+                json!(EstimateSmartFeeResult {
+                    fee_rate: self.fee_estimate,
+                    errors: self
+                        .fee_estimate
+                        .is_none()
+                        .then(|| vec!["Insufficient data or no feerate found".to_string()]),
+                    blocks,
+                })
+            }
+            _ => return Err(RpcError::new(RPC_METHOD_NOT_FOUND, "Method not found")),
+        };
+        Ok(result)
+    }
+
+    /// A block of the active chain or a stale one, with its height.
+    fn find_block(&self, hash: &BlockHash) -> Result<(usize, &Block), RpcError> {
+        self.chain
+            .iter()
+            .enumerate()
+            .chain(self.stale.iter().map(|block| (self.height_of(block), block)))
+            .find(|(_, block)| block.block_hash() == *hash)
+            .ok_or_else(|| RpcError::new(RPC_INVALID_ADDRESS_OR_KEY, "Block not found"))
+    }
+
+    /// Height of a stale block, one above its parent.
+    fn height_of(&self, block: &Block) -> usize {
+        let parent = block.header.prev_blockhash;
+        self.find_block(&parent).map(|(height, _)| height + 1).unwrap_or_default()
+    }
+
+    fn in_active_chain(&self, height: usize, block: &Block) -> bool {
+        self.chain.get(height) == Some(block)
+    }
+
+    /// A transaction of the active chain or the mempool, with the block confirming it.
+    fn find_transaction(&self, txid: &Txid) -> Option<(&Transaction, Option<(usize, &Block)>)> {
+        self.chain
+            .iter()
+            .enumerate()
+            .find_map(|(height, block)| {
+                block
+                    .txdata
+                    .iter()
+                    .find(|tx| tx.txid() == *txid)
+                    .map(|tx| (tx, Some((height, block))))
+            })
+            .or_else(|| {
+                self.mempool
+                    .iter()
+                    .find(|entry| entry.tx.txid() == *txid)
+                    .map(|entry| (&entry.tx, None))
+            })
+    }
+
+    /// An unspent output with its confirmations, like `gettxout`. Spends by the mempool
+    /// count when `include_mempool` is set, and so do its outputs.
+    fn utxo(&self, outpoint: &OutPoint, include_mempool: bool) -> Option<(TxOut, usize)> {
+        let (tx, block) = self.find_transaction(&outpoint.txid)?;
+        if block.is_none() && !include_mempool {
+            return None;
+        }
+        let output = tx.output.get(outpoint.vout as usize)?;
+        let mempool = self.mempool.iter().map(|entry| &entry.tx).filter(|_| include_mempool);
+        let spent = self
+            .chain
+            .iter()
+            .flat_map(|block| &block.txdata)
+            .chain(mempool)
+            .any(|tx| tx.input.iter().any(|input| input.previous_output == *outpoint));
+        if spent || output.script_pubkey.is_op_return() {
+            return None;
+        }
+        let confirmations = block.map_or(0, |(height, _)| self.chain.len() - height);
+        Some((output.clone(), confirmations))
+    }
+
+    fn prevout(&self, outpoint: &OutPoint) -> Option<TxOut> {
+        let (tx, _) = self.find_transaction(&outpoint.txid)?;
+        tx.output.get(outpoint.vout as usize).cloned()
+    }
+
+    /// Inputs minus outputs, zero for coinbases and unknown prevouts.
+    fn fee(&self, tx: &Transaction) -> Amount {
+        if tx.is_coinbase() {
+            return Amount::ZERO;
+        }
+        let input: Amount = tx
+            .input
+            .iter()
+            .filter_map(|input| self.prevout(&input.previous_output))
+            .map(|prevout| prevout.value)
+            .sum();
+        let output: Amount = tx.output.iter().map(|output| output.value).sum();
+        input.checked_sub(output).unwrap_or(Amount::ZERO)
+    }
+
+    fn add_to_mempool(&mut self, tx: Transaction) {
+        let fee = self.fee(&tx);
+        let time = u64::from(self.tip().header.time);
+        self.mempool.push(MempoolTx { tx, fee, time });
+    }
+
+    fn mine(&mut self, txs: Vec<Transaction>) -> Block {
+        let height = self.chain.len();
+        let mined: HashSet<_> = txs.iter().map(Transaction::txid).collect();
+        let fees: Amount = txs.iter().map(|tx| self.fee(tx)).sum();
+        self.mempool.retain(|entry| !mined.contains(&entry.tx.txid()));
+        self.mined += 1;
+        let tag = PushBytesBuf::try_from(format!("/mock {}/", self.mined).into_bytes())
+            .expect("short coinbase tag");
+        let coinbase = Transaction {
+            version: transaction::Version::ONE,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: Builder::new().push_int(height as i64).push_slice(tag).into_script(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: subsidy(height) + fees,
+                script_pubkey: ScriptBuf::from_bytes(MOCK_PAYOUT.to_vec()),
+            }],
+        };
+        let tip = self.tip().header;
+        let mut block = Block {
+            header: Header {
+                version: Version::from_consensus(0x2000_0000),
+                prev_blockhash: tip.block_hash(),
+                merkle_root: Hash::all_zeros(),
+                time: tip.time + 600,
+                bits: tip.bits,
+                nonce: 0,
+            },
+            txdata: std::iter::once(coinbase).chain(txs).collect(),
+        };
+        block.header.merkle_root = block.compute_merkle_root().expect("the block has a coinbase");
+        self.chain.push(block.clone());
+        block
+    }
+
+    fn header_info(&self, height: usize, block: &Block) -> GetBlockHeaderResult {
+        let active = self.in_active_chain(height, block);
+        let header = &block.header;
+        let ancestors = &self.chain[..height.min(self.chain.len())];
+        let mut times: Vec<_> = ancestors
+            .iter()
+            .rev()
+            .take(10)
+            .map(|block| block.header.time)
+            .chain(std::iter::once(header.time))
+            .collect();
+        times.sort_unstable();
+        let chainwork = ancestors
+            .iter()
+            .map(|block| block.header.work())
+            .fold(header.work(), |total, work| total + work);
+        GetBlockHeaderResult {
+            hash: block.block_hash(),
+            confirmations: match active {
+                true => (self.chain.len() - height) as i32,
+                false => -1,
+            },
+            height,
+            version: header.version,
+            version_hex: Some(header.version.to_consensus().to_be_bytes().to_vec()),
+            merkle_root: header.merkle_root,
+            time: header.time as usize,
+            median_time: Some(times[times.len() / 2] as usize),
+            nonce: header.nonce,
+            bits: format!("{:08x}", header.bits.to_consensus()),
+            difficulty: header.difficulty_float(),
+            chainwork: chainwork.to_be_bytes().to_vec(),
+            n_tx: block.txdata.len(),
+            previous_block_hash: (height > 0).then_some(header.prev_blockhash),
+            next_block_hash: match active {
+                true => self.chain.get(height + 1).map(Block::block_hash),
+                false => None,
+            },
+        }
+    }
+
+    /// The statistics bitcoind computes for a block, feerates rounded down to whole sat/vB.
+    fn block_stats(&self, height: usize, block: &Block) -> GetBlockStatsResult {
+        let txs = &block.txdata[1..];
+        let fees: Vec<_> = txs
+            .iter()
+            .map(|tx| (self.fee(tx).to_sat(), tx.vsize() as u64))
+            .collect();
+        let summary = BlockFees::from_fees(fees.iter().copied());
+        let sat = |value: f64| Amount::from_sat(value as u64);
+        let feerates = fees.iter().map(|(fee, vsize)| fee / (*vsize).max(1));
+        let sizes: Vec<_> = txs.iter().map(|tx| tx.total_size() as u32).collect();
+        let mut sorted_sizes = sizes.clone();
+        sorted_sizes.sort_unstable();
+        GetBlockStatsResult {
+            avg_fee: Amount::from_sat(fees.iter().map(|(fee, _)| fee).sum::<u64>() / (fees.len().max(1) as u64)),
+            avg_fee_rate: Amount::from_sat(
+                fees.iter().map(|(fee, _)| fee).sum::<u64>()
+                    / fees.iter().map(|(_, vsize)| vsize).sum::<u64>().max(1),
+            ),
+            avg_tx_size: sizes.iter().sum::<u32>() / (sizes.len().max(1) as u32),
+            block_hash: block.block_hash(),
+            fee_rate_percentiles: FeeRatePercentiles {
+                fr_10th: sat(summary.span[1]),
+                fr_25th: sat(summary.span[2]),
+                fr_50th: sat(summary.span[3]),
+                fr_75th: sat(summary.span[4]),
+                fr_90th: sat(summary.span[5]),
+            },
+            height: height as u64,
+            ins: txs.iter().map(|tx| tx.input.len()).sum(),
+            max_fee: Amount::from_sat(fees.iter().map(|(fee, _)| *fee).max().unwrap_or_default()),
+            max_fee_rate: Amount::from_sat(feerates.clone().max().unwrap_or_default()),
+            max_tx_size: sizes.iter().copied().max().unwrap_or_default(),
+            median_fee: Amount::from_sat({
+                let mut sorted: Vec<_> = fees.iter().map(|(fee, _)| *fee).collect();
+                sorted.sort_unstable();
+                sorted.get(sorted.len() / 2).copied().unwrap_or_default()
+            }),
+            median_time: self.header_info(height, block).median_time.unwrap_or_default() as u64,
+            median_tx_size: sorted_sizes.get(sorted_sizes.len() / 2).copied().unwrap_or_default(),
+            min_fee: Amount::from_sat(fees.iter().map(|(fee, _)| *fee).min().unwrap_or_default()),
+            min_fee_rate: Amount::from_sat(feerates.min().unwrap_or_default()),
+            min_tx_size: sizes.iter().copied().min().unwrap_or_default(),
+            outs: block.txdata.iter().map(|tx| tx.output.len()).sum(),
+            subsidy: subsidy(height),
+            sw_total_size: 0,
+            sw_total_weight: 0,
+            sw_txs: 0,
+            time: u64::from(block.header.time),
+            total_out: txs.iter().flat_map(|tx| &tx.output).map(|output| output.value).sum(),
+            total_size: sizes.iter().map(|size| *size as usize).sum(),
+            total_weight: txs.iter().map(|tx| tx.weight().to_wu() as usize).sum(),
+            total_fee: Amount::from_sat(fees.iter().map(|(fee, _)| fee).sum()),
+            txs: block.txdata.len(),
+            utxo_increase: 0,
+            utxo_size_inc: 0,
+        }
+    }
+
+    fn transaction_info(&self, tx: &Transaction, block: Option<(usize, &Block)>) -> GetRawTransactionResult {
+        GetRawTransactionResult {
+            in_active_chain: None,
+            hex: serialize(tx),
+            txid: tx.txid(),
+            hash: tx.wtxid(),
+            size: tx.total_size(),
+            vsize: tx.vsize(),
+            version: tx.version.0 as u32,
+            locktime: tx.lock_time.to_consensus_u32(),
+            vin: tx
+                .input
+                .iter()
+                .map(|input| GetRawTransactionResultVin {
+                    sequence: input.sequence.0,
+                    coinbase: tx.is_coinbase().then(|| input.script_sig.to_bytes()),
+                    txid: (!tx.is_coinbase()).then_some(input.previous_output.txid),
+                    vout: (!tx.is_coinbase()).then_some(input.previous_output.vout),
+                    script_sig: (!tx.is_coinbase()).then(|| GetRawTransactionResultVinScriptSig {
+                        asm: input.script_sig.to_asm_string(),
+                        hex: input.script_sig.to_bytes(),
+                    }),
+                    txinwitness: (!input.witness.is_empty()).then(|| input.witness.to_vec()),
+                })
+                .collect(),
+            vout: tx
+                .output
+                .iter()
+                .enumerate()
+                .map(|(n, output)| GetRawTransactionResultVout {
+                    value: output.value,
+                    n: n as u32,
+                    script_pub_key: GetRawTransactionResultVoutScriptPubKey {
+                        asm: output.script_pubkey.to_asm_string(),
+                        hex: output.script_pubkey.to_bytes(),
+                        req_sigs: None,
+                        type_: None,
+                        addresses: vec![],
+                        address: None,
+                    },
+                })
+                .collect(),
+            blockhash: block.map(|(_, block)| block.block_hash()),
+            confirmations: block.map(|(height, _)| (self.chain.len() - height) as u32),
+            time: block.map(|(_, block)| block.header.time as usize),
+            blocktime: block.map(|(_, block)| block.header.time as usize),
+        }
+    }
+
+    fn mempool_entry(&self, entry: &MempoolTx) -> GetMempoolEntryResult {
+        let txid = entry.tx.txid();
+        let ancestors = self.related(txid, |entry| {
+            entry.tx.input.iter().map(|input| input.previous_output.txid).collect()
+        });
+        let descendants = self.related(txid, |candidate| {
+            self.mempool
+                .iter()
+                .filter(|child| child.tx.input.iter().any(|input| input.previous_output.txid == candidate.tx.txid()))
+                .map(|child| child.tx.txid())
+                .collect()
+        });
+        let size = |entries: &[&MempoolTx]| entries.iter().map(|entry| entry.tx.vsize() as u64).sum::<u64>();
+        let fee = |entries: &[&MempoolTx]| entries.iter().map(|entry| entry.fee).sum::<Amount>();
+        let parents: Vec<_> = entry
+            .tx
+            .input
+            .iter()
+            .map(|input| input.previous_output.txid)
+            .filter(|parent| self.mempool.iter().any(|entry| entry.tx.txid() == *parent))
+            .collect();
+        let children: Vec<_> = self
+            .mempool
+            .iter()
+            .filter(|child| child.tx.input.iter().any(|input| input.previous_output.txid == txid))
+            .map(|child| child.tx.txid())
+            .collect();
+        GetMempoolEntryResult {
+            vsize: entry.tx.vsize() as u64,
+            weight: Some(entry.tx.weight().to_wu()),
+            time: entry.time,
+            height: self.tip_height() as u64,
+            descendant_count: descendants.len() as u64,
+            descendant_size: size(&descendants),
+            ancestor_count: ancestors.len() as u64,
+            ancestor_size: size(&ancestors),
+            wtxid: Txid::from_raw_hash(entry.tx.wtxid().to_raw_hash()),
+            fees: GetMempoolEntryResultFees {
+                base: entry.fee,
+                modified: entry.fee,
+                ancestor: fee(&ancestors),
+                descendant: fee(&descendants),
+            },
+            depends: parents,
+            spent_by: children,
+            bip125_replaceable: false,
+            unbroadcast: Some(false),
+        }
+    }
+
+    /// `txid` and every mempool transaction reachable from it through `next`.
+    fn related<F>(&self, txid: Txid, next: F) -> Vec<&MempoolTx>
+    where
+        F: Fn(&MempoolTx) -> Vec<Txid>,
+    {
+        let mut found: Vec<&MempoolTx> = Vec::new();
+        let mut queue = VecDeque::from([txid]);
+        while let Some(txid) = queue.pop_front() {
+            if found.iter().any(|entry| entry.tx.txid() == txid) {
+                continue;
+            }
+            if let Some(entry) = self.mempool.iter().find(|entry| entry.tx.txid() == txid) {
+                queue.extend(next(entry));
+                found.push(entry);
+            }
+        }
+        found
+    }
+}
+
+fn subsidy(height: usize) -> Amount {
+    Amount::from_sat(50 * 100_000_000u64.checked_shr((height / 210_000) as u32).unwrap_or_default())
+}
+
+fn param<T: for<'de> Deserialize<'de>>(params: &[Value], index: usize) -> Result<T, RpcError> {
+    optional_param(params, index)?
+        .ok_or_else(|| RpcError::new(RPC_INVALID_PARAMETER, format!("missing parameter {index}")))
+}
+
+fn optional_param<T: for<'de> Deserialize<'de>>(params: &[Value], index: usize) -> Result<Option<T>, RpcError> {
+    match params.get(index) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => serde_json::from_value(value.clone())
+            .map(Some)
+            .map_err(|e| RpcError::new(RPC_INVALID_PARAMETER, format!("invalid parameter {index}: {e}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoincore_rpc::json::{GetBlockHeaderResult, GetBlockStatsResult, GetMempoolEntryResult, GetRawTransactionResult};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_serves_fixture_chain() {
+        let mock = MockBitcoind::from_fixture("mainnet").await;
+        let client = mock.client().await;
+        assert_eq!(client.call::<u64>("getblockcount", &[]).await.unwrap(), 2);
+        let hash: BlockHash = client.call("getblockhash", &[json!(1)]).await.unwrap();
+        assert_eq!(hash.to_string(), "00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048");
+        let raw: String = client.call("getblock", &[json!(hash), json!(0)]).await.unwrap();
+        assert_eq!(raw, serialize(&mock.block(1)).to_lower_hex_string());
+
+        let coinbase = mock.block(2).txdata[0].txid();
+        let info: GetRawTransactionResult = client
+            .call("getrawtransaction", &[json!(coinbase), json!(true)])
+            .await
+            .unwrap();
+        assert_eq!(info.transaction().unwrap().txid(), coinbase);
+        let header: GetBlockHeaderResult = client
+            .call("getblockheader", &[json!(info.blockhash.unwrap())])
+            .await
+            .unwrap();
+        assert_eq!((header.height, header.confirmations), (2, 1));
+
+        mock.fail("getrawtransaction", RPC_INVALID_PARAMETER, "txid must be hexadecimal", 1);
+        assert!(client.call::<String>("getrawtransaction", &[json!("zz")]).await.is_err());
+        let missing = client
+            .call::<String>("getrawtransaction", &[json!(Txid::all_zeros())])
+            .await
+            .unwrap_err();
+        assert!(missing.is_not_found());
+        assert_eq!(mock.calls("getrawtransaction"), 3);
+    }
+
+    #[tokio::test]
+    async fn test_scripted_blocks_mempool_and_reorg() {
+        let mock = MockBitcoind::from_fixture("mainnet").await;
+        let client = mock.client().await;
+        let funding = mock.mine(vec![]);
+        let payout = OutPoint::new(funding.txdata[0].txid(), 0);
+        let tx = spend(
+            &[payout],
+            vec![TxOut {
+                value: Amount::from_sat(5_000_000_000 - 10_000),
+                script_pubkey: ScriptBuf::from_bytes(MOCK_PAYOUT.to_vec()),
+            }],
+        );
+        mock.add_to_mempool(tx.clone());
+        let txid = tx.txid();
+        let entry: GetMempoolEntryResult = client.call("getmempoolentry", &[json!(txid)]).await.unwrap();
+        assert_eq!(entry.fees.base, Amount::from_sat(10_000));
+
+        let block = mock.mine_mempool();
+        assert_eq!(block.txdata[0].output[0].value, Amount::from_sat(5_000_010_000));
+        let stats: GetBlockStatsResult = client.call("getblockstats", &[json!(4)]).await.unwrap();
+        assert_eq!(stats.total_fee, Amount::from_sat(10_000));
+
+        let orphaned = block.block_hash();
+        let replacement = mock.reorg(1, 2);
+        assert_eq!(mock.tip(), (5, replacement[1].block_hash()));
+        let hash: BlockHash = client.call("getblockhash", &[json!(4)]).await.unwrap();
+        assert_eq!(hash, replacement[0].block_hash());
+        let stale: GetBlockHeaderResult = client.call("getblockheader", &[json!(orphaned)]).await.unwrap();
+        assert_eq!(stale.confirmations, -1);
+        let mempool: Vec<Txid> = client.call("getrawmempool", &[]).await.unwrap();
+        assert_eq!(mempool, vec![txid]);
+    }
+}
@@ -2,6 +2,10 @@ use crate::{configure::AppConfig, error::AppResult};
 
 pub mod database;
 pub mod bitcoin;
+pub mod blk;
+#[cfg(test)]
+pub mod mock;
+pub mod source;
 
 
 pub trait ClientBuilder: Sized {
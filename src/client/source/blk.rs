@@ -0,0 +1,97 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bitcoincore_rpc::bitcoin::{Amount, Block, BlockHash, Network, OutPoint, Txid};
+
+use crate::client::blk::BlkIndex;
+use crate::client::source::{BlockSource, ChainHeader, SourceTransaction};
+use crate::constant::BlockHeight;
+use crate::error::{AppError, AppResult};
+use crate::service::ingest::BlockFees;
+use crate::service::mempool::MempoolEntry;
+
+/// Reads the blocks covered by Bitcoin Core's block files from disk and everything else,
+/// the tip and the mempool included, from `node`.
+pub struct BlkSource {
+    index: Arc<BlkIndex>,
+    node: Arc<dyn BlockSource>,
+}
+
+impl BlkSource {
+    pub fn new(index: Arc<BlkIndex>, node: Arc<dyn BlockSource>) -> Self {
+        Self { index, node }
+    }
+}
+
+#[async_trait]
+impl BlockSource for BlkSource {
+    async fn network(&self) -> AppResult<Network> {
+        self.node.network().await
+    }
+
+    async fn tip(&self) -> AppResult<(BlockHeight, BlockHash)> {
+        self.node.tip().await
+    }
+
+    async fn block_hash(&self, height: BlockHeight) -> AppResult<Option<BlockHash>> {
+        match self.index.hash(height) {
+            Some(hash) => Ok(Some(hash)),
+            None => self.node.block_hash(height).await,
+        }
+    }
+
+    async fn header(&self, hash: &BlockHash) -> AppResult<Option<ChainHeader>> {
+        self.node.header(hash).await
+    }
+
+    async fn block(&self, hash: &BlockHash) -> AppResult<Option<Block>> {
+        self.node.block(hash).await
+    }
+
+    async fn transaction(&self, txid: &Txid) -> AppResult<Option<SourceTransaction>> {
+        self.node.transaction(txid).await
+    }
+
+    async fn unspent(&self, outpoints: &[OutPoint]) -> AppResult<Vec<bool>> {
+        self.node.unspent(outpoints).await
+    }
+
+    async fn mempool(&self) -> AppResult<Vec<Txid>> {
+        self.node.mempool().await
+    }
+
+    async fn mempool_entries(&self) -> AppResult<Vec<MempoolEntry>> {
+        self.node.mempool_entries().await
+    }
+
+    async fn mempool_entry(&self, txid: &Txid) -> AppResult<Option<MempoolEntry>> {
+        self.node.mempool_entry(txid).await
+    }
+
+    async fn mempool_entries_of(&self, txids: &[Txid]) -> AppResult<Vec<MempoolEntry>> {
+        self.node.mempool_entries_of(txids).await
+    }
+
+    async fn mempool_min_fee(&self) -> AppResult<Amount> {
+        self.node.mempool_min_fee().await
+    }
+
+    async fn estimate_fee(&self, target: u16) -> AppResult<Option<Amount>> {
+        self.node.estimate_fee(target).await
+    }
+
+    async fn block_fees(&self, height: BlockHeight) -> AppResult<BlockFees> {
+        self.node.block_fees(height).await
+    }
+
+    async fn block_at(&self, height: BlockHeight) -> AppResult<Option<Block>> {
+        if self.index.hash(height).is_none() {
+            return self.node.block_at(height).await;
+        }
+        let index = self.index.clone();
+        tokio::task::spawn_blocking(move || index.read_block(height))
+            .await
+            .map_err(|e| AppError::UnknownError(e.into()))?
+            .map(Some)
+    }
+}
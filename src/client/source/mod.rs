@@ -0,0 +1,215 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bitcoincore_rpc::bitcoin::block::Header;
+use bitcoincore_rpc::bitcoin::blockdata::constants::genesis_block;
+use bitcoincore_rpc::bitcoin::{Amount, Block, BlockHash, Network, OutPoint, Transaction, Txid};
+use config::ConfigError;
+
+use crate::client::bitcoin::{BitcoinClient, BitcoinClientExt};
+use crate::configure::bitcoin::{BitcoinConfig, BlockFeesSource, BlockSourceKind};
+use crate::constant::BlockHeight;
+use crate::error::{AppError, AppResult};
+use crate::service::ingest::BlockFees;
+use crate::service::mempool::MempoolEntry;
+
+pub mod blk;
+pub mod rest;
+pub mod rpc;
+
+pub use blk::BlkSource;
+pub use rest::RestSource;
+pub use rpc::RpcSource;
+
+/// A header together with its height, stale headers included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainHeader {
+    pub height: BlockHeight,
+    pub header: Header,
+}
+
+/// A transaction and the block holding it, `None` while it is unconfirmed.
+#[derive(Debug, Clone)]
+pub struct SourceTransaction {
+    pub tx: Transaction,
+    pub block_hash: Option<BlockHash>,
+}
+
+/// Where the indexer and the API read the chain and the mempool from.
+///
+/// Lookups of something the source does not know return `None` rather than an error, so
+/// callers do not have to tell a missing block apart from an unreachable node. Sources
+/// that cannot answer a call at all, like the REST interface for fee estimates, return
+/// an error.
+#[async_trait]
+pub trait BlockSource: Send + Sync {
+    /// The chain the node runs.
+    async fn network(&self) -> AppResult<Network>;
+
+    /// Height and hash of the best block.
+    async fn tip(&self) -> AppResult<(BlockHeight, BlockHash)>;
+
+    /// Hash of the best chain block at `height`, `None` above the tip.
+    async fn block_hash(&self, height: BlockHeight) -> AppResult<Option<BlockHash>>;
+
+    async fn header(&self, hash: &BlockHash) -> AppResult<Option<ChainHeader>>;
+
+    async fn block(&self, hash: &BlockHash) -> AppResult<Option<Block>>;
+
+    /// A confirmed or mempool transaction. Confirmed transactions outside the wallet and
+    /// the mempool need the node's `txindex`.
+    async fn transaction(&self, txid: &Txid) -> AppResult<Option<SourceTransaction>>;
+
+    /// Whether each of `outpoints` is in the UTXO set once the spends of the mempool are
+    /// applied. Unknown and unspendable outputs are not.
+    async fn unspent(&self, outpoints: &[OutPoint]) -> AppResult<Vec<bool>>;
+
+    async fn mempool(&self) -> AppResult<Vec<Txid>>;
+
+    /// Every mempool entry. Transactions that leave the mempool while they are read are
+    /// skipped.
+    async fn mempool_entries(&self) -> AppResult<Vec<MempoolEntry>>;
+
+    /// A mempool entry, `None` once it left the mempool.
+    async fn mempool_entry(&self, txid: &Txid) -> AppResult<Option<MempoolEntry>>;
+
+    /// The entries of `txids` still in the mempool.
+    async fn mempool_entries_of(&self, txids: &[Txid]) -> AppResult<Vec<MempoolEntry>> {
+        let mut entries = Vec::with_capacity(txids.len());
+        for txid in txids {
+            entries.extend(self.mempool_entry(txid).await?);
+        }
+        Ok(entries)
+    }
+
+    /// Feerate per kvB below which the node's mempool rejects transactions.
+    async fn mempool_min_fee(&self) -> AppResult<Amount>;
+
+    /// Feerate per kvB expected to confirm within `target` blocks, `None` when the node
+    /// has not seen enough blocks to tell.
+    async fn estimate_fee(&self, target: u16) -> AppResult<Option<Amount>>;
+
+    /// Fee statistics of the best chain block at `height`.
+    async fn block_fees(&self, height: BlockHeight) -> AppResult<BlockFees>;
+
+    async fn header_at(&self, height: BlockHeight) -> AppResult<Option<ChainHeader>> {
+        match self.block_hash(height).await? {
+            Some(hash) => self.header(&hash).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn block_at(&self, height: BlockHeight) -> AppResult<Option<Block>> {
+        match self.block_hash(height).await? {
+            Some(hash) => self.block(&hash).await,
+            None => Ok(None),
+        }
+    }
+}
+
+/// Connects to the node through the interface picked in the configuration.
+pub async fn build_from_config(config: &BitcoinConfig) -> AppResult<Arc<dyn BlockSource>> {
+    let source: Arc<dyn BlockSource> = match config.block_source {
+        BlockSourceKind::Rpc => {
+            let client = BitcoinClient::build_from_config(config).await?;
+            Arc::new(RpcSource::new(Arc::new(client)))
+        }
+        BlockSourceKind::Rest if config.block_fees == BlockFeesSource::Rpc => {
+            return Err(AppError::ConfigError(config::ConfigError::Message(
+                "the REST interface has no block statistics, set block_fees to index".to_string(),
+            )))
+        }
+        BlockSourceKind::Rest if config.zmq_rawtx.is_some() => {
+            return Err(AppError::ConfigError(config::ConfigError::Message(
+                "the REST interface can only list the whole mempool, which is too slow for every \
+                 zmq_rawtx notification, unset zmq_rawtx or use the rpc block_source"
+                    .to_string(),
+            )))
+        }
+        BlockSourceKind::Rest => Arc::new(RestSource::new(config)?),
+    };
+    Ok(source)
+}
+
+/// Checks that the node runs `network`, by the chain it reports and by its genesis block,
+/// which also tells a custom signet apart from the default one.
+pub async fn verify_network(source: &dyn BlockSource, network: Network) -> AppResult {
+    let mismatch = |found: &dyn std::fmt::Display| {
+        AppError::ConfigError(ConfigError::Message(format!(
+            "bitcoin.network is {network} but the node runs {found}"
+        )))
+    };
+    let found = source.network().await?;
+    if found != network {
+        return Err(mismatch(&found));
+    }
+    let genesis = genesis_block(network).block_hash();
+    match source.block_hash(0).await? {
+        Some(hash) if hash != genesis => Err(mismatch(&format!("a {network} chain with genesis block {hash}"))),
+        _ => Ok(()),
+    }
+}
+
+/// The error of a call `source` cannot answer.
+fn unsupported(source: &str, call: &str) -> AppError {
+    AppError::UnknownError(anyhow::anyhow!("{call} is not available through the {source}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::MockBitcoind;
+
+    #[tokio::test]
+    async fn test_verify_network() {
+        let mock = MockBitcoind::from_fixture("mainnet").await;
+        let source = mock.source().await;
+        verify_network(&*source, Network::Bitcoin).await.unwrap();
+        let error = verify_network(&*source, Network::Testnet).await.unwrap_err();
+        assert!(matches!(error, AppError::ConfigError(_)));
+    }
+
+    #[tokio::test]
+    async fn test_unspent_applies_mempool_spends() {
+        let mock = MockBitcoind::from_fixture("mainnet").await;
+        let mut config = mock.config();
+        config.block_fees = BlockFeesSource::Index;
+        let sources: [Arc<dyn BlockSource>; 2] = [mock.source().await, Arc::new(RestSource::new(&config).unwrap())];
+        let spent = OutPoint::new(mock.block(1).txdata[0].txid(), 0);
+        let outpoints = [spent, OutPoint::new(mock.block(2).txdata[0].txid(), 0), OutPoint::new(spent.txid, 1)];
+        mock.add_to_mempool(crate::client::mock::spend(&[spent], vec![]));
+        for source in sources {
+            assert_eq!(source.unspent(&outpoints).await.unwrap(), [false, true, false]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mempool_entries_list_spends() {
+        let mock = MockBitcoind::from_fixture("mainnet").await;
+        let mut config = mock.config();
+        config.block_fees = BlockFeesSource::Index;
+        let sources: [Arc<dyn BlockSource>; 2] = [mock.source().await, Arc::new(RestSource::new(&config).unwrap())];
+        let spent = OutPoint::new(mock.block(1).txdata[0].txid(), 0);
+        let tx = crate::client::mock::spend(&[spent], vec![]);
+        mock.add_to_mempool(tx.clone());
+        for source in sources {
+            assert_eq!(source.mempool_entries().await.unwrap()[0].spends, [spent]);
+            assert_eq!(source.mempool_entry(&tx.txid()).await.unwrap().unwrap().spends, [spent]);
+            assert_eq!(source.mempool_entries_of(&[tx.txid()]).await.unwrap()[0].spends, [spent]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rest_rejects_zmq_rawtx() {
+        let mock = MockBitcoind::from_fixture("mainnet").await;
+        let mut config = mock.config();
+        config.block_source = BlockSourceKind::Rest;
+        config.block_fees = BlockFeesSource::Index;
+        assert!(build_from_config(&config).await.is_ok());
+        config.zmq_rawtx = Some("tcp://127.0.0.1:28333".to_string());
+        assert!(matches!(
+            build_from_config(&config).await,
+            Err(AppError::ConfigError(_))
+        ));
+    }
+}
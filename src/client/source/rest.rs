@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::{StreamExt, TryStreamExt};
+use bitcoincore_rpc::bitcoin::block::{Header, Version};
+use bitcoincore_rpc::bitcoin::consensus::encode;
+use bitcoincore_rpc::bitcoin::hashes::Hash;
+use bitcoincore_rpc::bitcoin::hex::FromHex;
+use bitcoincore_rpc::bitcoin::{Amount, Block, BlockHash, CompactTarget, Network, OutPoint, Transaction, Txid};
+use bitcoincore_rpc::json::{GetBlockHeaderResult, GetMempoolEntryResult, GetMempoolInfoResult};
+use hyper::{Method, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::client::bitcoin::{authority, BitcoinRpcError, NodeHttp};
+use crate::client::source::rpc::chain_network;
+use crate::client::source::{unsupported, BlockSource, ChainHeader, SourceTransaction};
+use crate::configure::bitcoin::BitcoinConfig;
+use crate::constant::BlockHeight;
+use crate::error::{AppError, AppResult};
+use crate::service::ingest::BlockFees;
+use crate::service::mempool::MempoolEntry;
+
+const SOURCE: &str = "REST interface";
+
+/// Outpoints bitcoind accepts in a single `getutxos` request.
+const MAX_GETUTXOS_OUTPOINTS: usize = 15;
+
+#[derive(Debug, Deserialize)]
+struct ChainInfo {
+    chain: String,
+    blocks: BlockHeight,
+    bestblockhash: BlockHash,
+}
+
+/// The part of `getutxos` the explorer reads: one `0` or `1` per requested outpoint.
+#[derive(Debug, Deserialize)]
+struct Utxos {
+    bitmap: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RestTransaction {
+    hex: String,
+    blockhash: Option<BlockHash>,
+}
+
+/// Reads from Bitcoin Core's unauthenticated REST interface, enabled with `-rest`. It is
+/// served on the RPC port and needs no credentials, but has no fee estimates nor block
+/// statistics.
+pub struct RestSource {
+    http: NodeHttp,
+    timeout: Duration,
+    /// Requests sent at once when reading many transactions.
+    connections: usize,
+}
+
+impl RestSource {
+    pub fn new(config: &BitcoinConfig) -> AppResult<Self> {
+        Ok(Self {
+            http: NodeHttp::new(&authority(&config.get_host(), config.network)?, config.rpc_connections),
+            timeout: Duration::from_secs(config.rpc_timeout_secs),
+            connections: config.rpc_connections.max(1),
+        })
+    }
+
+    /// GETs `/rest/{path}`, `None` when the node answers 404.
+    async fn get(&self, path: &str) -> AppResult<Option<Vec<u8>>> {
+        let path = format!("/rest/{path}");
+        let response = tokio::time::timeout(self.timeout, self.http.request(Method::GET, &path, &[], vec![]))
+            .await
+            .map_err(|_| BitcoinRpcError::Timeout(self.timeout))??;
+        match response.status() {
+            StatusCode::OK => Ok(Some(response.into_body().to_vec())),
+            StatusCode::NOT_FOUND => Ok(None),
+            status => Err(AppError::BitcoinRpcError(BitcoinRpcError::Http {
+                status: status.as_u16(),
+                body: String::from_utf8_lossy(response.body()).trim().to_string(),
+            })),
+        }
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, path: &str) -> AppResult<Option<T>> {
+        self.get(path)
+            .await?
+            .map(|body| serde_json::from_slice(&body).map_err(|e| AppError::UnknownError(e.into())))
+            .transpose()
+    }
+
+    /// The raw transaction `txid`, `None` when the node does not know it.
+    async fn raw_transaction(&self, txid: &Txid) -> AppResult<Option<Transaction>> {
+        let Some(body) = self.get(&format!("tx/{txid}.bin")).await? else {
+            return Ok(None);
+        };
+        encode::deserialize(&body).map(Some).map_err(|e| AppError::UnknownError(e.into()))
+    }
+
+    /// Completes `entries` with the outpoints they spend. The REST interface has no
+    /// batches, so the raw transactions are read one by one. Transactions that left the
+    /// mempool since are skipped.
+    async fn with_spends(&self, entries: Vec<(Txid, GetMempoolEntryResult)>) -> AppResult<Vec<MempoolEntry>> {
+        let txids: Vec<Txid> = entries.iter().map(|(txid, _)| *txid).collect();
+        let txs: Vec<Option<Transaction>> = futures::stream::iter(txids)
+            .map(|txid| async move { self.raw_transaction(&txid).await })
+            .buffered(self.connections)
+            .try_collect()
+            .await?;
+        Ok(entries
+            .iter()
+            .zip(txs)
+            .filter_map(|((txid, entry), tx)| Some(MempoolEntry::from_rpc(*txid, entry, &tx?)))
+            .collect())
+    }
+
+    async fn get_existing<T: DeserializeOwned>(&self, path: &str) -> AppResult<T> {
+        self.get_json(path)
+            .await?
+            .ok_or_else(|| AppError::UnknownError(anyhow::anyhow!("REST endpoint {path} not found, is -rest set?")))
+    }
+}
+
+#[async_trait]
+impl BlockSource for RestSource {
+    async fn network(&self) -> AppResult<Network> {
+        let info: ChainInfo = self.get_existing("chaininfo.json").await?;
+        chain_network(&info.chain)
+    }
+
+    async fn tip(&self) -> AppResult<(BlockHeight, BlockHash)> {
+        let info: ChainInfo = self.get_existing("chaininfo.json").await?;
+        Ok((info.blocks, info.bestblockhash))
+    }
+
+    async fn block_hash(&self, height: BlockHeight) -> AppResult<Option<BlockHash>> {
+        let Some(body) = self.get(&format!("blockhashbyheight/{height}.hex")).await? else {
+            return Ok(None);
+        };
+        let hash = BlockHash::from_str(String::from_utf8_lossy(&body).trim())
+            .map_err(|e| AppError::UnknownError(e.into()))?;
+        Ok(Some(hash))
+    }
+
+    async fn header(&self, hash: &BlockHash) -> AppResult<Option<ChainHeader>> {
+        let headers: Vec<GetBlockHeaderResult> = self
+            .get_json(&format!("headers/{hash}.json?count=1"))
+            .await?
+            .unwrap_or_default();
+        let Some(info) = headers.into_iter().next() else {
+            return Ok(None);
+        };
+        let bits = u32::from_str_radix(&info.bits, 16).map_err(|e| AppError::UnknownError(e.into()))?;
+        let header = Header {
+            version: Version::from_consensus(info.version.to_consensus()),
+            prev_blockhash: info.previous_block_hash.unwrap_or_else(BlockHash::all_zeros),
+            merkle_root: info.merkle_root,
+            time: info.time as u32,
+            bits: CompactTarget::from_consensus(bits),
+            nonce: info.nonce,
+        };
+        if header.block_hash() != *hash {
+            return Err(AppError::UnknownError(anyhow::anyhow!("the REST header of {hash} does not hash to it")));
+        }
+        Ok(Some(ChainHeader {
+            height: info.height as BlockHeight,
+            header,
+        }))
+    }
+
+    async fn block(&self, hash: &BlockHash) -> AppResult<Option<Block>> {
+        let Some(body) = self.get(&format!("block/{hash}.bin")).await? else {
+            return Ok(None);
+        };
+        let block = tokio::task::spawn_blocking(move || encode::deserialize::<Block>(&body))
+            .await
+            .map_err(|e| AppError::UnknownError(e.into()))?
+            .map_err(|e| AppError::UnknownError(e.into()))?;
+        Ok(Some(block))
+    }
+
+    async fn transaction(&self, txid: &Txid) -> AppResult<Option<SourceTransaction>> {
+        let Some(found): Option<RestTransaction> = self.get_json(&format!("tx/{txid}.json")).await? else {
+            return Ok(None);
+        };
+        let bytes = Vec::<u8>::from_hex(&found.hex).map_err(|e| AppError::UnknownError(e.into()))?;
+        let tx: Transaction = encode::deserialize(&bytes).map_err(|e| AppError::UnknownError(e.into()))?;
+        Ok(Some(SourceTransaction {
+            tx,
+            block_hash: found.blockhash,
+        }))
+    }
+
+    async fn unspent(&self, outpoints: &[OutPoint]) -> AppResult<Vec<bool>> {
+        let mut unspent = Vec::with_capacity(outpoints.len());
+        for chunk in outpoints.chunks(MAX_GETUTXOS_OUTPOINTS) {
+            let path: Vec<_> = chunk.iter().map(|outpoint| format!("{}-{}", outpoint.txid, outpoint.vout)).collect();
+            let utxos: Utxos = self
+                .get_existing(&format!("getutxos/checkmempool/{}.json", path.join("/")))
+                .await?;
+            if utxos.bitmap.len() != chunk.len() {
+                return Err(BitcoinRpcError::InvalidResponse(format!("getutxos bitmap {}", utxos.bitmap)).into());
+            }
+            unspent.extend(utxos.bitmap.chars().map(|bit| bit == '1'));
+        }
+        Ok(unspent)
+    }
+
+    async fn mempool(&self) -> AppResult<Vec<Txid>> {
+        self.get_existing("mempool/contents.json?verbose=false").await
+    }
+
+    async fn mempool_entries(&self) -> AppResult<Vec<MempoolEntry>> {
+        let entries: HashMap<Txid, GetMempoolEntryResult> = self.get_existing("mempool/contents.json").await?;
+        self.with_spends(entries.into_iter().collect()).await
+    }
+
+    /// The REST interface has no single entry lookup, so this downloads the whole mempool.
+    /// [`super::build_from_config`] refuses `zmq_rawtx` with this source for that reason.
+    async fn mempool_entry(&self, txid: &Txid) -> AppResult<Option<MempoolEntry>> {
+        Ok(self.mempool_entries_of(&[*txid]).await?.pop())
+    }
+
+    async fn mempool_entries_of(&self, txids: &[Txid]) -> AppResult<Vec<MempoolEntry>> {
+        let entries: HashMap<Txid, GetMempoolEntryResult> = self.get_existing("mempool/contents.json").await?;
+        let entries = entries.into_iter().filter(|(txid, _)| txids.contains(txid)).collect();
+        self.with_spends(entries).await
+    }
+
+    async fn mempool_min_fee(&self) -> AppResult<Amount> {
+        let info: GetMempoolInfoResult = self.get_existing("mempool/info.json").await?;
+        Ok(info.mempool_min_fee)
+    }
+
+    async fn estimate_fee(&self, _target: u16) -> AppResult<Option<Amount>> {
+        Err(unsupported(SOURCE, "estimatesmartfee"))
+    }
+
+    async fn block_fees(&self, _height: BlockHeight) -> AppResult<BlockFees> {
+        Err(unsupported(SOURCE, "getblockstats"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoincore_rpc::bitcoin::{OutPoint, ScriptBuf, TxOut};
+
+    use super::*;
+    use crate::client::mock::{self, MockBitcoind};
+
+    #[tokio::test]
+    async fn test_reads_mock_node() {
+        let mock = MockBitcoind::from_fixture("mainnet").await;
+        let source = RestSource::new(&mock.config()).unwrap();
+        let block = mock.block(1);
+        let hash = block.block_hash();
+        assert_eq!(source.tip().await.unwrap(), mock.tip());
+        assert_eq!(source.block_hash(1).await.unwrap(), Some(hash));
+        assert_eq!(source.block_hash(3).await.unwrap(), None);
+        assert_eq!(
+            source.header(&hash).await.unwrap(),
+            Some(ChainHeader {
+                height: 1,
+                header: block.header
+            })
+        );
+        assert_eq!(source.block_at(1).await.unwrap(), Some(block.clone()));
+        assert_eq!(source.block(&BlockHash::all_zeros()).await.unwrap(), None);
+
+        let coinbase = source.transaction(&block.txdata[0].txid()).await.unwrap().unwrap();
+        assert_eq!((coinbase.tx, coinbase.block_hash), (block.txdata[0].clone(), Some(hash)));
+        let tx = mock::spend(
+            &[OutPoint::new(mock.block(2).txdata[0].txid(), 0)],
+            vec![TxOut {
+                value: Amount::from_sat(4_999_990_000),
+                script_pubkey: ScriptBuf::new(),
+            }],
+        );
+        mock.add_to_mempool(tx.clone());
+        assert_eq!(source.mempool().await.unwrap(), vec![tx.txid()]);
+        let entry = source.mempool_entry(&tx.txid()).await.unwrap().unwrap();
+        assert_eq!(entry.fee, 10_000);
+        assert!(source.estimate_fee(1).await.is_err());
+    }
+}
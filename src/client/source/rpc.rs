@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bitcoincore_rpc::bitcoin::block::Header;
+use bitcoincore_rpc::bitcoin::consensus::encode;
+use bitcoincore_rpc::bitcoin::hex::FromHex;
+use bitcoincore_rpc::bitcoin::{Amount, Block, BlockHash, Network, OutPoint, Transaction, Txid};
+use bitcoincore_rpc::json::{
+    EstimateSmartFeeResult, GetBlockHeaderResult, GetBlockStatsResult, GetMempoolEntryResult,
+    GetMempoolInfoResult, GetRawTransactionResult,
+};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::client::bitcoin::{BitcoinClient, BitcoinRpcError, RpcResult};
+use crate::client::source::{BlockSource, ChainHeader, SourceTransaction};
+use crate::constant::BlockHeight;
+use crate::error::{AppError, AppResult};
+use crate::service::ingest::BlockFees;
+use crate::service::mempool::MempoolEntry;
+
+/// `RPC_INVALID_PARAMETER`, returned by bitcoind for heights above the tip.
+const RPC_INVALID_PARAMETER: i32 = -8;
+
+/// The part of `getblockchaininfo` the explorer reads.
+#[derive(Debug, Deserialize)]
+struct BlockchainInfo {
+    chain: String,
+    blocks: BlockHeight,
+    bestblockhash: BlockHash,
+}
+
+/// Reads from Bitcoin Core's JSON-RPC interface.
+pub struct RpcSource {
+    client: Arc<BitcoinClient>,
+}
+
+impl RpcSource {
+    pub fn new(client: Arc<BitcoinClient>) -> Self {
+        Self { client }
+    }
+
+    /// Completes `entries` with the outpoints they spend, read with one batched
+    /// `getrawtransaction`. Transactions that left the mempool since are skipped.
+    async fn with_spends(&self, entries: Vec<(Txid, GetMempoolEntryResult)>) -> AppResult<Vec<MempoolEntry>> {
+        let params: Vec<_> = entries.iter().map(|(txid, _)| vec![json!(txid)]).collect();
+        let results = self.client.batch::<String>("getrawtransaction", &params).await?;
+        let mut completed = Vec::with_capacity(entries.len());
+        for ((txid, entry), result) in entries.iter().zip(results) {
+            if let Some(raw) = not_found_as_none(result)? {
+                let tx: Transaction = decode(&raw)?;
+                completed.push(MempoolEntry::from_rpc(*txid, entry, &tx));
+            }
+        }
+        Ok(completed)
+    }
+}
+
+#[async_trait]
+impl BlockSource for RpcSource {
+    async fn network(&self) -> AppResult<Network> {
+        let info: BlockchainInfo = self.client.call("getblockchaininfo", &[]).await?;
+        chain_network(&info.chain)
+    }
+
+    async fn tip(&self) -> AppResult<(BlockHeight, BlockHash)> {
+        let info: BlockchainInfo = self.client.call("getblockchaininfo", &[]).await?;
+        Ok((info.blocks, info.bestblockhash))
+    }
+
+    async fn block_hash(&self, height: BlockHeight) -> AppResult<Option<BlockHash>> {
+        let result = self.client.call("getblockhash", &[json!(height)]).await;
+        Ok(missing_as_none(result, RPC_INVALID_PARAMETER)?)
+    }
+
+    async fn header(&self, hash: &BlockHash) -> AppResult<Option<ChainHeader>> {
+        // The verbose header has the height, the raw one the exact fields.
+        let mut results = self
+            .client
+            .batch::<serde_json::Value>("getblockheader", &[vec![json!(hash), json!(true)], vec![json!(hash), json!(false)]])
+            .await?
+            .into_iter();
+        let (Some(info), Some(raw)) = (results.next(), results.next()) else {
+            return Ok(None);
+        };
+        let Some(info) = not_found_as_none(info)? else {
+            return Ok(None);
+        };
+        let info: GetBlockHeaderResult = parse(info)?;
+        let raw: String = parse(raw?)?;
+        let header: Header = decode(&raw)?;
+        Ok(Some(ChainHeader {
+            height: info.height as BlockHeight,
+            header,
+        }))
+    }
+
+    async fn block(&self, hash: &BlockHash) -> AppResult<Option<Block>> {
+        let result = self.client.call::<String>("getblock", &[json!(hash), json!(0)]).await;
+        let Some(raw) = not_found_as_none(result)? else {
+            return Ok(None);
+        };
+        let block = tokio::task::spawn_blocking(move || decode(&raw))
+            .await
+            .map_err(|e| AppError::UnknownError(e.into()))??;
+        Ok(Some(block))
+    }
+
+    async fn transaction(&self, txid: &Txid) -> AppResult<Option<SourceTransaction>> {
+        let result = self
+            .client
+            .call::<GetRawTransactionResult>("getrawtransaction", &[json!(txid), json!(true)])
+            .await;
+        let Some(info) = not_found_as_none(result)? else {
+            return Ok(None);
+        };
+        Ok(Some(SourceTransaction {
+            tx: encode::deserialize(&info.hex).map_err(|e| AppError::UnknownError(e.into()))?,
+            block_hash: info.blockhash,
+        }))
+    }
+
+    async fn unspent(&self, outpoints: &[OutPoint]) -> AppResult<Vec<bool>> {
+        let params: Vec<_> = outpoints
+            .iter()
+            .map(|outpoint| vec![json!(outpoint.txid), json!(outpoint.vout), json!(true)])
+            .collect();
+        // gettxout answers null for outputs that are spent or unknown.
+        let results = self.client.batch::<Option<serde_json::Value>>("gettxout", &params).await?;
+        Ok(results
+            .into_iter()
+            .map(|result| result.map(|output| output.is_some()))
+            .collect::<RpcResult<_>>()?)
+    }
+
+    async fn mempool(&self) -> AppResult<Vec<Txid>> {
+        Ok(self.client.call("getrawmempool", &[]).await?)
+    }
+
+    async fn mempool_entries(&self) -> AppResult<Vec<MempoolEntry>> {
+        let entries: HashMap<Txid, GetMempoolEntryResult> = self.client.call("getrawmempool", &[json!(true)]).await?;
+        self.with_spends(entries.into_iter().collect()).await
+    }
+
+    async fn mempool_entry(&self, txid: &Txid) -> AppResult<Option<MempoolEntry>> {
+        let result = self.client.call("getmempoolentry", &[json!(txid)]).await;
+        let Some(entry) = not_found_as_none(result)? else {
+            return Ok(None);
+        };
+        Ok(self.with_spends(vec![(*txid, entry)]).await?.pop())
+    }
+
+    async fn mempool_entries_of(&self, txids: &[Txid]) -> AppResult<Vec<MempoolEntry>> {
+        let params: Vec<_> = txids.iter().map(|txid| vec![json!(txid)]).collect();
+        let results = self.client.batch::<GetMempoolEntryResult>("getmempoolentry", &params).await?;
+        let mut entries = Vec::with_capacity(txids.len());
+        for (txid, result) in txids.iter().zip(results) {
+            if let Some(entry) = not_found_as_none(result)? {
+                entries.push((*txid, entry));
+            }
+        }
+        self.with_spends(entries).await
+    }
+
+    async fn mempool_min_fee(&self) -> AppResult<Amount> {
+        let info: GetMempoolInfoResult = self.client.call("getmempoolinfo", &[]).await?;
+        Ok(info.mempool_min_fee)
+    }
+
+    async fn estimate_fee(&self, target: u16) -> AppResult<Option<Amount>> {
+        let estimate: EstimateSmartFeeResult = self.client.call("estimatesmartfee", &[json!(target)]).await?;
+        Ok(estimate.fee_rate)
+    }
+
+    async fn block_fees(&self, height: BlockHeight) -> AppResult<BlockFees> {
+        let stats: GetBlockStatsResult = self.client.call("getblockstats", &[json!(height)]).await?;
+        Ok(BlockFees::from(&stats))
+    }
+}
+
+/// The network of a `-chain` name as `getblockchaininfo` reports it.
+pub(crate) fn chain_network(chain: &str) -> AppResult<Network> {
+    Network::from_core_arg(chain).map_err(|_| BitcoinRpcError::InvalidResponse(format!("unknown chain {chain}")).into())
+}
+
+fn not_found_as_none<T>(result: RpcResult<T>) -> RpcResult<Option<T>> {
+    match result {
+        Err(e) if e.is_not_found() => Ok(None),
+        result => result.map(Some),
+    }
+}
+
+fn missing_as_none<T>(result: RpcResult<T>, code: i32) -> RpcResult<Option<T>> {
+    match result {
+        Err(e) if e.code() == Some(code) => Ok(None),
+        result => result.map(Some),
+    }
+}
+
+fn parse<T: DeserializeOwned>(value: serde_json::Value) -> AppResult<T> {
+    serde_json::from_value(value).map_err(|e| BitcoinRpcError::InvalidResponse(e.to_string()).into())
+}
+
+fn decode<T: encode::Decodable>(raw: &str) -> AppResult<T> {
+    let bytes = Vec::<u8>::from_hex(raw).map_err(|e| AppError::UnknownError(e.into()))?;
+    encode::deserialize(&bytes).map_err(|e| AppError::UnknownError(e.into()))
+}
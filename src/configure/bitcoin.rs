@@ -1,14 +1,204 @@
+use std::path::PathBuf;
+
+use bitcoincore_rpc::bitcoin::Network;
+use config::ConfigError;
 use serde::Deserialize;
 
+use crate::constant::{
+    DEFAULT_POOLS_FILE, DEFAULT_PREFETCH_CONCURRENCY, DEFAULT_PREFETCH_DEPTH, DEFAULT_RPC_CONNECTIONS,
+    DEFAULT_RPC_RETRIES, DEFAULT_RPC_RETRY_BACKOFF_MS, DEFAULT_RPC_TIMEOUT_SECS,
+};
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct BitcoinConfig {
+    /// The chain the node runs: `bitcoin`, `testnet`, `signet` or `regtest`. The indexer
+    /// refuses to start against a node on another chain.
+    #[serde(default = "default_network")]
+    pub network: Network,
+    /// `host:port` of the node. The port defaults to the network's RPC port.
     pub host: String,
-    pub username: String,
-    pub password: String
+    /// `rpcuser`/`rpcpassword` of the node, or the user of one of its `rpcauth` lines with
+    /// the password it was generated from.
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// bitcoind's `.cookie` file, used in place of a username and password. It is read again
+    /// whenever the node rejects the cookie, since bitcoind writes a new one on every start.
+    #[serde(default)]
+    pub cookie_file: Option<String>,
+    /// The node interface the chain and the mempool are read from.
+    #[serde(default)]
+    pub block_source: BlockSourceKind,
+    /// Connections kept open to the node, also the limit of calls in flight.
+    #[serde(default = "default_rpc_connections")]
+    pub rpc_connections: usize,
+    /// Seconds a call may take before it fails.
+    #[serde(default = "default_rpc_timeout_secs")]
+    pub rpc_timeout_secs: u64,
+    /// Retries of a call the node answered with a transient error, like during warmup or
+    /// with a full work queue.
+    #[serde(default = "default_rpc_retries")]
+    pub rpc_retries: u32,
+    /// Wait before the first retry, doubled after every attempt.
+    #[serde(default = "default_rpc_retry_backoff_ms")]
+    pub rpc_retry_backoff_ms: u64,
+    /// Number of blocks requested from the node at the same time during sync.
+    #[serde(default = "default_prefetch_concurrency")]
+    pub prefetch_concurrency: usize,
+    /// Number of fetched blocks allowed to wait for the writer before fetching pauses.
+    #[serde(default = "default_prefetch_depth")]
+    pub prefetch_depth: usize,
+    /// Bitcoin Core's `blocks` directory. When set, the initial sync reads blocks from the
+    /// `blk*.dat` files instead of downloading them over RPC.
+    #[serde(default)]
+    pub blocks_dir: Option<String>,
+    /// Endpoints of bitcoind's `zmqpubhashblock`, `zmqpubrawblock` and `zmqpubrawtx`, for
+    /// example `tcp://127.0.0.1:28332`. Without any of them the indexer only polls.
+    #[serde(default)]
+    pub zmq_hashblock: Option<String>,
+    #[serde(default)]
+    pub zmq_rawblock: Option<String>,
+    #[serde(default)]
+    pub zmq_rawtx: Option<String>,
+    /// Where the fee statistics of every block come from.
+    #[serde(default)]
+    pub block_fees: BlockFeesSource,
+    /// Mining pool definitions used to tag blocks, relative to the settings directory unless
+    /// absolute. Blocks are re-tagged on startup whenever the file changes. The bundled
+    /// definitions are for mainnet and match coinbase tags only, they list no payout
+    /// addresses. Other networks tag no pools unless this is set.
+    #[serde(default)]
+    pub pools_file: Option<String>,
+    /// Checks every stored header on startup, without trusting the node: hashes, proof of
+    /// work, links, median time past and the retarget schedule. The indexer stops on the
+    /// first inconsistency.
+    #[serde(default)]
+    pub verify_headers: bool,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockSourceKind {
+    /// JSON-RPC with the configured credentials.
+    #[default]
+    Rpc,
+    /// The REST interface bitcoind serves with `-rest`. It has no block statistics, so
+    /// `block_fees` must be `index`, and fee estimates fall back to the mempool and the
+    /// recent blocks.
+    Rest,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockFeesSource {
+    /// One `getblockstats` call per block. Bitcoin Core rounds the feerates down to whole
+    /// sat/vB.
+    #[default]
+    Rpc,
+    /// Computed from the prevouts resolved while indexing, without extra calls to the node.
+    Index,
+}
+
+/// How the explorer authenticates to the node's JSON-RPC interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RpcAuth {
+    /// No `Authorization` header, for a local regtest node behind an authenticating proxy
+    /// or with the check patched out.
+    None,
+    UserPass { username: String, password: String },
+    CookieFile(PathBuf),
 }
 
 impl BitcoinConfig {
     pub fn get_host(&self) -> String {
-        format!("{}", self.host)
+        self.host.clone()
+    }
+
+    pub fn pools_file(&self) -> Option<String> {
+        match (&self.pools_file, self.network) {
+            (Some(path), _) => Some(path.clone()),
+            (None, Network::Bitcoin) => Some(DEFAULT_POOLS_FILE.to_string()),
+            (None, _) => None,
+        }
+    }
+
+    /// The configured credentials, an error when it is unclear which ones are meant.
+    pub fn auth(&self) -> Result<RpcAuth, ConfigError> {
+        match (&self.username, &self.password, &self.cookie_file) {
+            (None, None, None) => Ok(RpcAuth::None),
+            (Some(username), Some(password), None) => Ok(RpcAuth::UserPass {
+                username: username.clone(),
+                password: password.clone(),
+            }),
+            (None, None, Some(cookie_file)) => Ok(RpcAuth::CookieFile(PathBuf::from(cookie_file))),
+            (_, _, Some(_)) => Err(ConfigError::Message(
+                "bitcoin.cookie_file cannot be combined with bitcoin.username or bitcoin.password".to_string(),
+            )),
+            (Some(_), None, None) => Err(ConfigError::Message("bitcoin.username is set without bitcoin.password".to_string())),
+            (None, Some(_), None) => Err(ConfigError::Message("bitcoin.password is set without bitcoin.username".to_string())),
+        }
+    }
+}
+
+fn default_network() -> Network {
+    Network::Bitcoin
+}
+
+fn default_rpc_connections() -> usize {
+    DEFAULT_RPC_CONNECTIONS
+}
+
+fn default_rpc_timeout_secs() -> u64 {
+    DEFAULT_RPC_TIMEOUT_SECS
+}
+
+fn default_rpc_retries() -> u32 {
+    DEFAULT_RPC_RETRIES
+}
+
+fn default_rpc_retry_backoff_ms() -> u64 {
+    DEFAULT_RPC_RETRY_BACKOFF_MS
+}
+
+fn default_prefetch_concurrency() -> usize {
+    DEFAULT_PREFETCH_CONCURRENCY
+}
+
+fn default_prefetch_depth() -> usize {
+    DEFAULT_PREFETCH_DEPTH
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn config(credentials: serde_json::Value) -> BitcoinConfig {
+        let mut value = json!({ "host": "localhost:8332" });
+        value.as_object_mut().unwrap().extend(credentials.as_object().unwrap().clone());
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_auth() {
+        assert_eq!(config(json!({})).auth().unwrap(), RpcAuth::None);
+        assert_eq!(
+            config(json!({ "username": "user", "password": "pass" })).auth().unwrap(),
+            RpcAuth::UserPass {
+                username: "user".to_string(),
+                password: "pass".to_string()
+            }
+        );
+        assert_eq!(
+            config(json!({ "cookie_file": "/bitcoin/.cookie" })).auth().unwrap(),
+            RpcAuth::CookieFile(PathBuf::from("/bitcoin/.cookie"))
+        );
+        assert!(config(json!({ "username": "user" })).auth().is_err());
+        assert!(config(json!({ "password": "pass" })).auth().is_err());
+        assert!(config(json!({ "username": "user", "password": "pass", "cookie_file": "/bitcoin/.cookie" }))
+            .auth()
+            .is_err());
     }
-}
\ No newline at end of file
+}
@@ -1,5 +1,7 @@
 use serde::Deserialize;
 
+use crate::constant::DEFAULT_BULK_BATCH_TXS;
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct DatabaseConfig {
     pub username: String,
@@ -7,7 +9,14 @@ pub struct DatabaseConfig {
     pub port: u16,
     pub host: String,
     pub max_connections: u32,
-    pub database_name: String
+    pub database_name: String,
+    /// Backfill history with `COPY` batches and deferred indexes, switching to per-block
+    /// writes once the index reaches the node tip.
+    #[serde(default)]
+    pub bulk_sync: bool,
+    /// Transactions buffered before a bulk batch is written.
+    #[serde(default = "default_bulk_batch_txs")]
+    pub bulk_batch_txs: usize,
 }
 
 impl DatabaseConfig {
@@ -29,4 +38,8 @@ impl DatabaseConfig {
     ) -> String {
         format!("postgres://{username}:{password}@{host}:{port}/{database_name}")
     }
-}
\ No newline at end of file
+}
+
+fn default_bulk_batch_txs() -> usize {
+    DEFAULT_BULK_BATCH_TXS
+}
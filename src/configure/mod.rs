@@ -36,7 +36,9 @@ impl AppConfig {
             .add_source(env_src)
             .build()?;
         info!("Successfully read config profile: {profile}.");
-        config.try_deserialize()
+        let config: Self = config.try_deserialize()?;
+        config.bitcoin.auth()?;
+        Ok(config)
     }
 }
 
@@ -1,3 +1,4 @@
+use std::time::Duration;
 use once_cell::sync::Lazy;
 use crate::{
   configure::{env::get_env_source}
@@ -9,3 +10,51 @@ pub static CONFIG: Lazy<crate::configure::AppConfig> =
 
 
 pub type BlockHeight = u32;
+
+pub const BLOCK_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+pub const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+pub const DEFAULT_PREFETCH_CONCURRENCY: usize = 8;
+
+pub const DEFAULT_PREFETCH_DEPTH: usize = 64;
+
+pub const DEFAULT_RPC_CONNECTIONS: usize = 16;
+
+pub const DEFAULT_RPC_TIMEOUT_SECS: u64 = 30;
+
+pub const DEFAULT_RPC_RETRIES: u32 = 5;
+
+pub const DEFAULT_RPC_RETRY_BACKOFF_MS: u64 = 250;
+
+pub const DEFAULT_BULK_BATCH_TXS: usize = 50_000;
+
+/// Deepest reorganization rolled back on its own. Anything deeper stops the sync, since it
+/// more likely means the node switched to another chain than that the chain reorganized.
+pub const MAX_REORG_DEPTH: BlockHeight = 100;
+
+/// Bulk sync is only worth it when the index is at least this many blocks behind the node.
+pub const BULK_SYNC_MIN_LAG: BlockHeight = 1_000;
+
+pub const ZMQ_RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// How often the mempool is reconciled with the node's, on top of the live notifications.
+pub const MEMPOOL_SYNC_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The projected blocks are rebuilt at most this often while the mempool keeps changing.
+pub const TEMPLATE_REBUILD_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Number of projected blocks built from the mempool, the last one takes whatever is left.
+pub const PROJECTED_BLOCKS: usize = 8;
+
+/// Number of recent blocks whose median feerate backs the fee recommendations.
+pub const RECENT_FEE_BLOCKS: u64 = 6;
+
+/// Pool definitions shipped in the settings directory.
+pub const DEFAULT_POOLS_FILE: &str = "pools.json";
+
+/// `blocks.pool_id` of blocks no known pool claims, the column default.
+pub const UNKNOWN_POOL_ID: i32 = -1;
+
+/// Blocks read per query while re-tagging or decoding the stored chain again.
+pub const BACKFILL_BATCH_BLOCKS: i32 = 10_000;
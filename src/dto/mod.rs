@@ -1,2 +1,3 @@
 
-pub mod response;
\ No newline at end of file
+pub mod request;
+pub mod response;
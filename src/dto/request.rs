@@ -0,0 +1,100 @@
+use garde::Validate;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Largest page size a request may ask for.
+const MAX_PAGE_SIZE: u64 = 100;
+
+/// Keeps `page_num * page_size` a valid SQL `OFFSET` for every allowed page size.
+const MAX_PAGE_NUM: u64 = i64::MAX as u64 / MAX_PAGE_SIZE;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, IntoParams, Validate, Clone)]
+pub struct PageQueryParam {
+    /// Zero-based page number.
+    #[serde(default)]
+    #[garde(range(max = MAX_PAGE_NUM))]
+    pub page_num: u64,
+    #[serde(default = "default_page_size")]
+    #[garde(range(min = 1, max = MAX_PAGE_SIZE))]
+    pub page_size: u64,
+}
+
+fn default_page_size() -> u64 {
+    25
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, IntoParams, Clone)]
+pub struct MiningPeriodQueryParam {
+    /// How far back from the last indexed block to count.
+    #[serde(default)]
+    pub period: MiningPeriod,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Copy, Clone, Default, PartialEq, Eq)]
+pub enum MiningPeriod {
+    #[serde(rename = "24h")]
+    Day,
+    #[serde(rename = "3d")]
+    ThreeDays,
+    #[default]
+    #[serde(rename = "1w")]
+    Week,
+    #[serde(rename = "1m")]
+    Month,
+    #[serde(rename = "3m")]
+    ThreeMonths,
+    #[serde(rename = "6m")]
+    SixMonths,
+    #[serde(rename = "1y")]
+    Year,
+    #[serde(rename = "all")]
+    All,
+}
+
+impl MiningPeriod {
+    /// Length of the period, `None` for the whole chain.
+    pub fn duration(self) -> Option<chrono::Duration> {
+        let days = match self {
+            Self::Day => 1,
+            Self::ThreeDays => 3,
+            Self::Week => 7,
+            Self::Month => 30,
+            Self::ThreeMonths => 90,
+            Self::SixMonths => 180,
+            Self::Year => 365,
+            Self::All => return None,
+        };
+        Some(chrono::Duration::days(days))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, IntoParams, Clone)]
+pub struct MerkleProofQueryParam {
+    #[serde(default)]
+    pub format: MerkleProofFormat,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Copy, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MerkleProofFormat {
+    /// The branch and position as returned by Electrum's `blockchain.transaction.get_merkle`.
+    #[default]
+    Electrum,
+    /// The serialized merkle block as returned by Bitcoin Core's `gettxoutproof`.
+    Txoutproof,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_bounds() {
+        let page = |page_num, page_size| PageQueryParam { page_num, page_size }.validate(&());
+        assert!(page(0, 25).is_ok());
+        assert!(page(MAX_PAGE_NUM, MAX_PAGE_SIZE).is_ok());
+        assert!(page(u64::MAX, MAX_PAGE_SIZE).is_err());
+        assert!(page(0, 0).is_err());
+        assert!(page(0, MAX_PAGE_SIZE + 1).is_err());
+    }
+}
@@ -1,6 +1,9 @@
+use bitcoincore_rpc::bitcoin::Network;
+use chrono::NaiveDateTime;
 use fake::Dummy;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
+use crate::entity;
 use crate::error::AppResponseError;
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -41,4 +44,440 @@ pub struct ServiceStatusResponse {
     pub db: bool,
     pub redis: bool,
     pub email: bool,
-}
\ No newline at end of file
+}
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct NetworkResponse {
+    /// `bitcoin`, `testnet`, `signet` or `regtest`.
+    pub network: String,
+    /// The same network as bitcoind's `-chain` names it.
+    pub chain: String,
+    pub genesis_hash: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct AddressResponse {
+    /// The network the address belongs to, `bitcoin`, `testnet`, `signet` or `regtest`.
+    pub network: String,
+    pub address: String,
+    /// Confirmed balance in sats.
+    pub balance: i64,
+    pub total_received: i64,
+    pub total_sent: i64,
+    pub tx_count: i32,
+    pub first_seen_height: i32,
+    pub last_seen_height: i32,
+}
+
+impl AddressResponse {
+    pub fn new(model: entity::address::Model, network: Network) -> Self {
+        Self {
+            network: network.to_string(),
+            address: model.address,
+            balance: model.balance,
+            total_received: model.total_received,
+            total_sent: model.total_sent,
+            tx_count: model.tx_count,
+            first_seen_height: model.first_seen_height,
+            last_seen_height: model.last_seen_height,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct AddressTxResponse {
+    pub txid: String,
+    pub block_height: i32,
+    pub received: i64,
+    pub sent: i64,
+    /// Net change of the address balance caused by the transaction, in sats.
+    pub balance_change: i64,
+}
+
+impl From<entity::address_tx::Model> for AddressTxResponse {
+    fn from(model: entity::address_tx::Model) -> Self {
+        Self {
+            txid: model.txid,
+            block_height: model.block_height,
+            received: model.received,
+            sent: model.sent,
+            balance_change: model.received - model.sent,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct AddressTxsResponse {
+    pub network: String,
+    pub address: String,
+    pub page_num: u64,
+    pub page_size: u64,
+    pub total: u64,
+    pub txs: Vec<AddressTxResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct BlockResponse {
+    /// The network of the chain the block belongs to.
+    pub network: String,
+    pub height: i32,
+    pub hash: String,
+    pub timestamp: NaiveDateTime,
+    pub size: i32,
+    pub weight: i32,
+    pub tx_count: i32,
+    pub coinbase_raw: Option<String>,
+    pub difficulty: f64,
+    pub pool_id: Option<i32>,
+    /// Total fees in sats.
+    pub fees: f64,
+    /// Feerates in sat/vB: `[min, 10th, 25th, 50th, 75th, 90th, max]`.
+    pub fee_span: Vec<f64>,
+    pub median_fee: f64,
+    pub coinbase: CoinbaseResponse,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct CoinbaseResponse {
+    /// Height pushed by the miner as required by BIP34, `None` before its activation.
+    pub height: Option<i32>,
+    /// Printable text found in the coinbase script.
+    pub tags: Vec<String>,
+    pub witness_commitment: Option<String>,
+    /// Auxiliary chains merkle root when the block is merge mined.
+    pub merged_mining_root: Option<String>,
+    pub malformed: bool,
+}
+
+impl BlockResponse {
+    pub fn new(model: entity::block::Model, network: Network) -> Self {
+        Self {
+            network: network.to_string(),
+            height: model.height,
+            hash: model.hash,
+            timestamp: model.block_created_at,
+            size: model.size,
+            weight: model.weight,
+            tx_count: model.tx_count,
+            coinbase_raw: model.coinbase_raw,
+            difficulty: model.difficulty,
+            pool_id: model.pool_id,
+            fees: model.fees,
+            fee_span: serde_json::from_value(model.fee_span).unwrap_or_default(),
+            median_fee: model.median_fee,
+            coinbase: CoinbaseResponse {
+                height: model.coinbase_height,
+                tags: model
+                    .coinbase_tags
+                    .and_then(|tags| serde_json::from_value(tags).ok())
+                    .unwrap_or_default(),
+                witness_commitment: model.witness_commitment,
+                merged_mining_root: model.merged_mining_root,
+                malformed: model.coinbase_malformed,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct BlocksResponse {
+    pub page_num: u64,
+    pub page_size: u64,
+    pub total: u64,
+    pub blocks: Vec<BlockResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct BlockTxResponse {
+    pub txid: String,
+    pub block_index: i32,
+    pub size: i32,
+    pub vsize: i32,
+    pub weight: i32,
+    /// Fee in sats.
+    pub fee: i64,
+    /// Feerate in sat/vB.
+    pub feerate: f64,
+    pub input_count: i32,
+    pub output_count: i32,
+    pub is_segwit: bool,
+    pub is_taproot: bool,
+}
+
+impl From<entity::transaction::Model> for BlockTxResponse {
+    fn from(model: entity::transaction::Model) -> Self {
+        Self {
+            txid: model.txid,
+            block_index: model.block_index,
+            size: model.size,
+            vsize: model.vsize,
+            weight: model.weight,
+            fee: model.fee,
+            feerate: model.feerate,
+            input_count: model.input_count,
+            output_count: model.output_count,
+            is_segwit: model.is_segwit,
+            is_taproot: model.is_taproot,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct BlockTxsResponse {
+    pub network: String,
+    pub height: i32,
+    pub hash: String,
+    pub page_num: u64,
+    pub page_size: u64,
+    pub total: u64,
+    pub txs: Vec<BlockTxResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct TxInputResponse {
+    pub is_coinbase: bool,
+    pub prev_txid: Option<String>,
+    pub prev_vout: Option<u32>,
+    /// Value of the spent output in sats, when the prevout is known.
+    pub value: Option<i64>,
+    pub address: Option<String>,
+    pub script_sig: String,
+    pub witness: Vec<String>,
+    pub sequence: u32,
+}
+
+impl From<entity::tx_input::Model> for TxInputResponse {
+    fn from(model: entity::tx_input::Model) -> Self {
+        let is_coinbase = model.prev_txid.is_none();
+        Self {
+            is_coinbase,
+            prev_txid: model.prev_txid,
+            prev_vout: model.prev_vout.map(|vout| vout as u32),
+            value: (!is_coinbase).then_some(model.value),
+            address: model.address,
+            script_sig: model.script_sig,
+            witness: serde_json::from_value(model.witness).unwrap_or_default(),
+            sequence: model.sequence as u32,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct TxOutputResponse {
+    pub vout: u32,
+    /// Value in sats.
+    pub value: i64,
+    pub script_pubkey: String,
+    pub script_type: String,
+    pub address: Option<String>,
+    pub spent: bool,
+    pub spent_by_txid: Option<String>,
+    pub spent_by_vin: Option<u32>,
+}
+
+impl From<entity::tx_output::Model> for TxOutputResponse {
+    fn from(model: entity::tx_output::Model) -> Self {
+        Self {
+            vout: model.vout as u32,
+            value: model.value,
+            script_pubkey: model.script_pubkey,
+            script_type: model.script_type,
+            address: model.address,
+            spent: model.spent_by_txid.is_some(),
+            spent_by_txid: model.spent_by_txid,
+            spent_by_vin: model.spent_by_vin.map(|vin| vin as u32),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct TransactionResponse {
+    /// The network the transaction and the addresses of its inputs and outputs belong to.
+    pub network: String,
+    pub txid: String,
+    /// `None` while the transaction is unconfirmed.
+    pub block_height: Option<i32>,
+    pub block_hash: Option<String>,
+    pub block_index: Option<i32>,
+    pub confirmations: u32,
+    pub size: i32,
+    pub vsize: i32,
+    pub weight: i32,
+    /// Fee in sats, `None` when some prevouts could not be resolved.
+    pub fee: Option<i64>,
+    /// Feerate in sat/vB.
+    pub feerate: Option<f64>,
+    pub version: i32,
+    pub locktime: u32,
+    pub is_segwit: bool,
+    pub is_taproot: bool,
+    pub inputs: Vec<TxInputResponse>,
+    pub outputs: Vec<TxOutputResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, PartialEq)]
+pub struct FeeHistogramBucket {
+    /// Lowest feerate in the bucket in sat/vB, inclusive.
+    pub min_feerate: f64,
+    /// Highest feerate in the bucket in sat/vB, exclusive. `None` for the last bucket.
+    pub max_feerate: Option<f64>,
+    pub count: usize,
+    pub vsize: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct MempoolResponse {
+    pub count: usize,
+    pub vsize: u64,
+    /// Total fees in sats.
+    pub total_fee: u64,
+    /// Non-empty feerate buckets, lowest feerate first.
+    pub fee_histogram: Vec<FeeHistogramBucket>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct ProjectedBlockResponse {
+    /// Position of the block, `0` being the next block to be mined.
+    pub index: usize,
+    pub tx_count: usize,
+    pub vsize: u64,
+    pub weight: u64,
+    /// Total fees in sats.
+    pub total_fee: u64,
+    /// Median effective feerate in sat/vB, weighted by vsize.
+    pub median_feerate: f64,
+    pub min_feerate: f64,
+    pub max_feerate: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct ProjectedTxResponse {
+    pub txid: String,
+    /// Projected block the transaction is in, `0` being the next block to be mined.
+    pub block: usize,
+    /// Feerate of the package the transaction is mined with, in sat/vB.
+    pub effective_feerate: f64,
+}
+
+/// Where a recommended feerate comes from, from the most to the least responsive.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeSource {
+    /// Projected blocks built from the current mempool.
+    Mempool,
+    /// Bitcoin Core's `estimatesmartfee`, or `mempoolminfee` for the minimum.
+    Node,
+    /// Median feerates of the last indexed blocks.
+    Blocks,
+    /// Nothing else was available, the relay minimum of 1 sat/vB is used.
+    Default,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, PartialEq)]
+pub struct FeeEstimate {
+    /// Feerate in sat/vB.
+    pub feerate: f64,
+    pub source: FeeSource,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct RecommendedFeesResponse {
+    /// Next block.
+    pub fastest: FeeEstimate,
+    /// Within 3 blocks.
+    pub half_hour: FeeEstimate,
+    /// Within 6 blocks.
+    pub hour: FeeEstimate,
+    /// Within a day.
+    pub economy: FeeEstimate,
+    /// Lowest feerate the node accepts into its mempool.
+    pub minimum: FeeEstimate,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct MiningPoolResponse {
+    /// `-1` for blocks no known pool claims.
+    pub id: i32,
+    pub name: String,
+    pub link: String,
+    pub block_count: i64,
+    /// Share of the blocks mined in the period, between 0 and 1.
+    pub share: f64,
+    /// Estimated hashrate in H/s.
+    pub hashrate: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct MiningPoolsResponse {
+    pub period: crate::dto::request::MiningPeriod,
+    pub block_count: i64,
+    /// Estimated network hashrate over the period in H/s.
+    pub hashrate: f64,
+    /// Pools with at least one block, most blocks first.
+    pub pools: Vec<MiningPoolResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct DifficultyAdjustmentResponse {
+    pub current_difficulty: f64,
+    /// First block of the current difficulty epoch.
+    pub epoch_start_height: i32,
+    /// First block mined with the next difficulty.
+    pub retarget_height: i32,
+    /// Share of the epoch already mined, between 0 and 100.
+    pub progress_percent: f64,
+    pub remaining_blocks: i32,
+    /// Average time between the blocks of the epoch so far, in seconds.
+    pub average_block_time: f64,
+    pub estimated_retarget_date: NaiveDateTime,
+    /// Expected difficulty change at the retarget if blocks keep coming at the current pace.
+    pub difficulty_change_percent: f64,
+    /// Change applied at the start of the current epoch.
+    pub previous_change_percent: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct HashratePoint {
+    pub timestamp: NaiveDateTime,
+    /// Estimated hashrate in H/s.
+    pub hashrate: f64,
+    /// Average difficulty of the blocks in the point.
+    pub difficulty: f64,
+    pub block_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct HashrateResponse {
+    pub period: crate::dto::request::MiningPeriod,
+    /// Latest point of the series, in H/s.
+    pub current_hashrate: f64,
+    pub current_difficulty: f64,
+    /// Oldest first.
+    pub hashrates: Vec<HashratePoint>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct ElectrumMerkleProofResponse {
+    pub block_height: i32,
+    pub block_hash: String,
+    /// Position of the transaction in the block.
+    pub pos: u32,
+    /// Sibling hashes from the transaction up to the merkle root.
+    pub merkle: Vec<String>,
+    /// Serialized 80-byte block header.
+    pub header: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct TxOutProofResponse {
+    pub block_height: i32,
+    pub block_hash: String,
+    /// Serialized merkle block, the header followed by a partial merkle tree.
+    pub proof: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+#[serde(untagged)]
+pub enum MerkleProofResponse {
+    Electrum(ElectrumMerkleProofResponse),
+    TxOutProof(TxOutProofResponse),
+}
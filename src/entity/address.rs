@@ -0,0 +1,28 @@
+use sea_orm::entity::prelude::*;
+
+use crate::error::ResourceType;
+
+use super::AppEntity;
+
+#[derive(Debug, PartialEq, Eq, Clone, DeriveEntityModel)]
+#[sea_orm(table_name = "addresses")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub address: String,
+    pub balance: i64,
+    pub total_received: i64,
+    pub total_sent: i64,
+    pub tx_count: i32,
+    pub first_seen_height: i32,
+    pub last_seen_height: i32,
+}
+
+impl AppEntity for Model {
+    const RESOURCE: ResourceType = ResourceType::Address;
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {}
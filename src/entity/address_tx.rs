@@ -0,0 +1,21 @@
+use sea_orm::entity::prelude::*;
+
+/// The effect of one transaction on one address.
+#[derive(Debug, PartialEq, Eq, Clone, DeriveEntityModel)]
+#[sea_orm(table_name = "address_txs")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub address: String,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub txid: String,
+    pub block_height: i32,
+    pub block_index: i32,
+    pub received: i64,
+    pub sent: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,50 @@
+use chrono::NaiveDateTime;
+use sea_orm::entity::prelude::*;
+
+use crate::error::ResourceType;
+
+use super::AppEntity;
+
+#[derive(Debug, PartialEq, Clone, DeriveEntityModel)]
+#[sea_orm(table_name = "blocks")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub height: i32,
+    pub hash: String,
+    pub block_created_at: NaiveDateTime,
+    pub size: i32,
+    pub weight: i32,
+    pub tx_count: i32,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub coinbase_raw: Option<String>,
+    #[sea_orm(column_type = "Double")]
+    pub difficulty: f64,
+    pub pool_id: Option<i32>,
+    #[sea_orm(column_type = "Double")]
+    pub fees: f64,
+    pub fee_span: Json,
+    #[sea_orm(column_type = "Double")]
+    pub median_fee: f64,
+    pub coinbase_height: Option<i32>,
+    /// `None` until the coinbase is decoded.
+    pub coinbase_tags: Option<Json>,
+    pub witness_commitment: Option<String>,
+    pub merged_mining_root: Option<String>,
+    pub coinbase_malformed: bool,
+    /// Header fields, `None` until read for blocks stored before they were kept.
+    pub version: Option<i32>,
+    pub previous_hash: Option<String>,
+    pub merkle_root: Option<String>,
+    pub bits: Option<i64>,
+    pub nonce: Option<i64>,
+}
+
+impl AppEntity for Model {
+    const RESOURCE: ResourceType = ResourceType::Block;
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {}
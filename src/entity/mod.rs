@@ -1,6 +1,13 @@
 pub mod user;
 pub mod role;
 pub mod store;
+pub mod block;
+pub mod transaction;
+pub mod tx_output;
+pub mod tx_input;
+pub mod address;
+pub mod address_tx;
+pub mod pool;
 
 use sea_orm::{DatabaseTransaction, TransactionTrait};
 use test_context::AsyncTestContext;
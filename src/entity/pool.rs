@@ -0,0 +1,26 @@
+use sea_orm::entity::prelude::*;
+
+use crate::error::ResourceType;
+
+use super::AppEntity;
+
+#[derive(Debug, PartialEq, Eq, Clone, DeriveEntityModel)]
+#[sea_orm(table_name = "pools")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub id: i32,
+    pub name: String,
+    pub link: String,
+    pub tags: Json,
+    pub addresses: Json,
+}
+
+impl AppEntity for Model {
+    const RESOURCE: ResourceType = ResourceType::Pool;
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,36 @@
+use sea_orm::entity::prelude::*;
+
+use crate::error::ResourceType;
+
+use super::AppEntity;
+
+#[derive(Debug, PartialEq, Clone, DeriveEntityModel)]
+#[sea_orm(table_name = "transactions")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub txid: String,
+    pub block_height: i32,
+    pub block_index: i32,
+    pub size: i32,
+    pub vsize: i32,
+    pub weight: i32,
+    pub fee: i64,
+    #[sea_orm(column_type = "Double")]
+    pub feerate: f64,
+    pub version: i32,
+    pub locktime: i64,
+    pub input_count: i32,
+    pub output_count: i32,
+    pub is_segwit: bool,
+    pub is_taproot: bool,
+}
+
+impl AppEntity for Model {
+    const RESOURCE: ResourceType = ResourceType::Transaction;
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,26 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Debug, PartialEq, Eq, Clone, DeriveEntityModel)]
+#[sea_orm(table_name = "tx_inputs")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub txid: String,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub vin: i32,
+    pub block_height: i32,
+    /// `None` for the coinbase input.
+    pub prev_txid: Option<String>,
+    pub prev_vout: Option<i32>,
+    pub value: i64,
+    pub address: Option<String>,
+    #[sea_orm(column_type = "Text")]
+    pub script_sig: String,
+    pub witness: Json,
+    pub sequence: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {}
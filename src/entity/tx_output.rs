@@ -0,0 +1,25 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Debug, PartialEq, Eq, Clone, DeriveEntityModel)]
+#[sea_orm(table_name = "tx_outputs")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub txid: String,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub vout: i32,
+    pub block_height: i32,
+    pub value: i64,
+    #[sea_orm(column_type = "Text")]
+    pub script_pubkey: String,
+    pub script_type: String,
+    pub address: Option<String>,
+    pub spent_by_txid: Option<String>,
+    pub spent_by_vin: Option<i32>,
+    pub spent_height: Option<i32>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {}
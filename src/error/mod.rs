@@ -9,7 +9,9 @@ use strum::EnumString;
 use utoipa::ToSchema;
 
 
+use crate::client::bitcoin::BitcoinRpcError;
 use crate::entity;
+use crate::service::header::HeaderError;
 
 pub type AppResult<T = ()> = std::result::Result<T, AppError>;
 
@@ -46,6 +48,10 @@ pub enum AppError {
     #[error(transparent)]
     IoError(#[from] std::io::Error),
     #[error(transparent)]
+    InvalidHeaderError(#[from] HeaderError),
+    #[error(transparent)]
+    BitcoinRpcError(#[from] BitcoinRpcError),
+    #[error(transparent)]
     UnknownError(#[from] anyhow::Error),
 }
 
@@ -157,6 +163,18 @@ impl AppError {
                 };
                 (kind, code, vec![], status)
             }
+            InvalidHeaderError(_err) => (
+                "INVALID_HEADER_ERROR".to_string(),
+                None,
+                vec![],
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            BitcoinRpcError(_err) => (
+                "BITCOIN_RPC_ERROR".to_string(),
+                None,
+                vec![],
+                StatusCode::BAD_GATEWAY,
+            ),
             UnknownError(_err) => (
                 "UNKNOWN_ERROR".to_string(),
                 None,
@@ -227,6 +245,14 @@ pub enum ResourceType {
     Message,
     #[strum(serialize = "STORE")]
     Store,
+    #[strum(serialize = "ADDRESS")]
+    Address,
+    #[strum(serialize = "BLOCK")]
+    Block,
+    #[strum(serialize = "TRANSACTION")]
+    Transaction,
+    #[strum(serialize = "POOL")]
+    Pool,
 }
 
 pub trait ToAppResult {
@@ -0,0 +1,57 @@
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use tracing::warn;
+
+use crate::dto::request::PageQueryParam;
+use crate::dto::response::{AddressResponse, AddressTxsResponse};
+use crate::error::AppResult;
+use crate::server::state::AppState;
+use crate::service;
+
+// Address summary
+#[utoipa::path(
+    get,
+    path = "/api/v1/address/{address}",
+    params(("address" = String, Path, description = "bitcoin address")),
+    responses(
+        (status = 200, description = "address balance and totals", body = [AddressResponse]),
+        (status = 400, description = "invalid address", body = [AppResponseError]),
+        (status = 404, description = "address never seen on chain", body = [AppResponseError]),
+    )
+)]
+pub async fn get_address(
+    State(state): State<AppState>,
+    Path(address): Path<String>,
+) -> AppResult<Json<AddressResponse>> {
+    match service::address::get(&state, &address).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => {
+            warn!("Failed to get address {address}: {e:?}.");
+            Err(e)
+        }
+    }
+}
+
+// Address history
+#[utoipa::path(
+    get,
+    path = "/api/v1/address/{address}/txs",
+    params(("address" = String, Path, description = "bitcoin address"), PageQueryParam),
+    responses(
+        (status = 200, description = "transactions touching the address, newest first", body = [AddressTxsResponse]),
+        (status = 400, description = "invalid address or page", body = [AppResponseError]),
+    )
+)]
+pub async fn get_address_txs(
+    State(state): State<AppState>,
+    Path(address): Path<String>,
+    Query(param): Query<PageQueryParam>,
+) -> AppResult<Json<AddressTxsResponse>> {
+    match service::address::list_txs(&state, &address, param).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => {
+            warn!("Failed to get address history {address}: {e:?}.");
+            Err(e)
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use tracing::warn;
+
+use crate::dto::request::PageQueryParam;
+use crate::dto::response::{BlockResponse, BlockTxsResponse, BlocksResponse};
+use crate::error::AppResult;
+use crate::server::state::AppState;
+use crate::service;
+
+// Latest blocks
+#[utoipa::path(
+    get,
+    path = "/api/v1/blocks",
+    params(PageQueryParam),
+    responses(
+        (status = 200, description = "indexed blocks, newest first", body = [BlocksResponse]),
+        (status = 400, description = "invalid page", body = [AppResponseError]),
+    )
+)]
+pub async fn get_blocks(
+    State(state): State<AppState>,
+    Query(param): Query<PageQueryParam>,
+) -> AppResult<Json<BlocksResponse>> {
+    match service::block::list(&state, param).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => {
+            warn!("Failed to get blocks: {e:?}.");
+            Err(e)
+        }
+    }
+}
+
+// Block by height
+#[utoipa::path(
+    get,
+    path = "/api/v1/block/{height}",
+    params(("height" = i32, Path, description = "block height")),
+    responses(
+        (status = 200, description = "block at the given height", body = [BlockResponse]),
+        (status = 404, description = "block not indexed", body = [AppResponseError]),
+    )
+)]
+pub async fn get_block_by_height(
+    State(state): State<AppState>,
+    Path(height): Path<i32>,
+) -> AppResult<Json<BlockResponse>> {
+    match service::block::get_by_height(&state, height).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => {
+            warn!("Failed to get block {height}: {e:?}.");
+            Err(e)
+        }
+    }
+}
+
+// Block by hash
+#[utoipa::path(
+    get,
+    path = "/api/v1/block/hash/{hash}",
+    params(("hash" = String, Path, description = "block hash")),
+    responses(
+        (status = 200, description = "block with the given hash", body = [BlockResponse]),
+        (status = 400, description = "invalid block hash", body = [AppResponseError]),
+        (status = 404, description = "block not indexed", body = [AppResponseError]),
+    )
+)]
+pub async fn get_block_by_hash(
+    State(state): State<AppState>,
+    Path(hash): Path<String>,
+) -> AppResult<Json<BlockResponse>> {
+    match service::block::get_by_hash(&state, &hash).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => {
+            warn!("Failed to get block {hash}: {e:?}.");
+            Err(e)
+        }
+    }
+}
+
+// Block transactions
+#[utoipa::path(
+    get,
+    path = "/api/v1/block/{id}/txs",
+    params(("id" = String, Path, description = "block height or hash"), PageQueryParam),
+    responses(
+        (status = 200, description = "transactions of the block in block order", body = [BlockTxsResponse]),
+        (status = 400, description = "invalid block id or page", body = [AppResponseError]),
+        (status = 404, description = "block not indexed", body = [AppResponseError]),
+    )
+)]
+pub async fn get_block_txs(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(param): Query<PageQueryParam>,
+) -> AppResult<Json<BlockTxsResponse>> {
+    match service::block::list_txs(&state, &id, param).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => {
+            warn!("Failed to get transactions of block {id}: {e:?}.");
+            Err(e)
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use axum::extract::State;
+use axum::Json;
+use tracing::warn;
+
+use crate::dto::response::RecommendedFeesResponse;
+use crate::error::AppResult;
+use crate::server::state::AppState;
+use crate::service;
+
+// Recommended fees
+#[utoipa::path(
+    get,
+    path = "/api/v1/fees/recommended",
+    responses(
+        (status = 200, description = "recommended feerates in sat/vB with the source of each", body = [RecommendedFeesResponse]),
+        (status = 500, description = "internal server error", body = [AppResponseError]),
+    )
+)]
+pub async fn get_recommended_fees(
+    State(state): State<AppState>,
+) -> AppResult<Json<RecommendedFeesResponse>> {
+    match service::fee::recommended(&state).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => {
+            warn!("Failed to get recommended fees: {e:?}.");
+            Err(e)
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use axum::extract::{Path, State};
+use axum::Json;
+use tracing::warn;
+
+use crate::dto::response::{MempoolResponse, ProjectedBlockResponse, ProjectedTxResponse};
+use crate::error::AppResult;
+use crate::server::state::AppState;
+use crate::service;
+
+// Mempool summary
+#[utoipa::path(
+    get,
+    path = "/api/v1/mempool",
+    responses(
+        (status = 200, description = "mempool size, total fees and fee histogram", body = [MempoolResponse]),
+    )
+)]
+pub async fn get_mempool(State(state): State<AppState>) -> Json<MempoolResponse> {
+    Json(service::mempool::get(&state).await)
+}
+
+// Mempool txids
+#[utoipa::path(
+    get,
+    path = "/api/v1/mempool/txids",
+    responses(
+        (status = 200, description = "txids of every unconfirmed transaction", body = [String]),
+    )
+)]
+pub async fn list_mempool_txids(State(state): State<AppState>) -> Json<Vec<String>> {
+    Json(service::mempool::list_txids(&state).await)
+}
+
+// Projected blocks
+#[utoipa::path(
+    get,
+    path = "/api/v1/mempool/blocks",
+    responses(
+        (status = 200, description = "next blocks projected from the mempool by ancestor score", body = [ProjectedBlockResponse]),
+    )
+)]
+pub async fn list_projected_blocks(State(state): State<AppState>) -> Json<Vec<ProjectedBlockResponse>> {
+    Json(service::mempool::projected_blocks(&state).await)
+}
+
+// Projected block of a transaction
+#[utoipa::path(
+    get,
+    path = "/api/v1/mempool/tx/{txid}",
+    params(("txid" = String, Path, description = "transaction id")),
+    responses(
+        (status = 200, description = "projected block the transaction is expected in", body = [ProjectedTxResponse]),
+        (status = 400, description = "invalid txid", body = [AppResponseError]),
+        (status = 404, description = "transaction not in the mempool", body = [AppResponseError]),
+    )
+)]
+pub async fn get_projected_tx(
+    State(state): State<AppState>,
+    Path(txid): Path<String>,
+) -> AppResult<Json<ProjectedTxResponse>> {
+    match service::mempool::projected_tx(&state, &txid).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => {
+            warn!("Failed to get projected block of {txid}: {e:?}.");
+            Err(e)
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use axum::extract::{Query, State};
+use axum::Json;
+use tracing::warn;
+
+use crate::dto::request::MiningPeriodQueryParam;
+use crate::dto::response::{DifficultyAdjustmentResponse, HashrateResponse, MiningPoolsResponse};
+use crate::error::AppResult;
+use crate::server::state::AppState;
+use crate::service;
+
+// Mining pools
+#[utoipa::path(
+    get,
+    path = "/api/v1/mining/pools",
+    params(MiningPeriodQueryParam),
+    responses(
+        (status = 200, description = "block share and estimated hashrate per pool", body = [MiningPoolsResponse]),
+        (status = 400, description = "invalid period", body = [AppResponseError]),
+    )
+)]
+pub async fn get_mining_pools(
+    State(state): State<AppState>,
+    Query(param): Query<MiningPeriodQueryParam>,
+) -> AppResult<Json<MiningPoolsResponse>> {
+    match service::pool::list_stats(&state, param).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => {
+            warn!("Failed to get mining pools: {e:?}.");
+            Err(e)
+        }
+    }
+}
+
+// Difficulty adjustment
+#[utoipa::path(
+    get,
+    path = "/api/v1/mining/difficulty-adjustment",
+    responses(
+        (status = 200, description = "progress of the current difficulty epoch and the expected change", body = [DifficultyAdjustmentResponse]),
+        (status = 404, description = "no block indexed yet", body = [AppResponseError]),
+    )
+)]
+pub async fn get_difficulty_adjustment(
+    State(state): State<AppState>,
+) -> AppResult<Json<DifficultyAdjustmentResponse>> {
+    match service::mining::difficulty_adjustment(&state).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => {
+            warn!("Failed to get difficulty adjustment: {e:?}.");
+            Err(e)
+        }
+    }
+}
+
+// Network hashrate
+#[utoipa::path(
+    get,
+    path = "/api/v1/mining/hashrate",
+    params(MiningPeriodQueryParam),
+    responses(
+        (status = 200, description = "estimated network hashrate series, oldest first", body = [HashrateResponse]),
+        (status = 400, description = "invalid period", body = [AppResponseError]),
+    )
+)]
+pub async fn get_hashrate(
+    State(state): State<AppState>,
+    Query(param): Query<MiningPeriodQueryParam>,
+) -> AppResult<Json<HashrateResponse>> {
+    match service::mining::hashrate(&state, param).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => {
+            warn!("Failed to get hashrate: {e:?}.");
+            Err(e)
+        }
+    }
+}
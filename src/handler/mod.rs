@@ -1,2 +1,8 @@
+pub mod address;
+pub mod block;
+pub mod fee;
+pub mod mempool;
+pub mod mining;
 pub mod openapi;
 pub mod server;
+pub mod transaction;
@@ -1,4 +1,13 @@
-use crate::dto::response::MessageResponse;
+use crate::dto::request::{
+    MerkleProofFormat, MerkleProofQueryParam, MiningPeriod, MiningPeriodQueryParam, PageQueryParam,
+};
+use crate::dto::response::{
+    AddressResponse, AddressTxResponse, AddressTxsResponse, BlockResponse, BlockTxResponse,
+    BlockTxsResponse, BlocksResponse, CoinbaseResponse, DifficultyAdjustmentResponse, ElectrumMerkleProofResponse, FeeEstimate, FeeHistogramBucket, FeeSource,
+    HashratePoint, HashrateResponse, MempoolResponse, MerkleProofResponse, MessageResponse, MiningPoolResponse, NetworkResponse, MiningPoolsResponse, ProjectedBlockResponse, ProjectedTxResponse,
+    RecommendedFeesResponse, TransactionResponse, TxOutProofResponse, TxInputResponse, TxOutputResponse,
+};
+use crate::error::AppResponseError;
 use utoipa::{Modify, OpenApi};
 use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
 #[derive(OpenApi)]
@@ -10,19 +19,82 @@ use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
     paths(
         //server Api
         crate::handler::server::health_check,
+        crate::handler::server::get_network,
+        //address Api
+        crate::handler::address::get_address,
+        crate::handler::address::get_address_txs,
+        //block Api
+        crate::handler::block::get_blocks,
+        crate::handler::block::get_block_by_height,
+        crate::handler::block::get_block_by_hash,
+        crate::handler::block::get_block_txs,
+        //transaction Api
+        crate::handler::transaction::get_transaction,
+        crate::handler::transaction::get_merkle_proof,
+        //mempool Api
+        crate::handler::mempool::get_mempool,
+        crate::handler::mempool::list_mempool_txids,
+        crate::handler::mempool::list_projected_blocks,
+        crate::handler::mempool::get_projected_tx,
+        //fee Api
+        crate::handler::fee::get_recommended_fees,
+        //mining Api
+        crate::handler::mining::get_mining_pools,
+        crate::handler::mining::get_difficulty_adjustment,
+        crate::handler::mining::get_hashrate,
     ),
     components(
         schemas(
-            MessageResponse
+            MessageResponse,
+            NetworkResponse,
+            AppResponseError,
+            PageQueryParam,
+            AddressResponse,
+            AddressTxResponse,
+            AddressTxsResponse,
+            BlockResponse,
+            BlocksResponse,
+            BlockTxResponse,
+            BlockTxsResponse,
+            TransactionResponse,
+            TxInputResponse,
+            TxOutputResponse,
+            MempoolResponse,
+            FeeHistogramBucket,
+            ProjectedBlockResponse,
+            ProjectedTxResponse,
+            RecommendedFeesResponse,
+            FeeEstimate,
+            FeeSource,
+            MiningPeriodQueryParam,
+            MiningPeriod,
+            MiningPoolsResponse,
+            MiningPoolResponse,
+            CoinbaseResponse,
+            DifficultyAdjustmentResponse,
+            HashrateResponse,
+            HashratePoint,
+            MerkleProofQueryParam,
+            MerkleProofFormat,
+            MerkleProofResponse,
+            ElectrumMerkleProofResponse,
+            TxOutProofResponse,
         )
     ),
     tags(
-        (name = "crate::handler::server", description = "server endpoints.")
+        (name = "crate::handler::server", description = "server endpoints."),
+        (name = "crate::handler::address", description = "address endpoints."),
+        (name = "crate::handler::block", description = "block endpoints."),
+        (name = "crate::handler::transaction", description = "transaction endpoints."),
+        (name = "crate::handler::mempool", description = "mempool endpoints."),
+        (name = "crate::handler::fee", description = "fee endpoints."),
+        (name = "crate::handler::mining", description = "mining endpoints.")
     ),
     modifiers()
 )]
 pub struct ApiDoc;
 
+#[allow(dead_code)]
 struct SecurityAddon;
 
 impl Modify for SecurityAddon {
@@ -1,6 +1,9 @@
+use axum::extract::State;
 use axum::Json;
-use crate::dto::response::{MessageResponse, ServiceStatusResponse};
+use bitcoincore_rpc::bitcoin::blockdata::constants::genesis_block;
+use crate::dto::response::{MessageResponse, NetworkResponse, ServiceStatusResponse};
 use crate::error::AppResult;
+use crate::server::state::AppState;
 
 // Health check
 #[utoipa::path(
@@ -14,6 +17,23 @@ pub async fn health_check() -> AppResult<Json<MessageResponse>> {
     Ok(Json(MessageResponse::new("Ok")))
 }
 
+// Network
+#[utoipa::path(
+    get,
+    path = "/api/v1/server/network",
+    responses(
+        (status = 200, description = "the chain this explorer indexes", body = [NetworkResponse])
+    )
+)]
+pub async fn get_network(State(state): State<AppState>) -> AppResult<Json<NetworkResponse>> {
+    let network = state.config.bitcoin.network;
+    Ok(Json(NetworkResponse {
+        network: network.to_string(),
+        chain: network.to_core_arg().to_string(),
+        genesis_hash: genesis_block(network).block_hash().to_string(),
+    }))
+}
+
 pub async fn server_state() -> AppResult<Json<ServiceStatusResponse>> {
     let resp = ServiceStatusResponse {
         db: true,
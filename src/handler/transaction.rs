@@ -0,0 +1,58 @@
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use tracing::warn;
+
+use crate::dto::request::MerkleProofQueryParam;
+use crate::dto::response::{MerkleProofResponse, TransactionResponse};
+use crate::error::AppResult;
+use crate::server::state::AppState;
+use crate::service;
+
+// Transaction detail
+#[utoipa::path(
+    get,
+    path = "/api/v1/tx/{txid}",
+    params(("txid" = String, Path, description = "transaction id")),
+    responses(
+        (status = 200, description = "decoded transaction with prevouts and confirmations", body = [TransactionResponse]),
+        (status = 400, description = "invalid txid", body = [AppResponseError]),
+        (status = 404, description = "transaction unknown to the index and the node", body = [AppResponseError]),
+    )
+)]
+pub async fn get_transaction(
+    State(state): State<AppState>,
+    Path(txid): Path<String>,
+) -> AppResult<Json<TransactionResponse>> {
+    match service::transaction::get(&state, &txid).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => {
+            warn!("Failed to get transaction {txid}: {e:?}.");
+            Err(e)
+        }
+    }
+}
+
+// Merkle proof
+#[utoipa::path(
+    get,
+    path = "/api/v1/tx/{txid}/merkle-proof",
+    params(("txid" = String, Path, description = "transaction id"), MerkleProofQueryParam),
+    responses(
+        (status = 200, description = "inclusion proof of a confirmed transaction", body = [MerkleProofResponse]),
+        (status = 400, description = "invalid txid or format", body = [AppResponseError]),
+        (status = 404, description = "transaction not confirmed in the index, or block header not stored yet", body = [AppResponseError]),
+    )
+)]
+pub async fn get_merkle_proof(
+    State(state): State<AppState>,
+    Path(txid): Path<String>,
+    Query(param): Query<MerkleProofQueryParam>,
+) -> AppResult<Json<MerkleProofResponse>> {
+    match service::merkle::proof(&state, &txid, param).await {
+        Ok(resp) => Ok(Json(resp)),
+        Err(e) => {
+            warn!("Failed to get merkle proof of transaction {txid}: {e:?}.");
+            Err(e)
+        }
+    }
+}
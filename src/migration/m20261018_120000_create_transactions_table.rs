@@ -0,0 +1,43 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "CREATE TABLE transactions (
+                    txid varchar(64) PRIMARY KEY NOT NULL,
+                    block_height integer NOT NULL,
+                    block_index integer NOT NULL,
+                    size integer NOT NULL,
+                    vsize integer NOT NULL,
+                    weight integer NOT NULL,
+                    fee bigint NOT NULL,
+                    feerate double precision NOT NULL,
+                    version integer NOT NULL,
+                    locktime bigint NOT NULL,
+                    input_count integer NOT NULL,
+                    output_count integer NOT NULL,
+                    is_segwit boolean NOT NULL,
+                    is_taproot boolean NOT NULL
+                )",
+        ).await?;
+        db.execute_unprepared(
+            "CREATE INDEX transactions_block_height_idx ON transactions (block_height, block_index)",
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("DROP TABLE transactions")
+            .await?;
+
+        Ok(())
+    }
+}
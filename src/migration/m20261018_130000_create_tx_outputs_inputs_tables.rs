@@ -0,0 +1,57 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "CREATE TABLE tx_outputs (
+                    txid varchar(64) NOT NULL,
+                    vout integer NOT NULL,
+                    block_height integer NOT NULL,
+                    value bigint NOT NULL,
+                    script_pubkey text NOT NULL,
+                    script_type varchar(32) NOT NULL,
+                    address varchar(100),
+                    spent_by_txid varchar(64),
+                    spent_by_vin integer,
+                    spent_height integer,
+                    PRIMARY KEY (txid, vout)
+                )",
+        ).await?;
+        db.execute_unprepared("CREATE INDEX tx_outputs_block_height_idx ON tx_outputs (block_height)")
+            .await?;
+        db.execute_unprepared("CREATE INDEX tx_outputs_spent_height_idx ON tx_outputs (spent_height)")
+            .await?;
+        db.execute_unprepared(
+            "CREATE TABLE tx_inputs (
+                    txid varchar(64) NOT NULL,
+                    vin integer NOT NULL,
+                    block_height integer NOT NULL,
+                    prev_txid varchar(64),
+                    prev_vout integer,
+                    value bigint NOT NULL,
+                    address varchar(100),
+                    script_sig text NOT NULL,
+                    witness json NOT NULL,
+                    sequence bigint NOT NULL,
+                    PRIMARY KEY (txid, vin)
+                )",
+        ).await?;
+        db.execute_unprepared("CREATE INDEX tx_inputs_block_height_idx ON tx_inputs (block_height)")
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared("DROP TABLE tx_inputs").await?;
+        db.execute_unprepared("DROP TABLE tx_outputs").await?;
+
+        Ok(())
+    }
+}
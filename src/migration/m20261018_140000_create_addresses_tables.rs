@@ -0,0 +1,48 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "CREATE TABLE addresses (
+                    address varchar(100) PRIMARY KEY NOT NULL,
+                    balance bigint NOT NULL,
+                    total_received bigint NOT NULL,
+                    total_sent bigint NOT NULL,
+                    tx_count integer NOT NULL,
+                    first_seen_height integer NOT NULL,
+                    last_seen_height integer NOT NULL
+                )",
+        ).await?;
+        db.execute_unprepared(
+            "CREATE TABLE address_txs (
+                    address varchar(100) NOT NULL,
+                    txid varchar(64) NOT NULL,
+                    block_height integer NOT NULL,
+                    block_index integer NOT NULL,
+                    received bigint NOT NULL,
+                    sent bigint NOT NULL,
+                    PRIMARY KEY (address, txid)
+                )",
+        ).await?;
+        db.execute_unprepared(
+            "CREATE INDEX address_txs_history_idx ON address_txs (address, block_height DESC, block_index DESC)",
+        ).await?;
+        db.execute_unprepared("CREATE INDEX address_txs_block_height_idx ON address_txs (block_height)")
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared("DROP TABLE address_txs").await?;
+        db.execute_unprepared("DROP TABLE addresses").await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,32 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared(
+            "CREATE TABLE pools (
+                    id integer PRIMARY KEY NOT NULL,
+                    name varchar(100) NOT NULL,
+                    link varchar(255) NOT NULL,
+                    tags json NOT NULL,
+                    addresses json NOT NULL
+                )",
+        ).await?;
+        db.execute_unprepared("CREATE INDEX blocks_block_created_at_idx ON blocks (block_created_at)")
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared("DROP INDEX IF EXISTS blocks_block_created_at_idx").await?;
+        db.execute_unprepared("DROP TABLE pools").await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,40 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // `coinbase_tags` stays NULL until the coinbase of an existing block is decoded.
+        db.execute_unprepared(
+            "ALTER TABLE blocks
+                    ADD COLUMN coinbase_height integer,
+                    ADD COLUMN coinbase_tags json,
+                    ADD COLUMN witness_commitment varchar(64),
+                    ADD COLUMN merged_mining_root varchar(64),
+                    ADD COLUMN coinbase_malformed boolean NOT NULL DEFAULT false",
+        ).await?;
+        db.execute_unprepared(
+            "CREATE INDEX blocks_coinbase_malformed_idx ON blocks (height) WHERE coinbase_malformed",
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared("DROP INDEX IF EXISTS blocks_coinbase_malformed_idx").await?;
+        db.execute_unprepared(
+            "ALTER TABLE blocks
+                    DROP COLUMN coinbase_height,
+                    DROP COLUMN coinbase_tags,
+                    DROP COLUMN witness_commitment,
+                    DROP COLUMN merged_mining_root,
+                    DROP COLUMN coinbase_malformed",
+        ).await?;
+
+        Ok(())
+    }
+}
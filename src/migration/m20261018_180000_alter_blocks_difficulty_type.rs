@@ -0,0 +1,23 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Stored values stay truncated until the indexer recomputes them from the block bits.
+        db.execute_unprepared("ALTER TABLE blocks ALTER COLUMN difficulty TYPE double precision")
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared("ALTER TABLE blocks ALTER COLUMN difficulty TYPE bigint").await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // The header fields stay NULL for existing blocks until the header verification
+        // reads them from the node. `bits` and `nonce` are unsigned 32-bit integers.
+        db.execute_unprepared(
+            "ALTER TABLE blocks
+                    ADD COLUMN version integer,
+                    ADD COLUMN previous_hash varchar(64),
+                    ADD COLUMN merkle_root varchar(64),
+                    ADD COLUMN bits bigint,
+                    ADD COLUMN nonce bigint",
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            "ALTER TABLE blocks
+                    DROP COLUMN version,
+                    DROP COLUMN previous_hash,
+                    DROP COLUMN merkle_root,
+                    DROP COLUMN bits,
+                    DROP COLUMN nonce",
+        ).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "CREATE TABLE deferred_indexes (
+                    name varchar(63) PRIMARY KEY NOT NULL,
+                    definition text NOT NULL
+                )",
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        // Indexes dropped by an unfinished bulk sync are only defined here.
+        db.execute_unprepared(
+            "DO $$
+            DECLARE deferred record;
+            BEGIN
+                FOR deferred IN SELECT definition FROM deferred_indexes LOOP
+                    EXECUTE deferred.definition;
+                END LOOP;
+            END $$",
+        ).await?;
+        db.execute_unprepared("DROP TABLE deferred_indexes").await?;
+
+        Ok(())
+    }
+}
@@ -1,4 +1,13 @@
 mod m20240414_192211_create_blocks_table;
+mod m20261018_120000_create_transactions_table;
+mod m20261018_130000_create_tx_outputs_inputs_tables;
+mod m20261018_140000_create_addresses_tables;
+mod m20261018_150000_create_blocks_hash_index;
+mod m20261018_160000_create_pools_table;
+mod m20261018_170000_add_blocks_coinbase_columns;
+mod m20261018_180000_alter_blocks_difficulty_type;
+mod m20261018_190000_add_blocks_header_columns;
+mod m20261018_200000_create_deferred_indexes_table;
 
 pub use sea_orm_migration::prelude::*;
 
@@ -8,7 +17,16 @@ pub struct Migrator;
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
         vec![
-            Box::new(m20240414_192211_create_blocks_table::Migration)
+            Box::new(m20240414_192211_create_blocks_table::Migration),
+            Box::new(m20261018_120000_create_transactions_table::Migration),
+            Box::new(m20261018_130000_create_tx_outputs_inputs_tables::Migration),
+            Box::new(m20261018_140000_create_addresses_tables::Migration),
+            Box::new(m20261018_150000_create_blocks_hash_index::Migration),
+            Box::new(m20261018_160000_create_pools_table::Migration),
+            Box::new(m20261018_170000_add_blocks_coinbase_columns::Migration),
+            Box::new(m20261018_180000_alter_blocks_difficulty_type::Migration),
+            Box::new(m20261018_190000_add_blocks_header_columns::Migration),
+            Box::new(m20261018_200000_create_deferred_indexes_table::Migration),
         ]
     }
 }
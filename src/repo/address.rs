@@ -0,0 +1,160 @@
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DbBackend, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+    Statement,
+};
+use sqlx::PgConnection;
+
+use crate::entity::{address, address_tx};
+use crate::error::AppResult;
+use crate::repo::copy::CopyRows;
+
+#[tracing::instrument(skip_all)]
+pub async fn find_by_address<C>(conn: &C, address: &str) -> AppResult<Option<address::Model>>
+where
+    C: ConnectionTrait,
+{
+    let model = address::Entity::find_by_id(address).one(conn).await?;
+    Ok(model)
+}
+
+/// Returns one page of an address history, newest first, with the total row count.
+#[tracing::instrument(skip_all)]
+pub async fn find_txs_page<C>(
+    conn: &C,
+    address: &str,
+    page_num: u64,
+    page_size: u64,
+) -> AppResult<(Vec<address_tx::Model>, u64)>
+where
+    C: ConnectionTrait,
+{
+    let paginator = address_tx::Entity::find()
+        .filter(address_tx::Column::Address.eq(address))
+        .order_by_desc(address_tx::Column::BlockHeight)
+        .order_by_desc(address_tx::Column::BlockIndex)
+        .paginate(conn, page_size);
+    let total = paginator.num_items().await?;
+    let models = paginator.fetch_page(page_num).await?;
+    Ok((models, total))
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn save_txs<C>(conn: &C, models: Vec<address_tx::ActiveModel>) -> AppResult
+where
+    C: ConnectionTrait,
+{
+    let on_conflict = OnConflict::columns([address_tx::Column::Address, address_tx::Column::Txid])
+        .do_nothing()
+        .to_owned();
+    super::insert_chunked(conn, models, on_conflict).await
+}
+
+/// Adds the per-block deltas in `models` to the running totals of each address.
+#[tracing::instrument(skip_all)]
+pub async fn apply_deltas<C>(conn: &C, models: Vec<address::ActiveModel>) -> AppResult
+where
+    C: ConnectionTrait,
+{
+    let on_conflict = OnConflict::column(address::Column::Address)
+        .value(address::Column::Balance, Expr::cust("addresses.balance + excluded.balance"))
+        .value(
+            address::Column::TotalReceived,
+            Expr::cust("addresses.total_received + excluded.total_received"),
+        )
+        .value(address::Column::TotalSent, Expr::cust("addresses.total_sent + excluded.total_sent"))
+        .value(address::Column::TxCount, Expr::cust("addresses.tx_count + excluded.tx_count"))
+        .value(address::Column::LastSeenHeight, Expr::cust("excluded.last_seen_height"))
+        .to_owned();
+    super::insert_chunked(conn, models, on_conflict).await
+}
+
+/// Reverts the effect of every transaction above `height` on the address totals.
+#[tracing::instrument(skip_all)]
+pub async fn revert_above<C>(conn: &C, height: i32) -> AppResult
+where
+    C: ConnectionTrait,
+{
+    let statements = [
+        "UPDATE addresses AS a
+            SET balance = a.balance - r.received + r.sent,
+                total_received = a.total_received - r.received,
+                total_sent = a.total_sent - r.sent,
+                tx_count = a.tx_count - r.tx_count
+            FROM (
+                SELECT address, SUM(received) AS received, SUM(sent) AS sent, COUNT(*) AS tx_count
+                FROM address_txs WHERE block_height > $1 GROUP BY address
+            ) AS r
+            WHERE a.address = r.address",
+        "DELETE FROM address_txs WHERE block_height > $1",
+        "DELETE FROM addresses WHERE tx_count = 0",
+        "UPDATE addresses AS a
+            SET last_seen_height = (SELECT MAX(block_height) FROM address_txs AS t WHERE t.address = a.address)
+            WHERE a.last_seen_height > $1",
+    ];
+    for sql in statements {
+        conn.execute(Statement::from_sql_and_values(DbBackend::Postgres, sql, [height.into()]))
+            .await?;
+    }
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn copy_txs(conn: &mut PgConnection, models: &[address_tx::Model]) -> AppResult<u64> {
+    let mut rows = CopyRows::default();
+    for model in models {
+        rows.push(&[
+            &model.address,
+            &model.txid,
+            &model.block_height,
+            &model.block_index,
+            &model.received,
+            &model.sent,
+        ]);
+    }
+    let statement =
+        "COPY address_txs (address, txid, block_height, block_index, received, sent) FROM STDIN";
+    super::copy::copy_in(conn, statement, rows).await
+}
+
+/// Same as [`apply_deltas`] for a whole batch: the deltas are staged with `COPY` and
+/// merged into the running totals in a single statement.
+#[tracing::instrument(skip_all)]
+pub async fn copy_deltas(conn: &mut PgConnection, models: &[address::Model]) -> AppResult<u64> {
+    if models.is_empty() {
+        return Ok(0);
+    }
+    super::copy::execute(
+        conn,
+        "CREATE TEMP TABLE addresses_staging (LIKE addresses) ON COMMIT DROP",
+    )
+    .await?;
+    let mut rows = CopyRows::default();
+    for model in models {
+        rows.push(&[
+            &model.address,
+            &model.balance,
+            &model.total_received,
+            &model.total_sent,
+            &model.tx_count,
+            &model.first_seen_height,
+            &model.last_seen_height,
+        ]);
+    }
+    let statement = "COPY addresses_staging (address, balance, total_received, total_sent, tx_count, \
+        first_seen_height, last_seen_height) FROM STDIN";
+    super::copy::copy_in(conn, statement, rows).await?;
+    super::copy::execute(
+        conn,
+        "INSERT INTO addresses
+            SELECT address, balance, total_received, total_sent, tx_count, first_seen_height, last_seen_height
+            FROM addresses_staging
+            ON CONFLICT (address) DO UPDATE SET
+                balance = addresses.balance + excluded.balance,
+                total_received = addresses.total_received + excluded.total_received,
+                total_sent = addresses.total_sent + excluded.total_sent,
+                tx_count = addresses.tx_count + excluded.tx_count,
+                last_seen_height = excluded.last_seen_height",
+    )
+    .await
+}
@@ -0,0 +1,404 @@
+use chrono::NaiveDateTime;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DbBackend, EntityTrait, FromQueryResult,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Statement,
+};
+use sqlx::PgConnection;
+
+use crate::entity::block;
+use crate::error::AppResult;
+use crate::repo::copy::CopyRows;
+
+#[tracing::instrument(skip_all)]
+pub async fn save<C>(conn: &C, model: block::ActiveModel) -> AppResult<block::Model>
+where
+    C: ConnectionTrait,
+{
+    let model = model.insert(conn).await?;
+    Ok(model)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn find_max_height<C>(conn: &C) -> AppResult<Option<i32>>
+where
+    C: ConnectionTrait,
+{
+    let height = block::Entity::find()
+        .select_only()
+        .column(block::Column::Height)
+        .order_by_desc(block::Column::Height)
+        .into_tuple::<i32>()
+        .one(conn)
+        .await?;
+    Ok(height)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn find_tip<C>(conn: &C) -> AppResult<Option<block::Model>>
+where
+    C: ConnectionTrait,
+{
+    let model = block::Entity::find()
+        .order_by_desc(block::Column::Height)
+        .one(conn)
+        .await?;
+    Ok(model)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn find_by_height<C>(conn: &C, height: i32) -> AppResult<Option<block::Model>>
+where
+    C: ConnectionTrait,
+{
+    let model = block::Entity::find_by_id(height).one(conn).await?;
+    Ok(model)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn find_by_hash<C>(conn: &C, hash: &str) -> AppResult<Option<block::Model>>
+where
+    C: ConnectionTrait,
+{
+    let model = block::Entity::find()
+        .filter(block::Column::Hash.eq(hash))
+        .one(conn)
+        .await?;
+    Ok(model)
+}
+
+/// Returns one page of blocks, newest first, with the total row count.
+#[tracing::instrument(skip_all)]
+pub async fn find_page<C>(conn: &C, page_num: u64, page_size: u64) -> AppResult<(Vec<block::Model>, u64)>
+where
+    C: ConnectionTrait,
+{
+    let paginator = block::Entity::find()
+        .order_by_desc(block::Column::Height)
+        .paginate(conn, page_size);
+    let total = paginator.num_items().await?;
+    let models = paginator.fetch_page(page_num).await?;
+    Ok((models, total))
+}
+
+/// Returns the `median_fee` of the last `limit` blocks that paid any fees, newest first.
+#[tracing::instrument(skip_all)]
+pub async fn find_recent_median_fees<C>(conn: &C, limit: u64) -> AppResult<Vec<f64>>
+where
+    C: ConnectionTrait,
+{
+    let fees = block::Entity::find()
+        .select_only()
+        .column(block::Column::MedianFee)
+        .filter(block::Column::MedianFee.gt(0.0))
+        .order_by_desc(block::Column::Height)
+        .limit(limit)
+        .into_tuple()
+        .all(conn)
+        .await?;
+    Ok(fees)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn delete_above<C>(conn: &C, height: i32) -> AppResult<u64>
+where
+    C: ConnectionTrait,
+{
+    let result = block::Entity::delete_many()
+        .filter(block::Column::Height.gt(height))
+        .exec(conn)
+        .await?;
+    Ok(result.rows_affected)
+}
+
+/// Coinbase of a stored block with the addresses its outputs pay to, joined by commas.
+#[derive(Debug, FromQueryResult)]
+pub struct CoinbaseRow {
+    pub height: i32,
+    pub coinbase_raw: Option<String>,
+    pub pool_id: Option<i32>,
+    pub addresses: Option<String>,
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn find_coinbases<C>(conn: &C, from: i32, to: i32) -> AppResult<Vec<CoinbaseRow>>
+where
+    C: ConnectionTrait,
+{
+    let sql = "SELECT b.height, b.coinbase_raw, b.pool_id, string_agg(o.address, ',') AS addresses
+        FROM blocks AS b
+        LEFT JOIN transactions AS t ON t.block_height = b.height AND t.block_index = 0
+        LEFT JOIN tx_outputs AS o ON o.txid = t.txid
+        WHERE b.height BETWEEN $1 AND $2
+        GROUP BY b.height
+        ORDER BY b.height";
+    let rows = CoinbaseRow::find_by_statement(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        sql,
+        [from.into(), to.into()],
+    ))
+    .all(conn)
+    .await?;
+    Ok(rows)
+}
+
+/// Sets the `pool_id` of every `(height, pool_id)` pair.
+#[tracing::instrument(skip_all)]
+pub async fn update_pool_ids<C>(conn: &C, pool_ids: &[(i32, i32)]) -> AppResult
+where
+    C: ConnectionTrait,
+{
+    for chunk in pool_ids.chunks(super::CHUNK_SIZE) {
+        let values: Vec<_> = chunk
+            .iter()
+            .map(|(height, pool_id)| format!("({height}, {pool_id})"))
+            .collect();
+        let sql = format!(
+            "UPDATE blocks AS b SET pool_id = v.pool_id
+                FROM (VALUES {}) AS v (height, pool_id)
+                WHERE b.height = v.height",
+            values.join(", ")
+        );
+        conn.execute_unprepared(&sql).await?;
+    }
+    Ok(())
+}
+
+/// Coinbase of a block stored before coinbases were decoded, with the coinbase output
+/// scripts that look like a witness commitment, joined by commas.
+#[derive(Debug, FromQueryResult)]
+pub struct UndecodedCoinbaseRow {
+    pub height: i32,
+    pub coinbase_raw: Option<String>,
+    pub commitments: Option<String>,
+}
+
+/// Blocks from `from` to `to` inclusive, lowest first.
+#[tracing::instrument(skip_all)]
+pub async fn find_range<C>(conn: &C, from: i32, to: i32) -> AppResult<Vec<block::Model>>
+where
+    C: ConnectionTrait,
+{
+    let models = block::Entity::find()
+        .filter(block::Column::Height.between(from, to))
+        .order_by_asc(block::Column::Height)
+        .all(conn)
+        .await?;
+    Ok(models)
+}
+
+/// Returns up to `limit` blocks above `after` whose coinbase was not decoded yet.
+#[tracing::instrument(skip_all)]
+pub async fn find_undecoded_coinbases<C>(conn: &C, after: i32, limit: u64) -> AppResult<Vec<UndecodedCoinbaseRow>>
+where
+    C: ConnectionTrait,
+{
+    let sql = "SELECT b.height, b.coinbase_raw, string_agg(o.script_pubkey, ',' ORDER BY o.vout) AS commitments
+        FROM (
+            SELECT height, coinbase_raw FROM blocks
+            WHERE coinbase_tags IS NULL AND height > $1
+            ORDER BY height LIMIT $2
+        ) AS b
+        LEFT JOIN transactions AS t ON t.block_height = b.height AND t.block_index = 0
+        LEFT JOIN tx_outputs AS o ON o.txid = t.txid AND o.script_pubkey LIKE '6a24aa21a9ed%'
+        GROUP BY b.height, b.coinbase_raw
+        ORDER BY b.height";
+    let rows = UndecodedCoinbaseRow::find_by_statement(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        sql,
+        [after.into(), (limit as i64).into()],
+    ))
+    .all(conn)
+    .await?;
+    Ok(rows)
+}
+
+/// Returns up to `limit` blocks above `after` stored before their headers were kept,
+/// lowest first.
+#[tracing::instrument(skip_all)]
+pub async fn find_missing_headers<C>(conn: &C, after: i32, limit: u64) -> AppResult<Vec<block::Model>>
+where
+    C: ConnectionTrait,
+{
+    let models = block::Entity::find()
+        .filter(block::Column::Version.is_null())
+        .filter(block::Column::Height.gt(after))
+        .order_by_asc(block::Column::Height)
+        .limit(limit)
+        .all(conn)
+        .await?;
+    Ok(models)
+}
+
+/// Updates the columns set in `models`, one statement per block.
+#[tracing::instrument(skip_all)]
+pub async fn update_each<C>(conn: &C, models: Vec<block::ActiveModel>) -> AppResult
+where
+    C: ConnectionTrait,
+{
+    for model in models {
+        block::Entity::update(model).exec(conn).await?;
+    }
+    Ok(())
+}
+
+/// Distinct `bits` of the blocks whose stored difficulty lost its fractional part when the
+/// column was an integer. Only the minimum difficulty is a whole number in practice.
+#[tracing::instrument(skip_all)]
+pub async fn find_truncated_bits<C>(conn: &C) -> AppResult<Vec<i64>>
+where
+    C: ConnectionTrait,
+{
+    let bits = block::Entity::find()
+        .select_only()
+        .column(block::Column::Bits)
+        .distinct()
+        .filter(Expr::cust("difficulty = trunc(difficulty)"))
+        .filter(block::Column::Difficulty.gt(1.0))
+        .filter(block::Column::Bits.is_not_null())
+        .order_by_asc(block::Column::Bits)
+        .into_tuple()
+        .all(conn)
+        .await?;
+    Ok(bits)
+}
+
+/// Sets the difficulty of every block with `bits`.
+#[tracing::instrument(skip_all)]
+pub async fn update_difficulty<C>(conn: &C, bits: i64, difficulty: f64) -> AppResult<u64>
+where
+    C: ConnectionTrait,
+{
+    let result = block::Entity::update_many()
+        .col_expr(block::Column::Difficulty, Expr::value(difficulty))
+        .filter(block::Column::Bits.eq(bits))
+        .exec(conn)
+        .await?;
+    Ok(result.rows_affected)
+}
+
+/// Blocks mined in one bucket of a hashrate series.
+#[derive(Debug, FromQueryResult)]
+pub struct HashrateBucket {
+    pub bucket_start: NaiveDateTime,
+    pub block_count: i64,
+    pub average_difficulty: f64,
+}
+
+/// Groups the blocks mined since `since` by `unit`, any `date_trunc` unit, oldest first.
+#[tracing::instrument(skip_all)]
+pub async fn find_hashrate_buckets<C>(
+    conn: &C,
+    unit: &str,
+    since: Option<NaiveDateTime>,
+) -> AppResult<Vec<HashrateBucket>>
+where
+    C: ConnectionTrait,
+{
+    let sql = "SELECT date_trunc($1, block_created_at) AS bucket_start,
+            COUNT(*) AS block_count,
+            AVG(difficulty) AS average_difficulty
+        FROM blocks
+        WHERE $2::timestamp IS NULL OR block_created_at >= $2
+        GROUP BY bucket_start
+        ORDER BY bucket_start";
+    let buckets = HashrateBucket::find_by_statement(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        sql,
+        [unit.into(), since.into()],
+    ))
+    .all(conn)
+    .await?;
+    Ok(buckets)
+}
+
+/// Counts the blocks mined since `since` per `pool_id`.
+#[tracing::instrument(skip_all)]
+pub async fn count_by_pool<C>(conn: &C, since: Option<NaiveDateTime>) -> AppResult<Vec<(Option<i32>, i64)>>
+where
+    C: ConnectionTrait,
+{
+    let mut query = block::Entity::find()
+        .select_only()
+        .column(block::Column::PoolId)
+        .column_as(block::Column::Height.count(), "block_count")
+        .group_by(block::Column::PoolId);
+    if let Some(since) = since {
+        query = query.filter(block::Column::BlockCreatedAt.gte(since));
+    }
+    let counts = query.into_tuple().all(conn).await?;
+    Ok(counts)
+}
+
+/// Totals over the blocks mined since `since`.
+#[derive(Debug, FromQueryResult)]
+pub struct BlockWindow {
+    pub block_count: i64,
+    pub total_difficulty: Option<f64>,
+    pub first_block_at: Option<NaiveDateTime>,
+    pub last_block_at: Option<NaiveDateTime>,
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn find_window<C>(conn: &C, since: Option<NaiveDateTime>) -> AppResult<BlockWindow>
+where
+    C: ConnectionTrait,
+{
+    let sql = "SELECT COUNT(*) AS block_count,
+            SUM(difficulty) AS total_difficulty,
+            MIN(block_created_at) AS first_block_at,
+            MAX(block_created_at) AS last_block_at
+        FROM blocks
+        WHERE $1::timestamp IS NULL OR block_created_at >= $1";
+    let window = BlockWindow::find_by_statement(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        sql,
+        [since.into()],
+    ))
+    .one(conn)
+    .await?
+    .unwrap_or(BlockWindow {
+        block_count: 0,
+        total_difficulty: None,
+        first_block_at: None,
+        last_block_at: None,
+    });
+    Ok(window)
+}
+
+/// Streams `models` into `blocks`.
+#[tracing::instrument(skip_all)]
+pub async fn copy_many(conn: &mut PgConnection, models: &[block::Model]) -> AppResult<u64> {
+    let mut rows = CopyRows::default();
+    for model in models {
+        rows.push(&[
+            &model.height,
+            &model.hash,
+            &model.block_created_at,
+            &model.size,
+            &model.weight,
+            &model.tx_count,
+            &model.coinbase_raw,
+            &model.difficulty,
+            &model.pool_id,
+            &model.fees,
+            &model.fee_span,
+            &model.median_fee,
+            &model.coinbase_height,
+            &model.coinbase_tags,
+            &model.witness_commitment,
+            &model.merged_mining_root,
+            &model.coinbase_malformed,
+            &model.version,
+            &model.previous_hash,
+            &model.merkle_root,
+            &model.bits,
+            &model.nonce,
+        ]);
+    }
+    let statement = "COPY blocks (height, hash, block_created_at, size, weight, tx_count, coinbase_raw, \
+        difficulty, pool_id, fees, fee_span, median_fee, coinbase_height, coinbase_tags, \
+        witness_commitment, merged_mining_root, coinbase_malformed, version, previous_hash, merkle_root, \
+        bits, nonce) FROM STDIN";
+    super::copy::copy_in(conn, statement, rows).await
+}
@@ -0,0 +1,215 @@
+use chrono::NaiveDateTime;
+use sea_orm::{DbErr, RuntimeErr};
+use sqlx::{PgConnection, PgPool};
+use tracing::info;
+
+use crate::error::{AppError, AppResult};
+
+/// Secondary indexes dropped while backfilling with `COPY` and rebuilt once the backfill
+/// reaches the tip. The migrations define them: their definitions are moved from
+/// `pg_indexes` to the `deferred_indexes` table when they are dropped, so an interrupted
+/// bulk sync still rebuilds them. Primary keys stay in place because prevouts are looked
+/// up by them, and so does the unique `blocks_hash_idx`.
+const DEFERRED_INDEXES: &[&str] = &[
+    "transactions_block_height_idx",
+    "tx_outputs_block_height_idx",
+    "tx_outputs_spent_height_idx",
+    "tx_inputs_block_height_idx",
+    "address_txs_history_idx",
+    "address_txs_block_height_idx",
+];
+
+/// A value that can be written as one column of the `COPY` text format.
+pub trait CopyValue {
+    fn write_copy(&self, out: &mut String);
+}
+
+impl CopyValue for i32 {
+    fn write_copy(&self, out: &mut String) {
+        out.push_str(&self.to_string());
+    }
+}
+
+impl CopyValue for i64 {
+    fn write_copy(&self, out: &mut String) {
+        out.push_str(&self.to_string());
+    }
+}
+
+impl CopyValue for f64 {
+    fn write_copy(&self, out: &mut String) {
+        out.push_str(&self.to_string());
+    }
+}
+
+impl CopyValue for bool {
+    fn write_copy(&self, out: &mut String) {
+        out.push(if *self { 't' } else { 'f' });
+    }
+}
+
+impl CopyValue for str {
+    fn write_copy(&self, out: &mut String) {
+        for c in self.chars() {
+            match c {
+                '\\' => out.push_str("\\\\"),
+                '\t' => out.push_str("\\t"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                c => out.push(c),
+            }
+        }
+    }
+}
+
+impl CopyValue for String {
+    fn write_copy(&self, out: &mut String) {
+        self.as_str().write_copy(out);
+    }
+}
+
+impl CopyValue for serde_json::Value {
+    fn write_copy(&self, out: &mut String) {
+        self.to_string().write_copy(out);
+    }
+}
+
+impl CopyValue for NaiveDateTime {
+    fn write_copy(&self, out: &mut String) {
+        out.push_str(&self.format("%Y-%m-%d %H:%M:%S").to_string());
+    }
+}
+
+impl<T: CopyValue> CopyValue for Option<T> {
+    fn write_copy(&self, out: &mut String) {
+        match self {
+            Some(value) => value.write_copy(out),
+            None => out.push_str("\\N"),
+        }
+    }
+}
+
+/// Rows encoded in the `COPY` text format, ready to be streamed to Postgres.
+#[derive(Debug, Default)]
+pub struct CopyRows {
+    data: String,
+}
+
+impl CopyRows {
+    pub fn push(&mut self, values: &[&dyn CopyValue]) {
+        for (i, value) in values.iter().enumerate() {
+            if i > 0 {
+                self.data.push('\t');
+            }
+            value.write_copy(&mut self.data);
+        }
+        self.data.push('\n');
+    }
+}
+
+/// Streams `rows` through `statement`, a `COPY ... FROM STDIN` statement.
+pub async fn copy_in(conn: &mut PgConnection, statement: &str, rows: CopyRows) -> AppResult<u64> {
+    if rows.data.is_empty() {
+        return Ok(0);
+    }
+    let mut copy = conn.copy_in_raw(statement).await.map_err(sqlx_error)?;
+    copy.send(rows.data.into_bytes()).await.map_err(sqlx_error)?;
+    copy.finish().await.map_err(sqlx_error)
+}
+
+pub async fn execute(conn: &mut PgConnection, sql: &str) -> AppResult<u64> {
+    let result = sqlx::query(sql).execute(conn).await.map_err(sqlx_error)?;
+    Ok(result.rows_affected())
+}
+
+pub fn sqlx_error(error: sqlx::Error) -> AppError {
+    DbErr::Exec(RuntimeErr::SqlxError(error)).into()
+}
+
+pub async fn drop_deferred_indexes(pool: &PgPool) -> AppResult {
+    let mut tx = pool.begin().await.map_err(sqlx_error)?;
+    for name in DEFERRED_INDEXES {
+        // Indexes dropped by an earlier run are already recorded and no longer listed.
+        sqlx::query(
+            "INSERT INTO deferred_indexes (name, definition)
+                SELECT indexname, indexdef FROM pg_indexes
+                WHERE schemaname = current_schema() AND indexname = $1
+            ON CONFLICT (name) DO NOTHING",
+        )
+        .bind(name)
+        .execute(&mut *tx)
+        .await
+        .map_err(sqlx_error)?;
+        execute(&mut tx, &format!("DROP INDEX IF EXISTS {name}")).await?;
+    }
+    tx.commit().await.map_err(sqlx_error)
+}
+
+pub async fn create_deferred_indexes(pool: &PgPool) -> AppResult {
+    let deferred: Vec<(String, String)> = sqlx::query_as("SELECT name, definition FROM deferred_indexes ORDER BY name")
+        .fetch_all(pool)
+        .await
+        .map_err(sqlx_error)?;
+    for (name, definition) in deferred {
+        info!("Creating index {name}.");
+        let mut tx = pool.begin().await.map_err(sqlx_error)?;
+        execute(&mut tx, &definition).await?;
+        sqlx::query("DELETE FROM deferred_indexes WHERE name = $1")
+            .bind(&name)
+            .execute(&mut *tx)
+            .await
+            .map_err(sqlx_error)?;
+        tx.commit().await.map_err(sqlx_error)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::database::{drop_database, migrate_database, setup_new_database, DatabaseClient, DatabaseClientExt};
+    use crate::constant::CONFIG;
+
+    async fn index_names(pool: &PgPool) -> Vec<String> {
+        sqlx::query_scalar("SELECT indexname::text FROM pg_indexes WHERE schemaname = current_schema() ORDER BY 1")
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_deferred_indexes_survive_an_interrupted_bulk_sync() {
+        let mut config = CONFIG.clone();
+        let admin = setup_new_database(&mut config).await.unwrap();
+        let db = DatabaseClient::build_from_config(&config).await.unwrap();
+        migrate_database(&db).await.unwrap();
+        let pool = db.get_postgres_connection_pool();
+        let before = index_names(pool).await;
+
+        drop_deferred_indexes(pool).await.unwrap();
+        // A restart drops them again before the first run rebuilt them.
+        drop_deferred_indexes(pool).await.unwrap();
+        let dropped = index_names(pool).await;
+        assert!(DEFERRED_INDEXES.iter().all(|name| !dropped.contains(&name.to_string())));
+        assert!(dropped.contains(&"blocks_hash_idx".to_string()));
+
+        create_deferred_indexes(pool).await.unwrap();
+        assert_eq!(index_names(pool).await, before);
+        let pending: i64 = sqlx::query_scalar("SELECT count(*) FROM deferred_indexes")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        assert_eq!(pending, 0);
+
+        db.close().await.unwrap();
+        drop_database(&admin, &config.db.database_name).await.unwrap();
+    }
+
+    #[test]
+    fn test_copy_rows_escape_text_format() {
+        let mut rows = CopyRows::default();
+        let json = serde_json::json!(["a\tb"]);
+        rows.push(&[&1i32, &"x\\y\nz".to_string(), &None::<i64>, &true, &json]);
+        assert_eq!(rows.data, "1\tx\\\\y\\nz\t\\N\tt\t[\"a\\\\tb\"]\n");
+    }
+}
@@ -1 +1,33 @@
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ActiveModelTrait, ConnectionTrait, EntityTrait, IntoActiveModel};
 
+use crate::error::AppResult;
+
+pub mod address;
+pub mod block;
+pub mod copy;
+pub mod pool;
+pub mod transaction;
+pub mod tx_input;
+pub mod tx_output;
+
+/// Rows per statement, keeping every batch below the Postgres bind parameter limit.
+pub(crate) const CHUNK_SIZE: usize = 1000;
+
+/// Inserts `models` in chunks, skipping rows that hit `on_conflict`.
+pub(crate) async fn insert_chunked<C, A>(conn: &C, models: Vec<A>, on_conflict: OnConflict) -> AppResult
+where
+    C: ConnectionTrait,
+    A: ActiveModelTrait,
+    <A::Entity as EntityTrait>::Model: IntoActiveModel<A>,
+{
+    let mut models = models.into_iter().peekable();
+    while models.peek().is_some() {
+        let chunk: Vec<_> = models.by_ref().take(CHUNK_SIZE).collect();
+        A::Entity::insert_many(chunk)
+            .on_conflict(on_conflict.clone())
+            .exec_without_returning(conn)
+            .await?;
+    }
+    Ok(())
+}
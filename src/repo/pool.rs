@@ -0,0 +1,31 @@
+use sea_orm::{ConnectionTrait, EntityTrait, IntoActiveModel, QueryOrder};
+
+use crate::entity::pool;
+use crate::error::AppResult;
+
+#[tracing::instrument(skip_all)]
+pub async fn find_all<C>(conn: &C) -> AppResult<Vec<pool::Model>>
+where
+    C: ConnectionTrait,
+{
+    let models = pool::Entity::find()
+        .order_by_asc(pool::Column::Id)
+        .all(conn)
+        .await?;
+    Ok(models)
+}
+
+/// Replaces every stored pool with `models`.
+#[tracing::instrument(skip_all)]
+pub async fn replace_all<C>(conn: &C, models: Vec<pool::Model>) -> AppResult
+where
+    C: ConnectionTrait,
+{
+    pool::Entity::delete_many().exec(conn).await?;
+    for chunk in models.chunks(super::CHUNK_SIZE) {
+        pool::Entity::insert_many(chunk.iter().cloned().map(IntoActiveModel::into_active_model))
+            .exec_without_returning(conn)
+            .await?;
+    }
+    Ok(())
+}
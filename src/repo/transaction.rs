@@ -0,0 +1,123 @@
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect,
+};
+use sqlx::PgConnection;
+
+use crate::entity::transaction;
+use crate::error::AppResult;
+use crate::repo::copy::CopyRows;
+
+/// Inserts the transactions of a block. Duplicate txids (the BIP30 coinbases) are skipped.
+#[tracing::instrument(skip_all)]
+pub async fn save_many<C>(conn: &C, models: Vec<transaction::ActiveModel>) -> AppResult
+where
+    C: ConnectionTrait,
+{
+    let on_conflict = OnConflict::column(transaction::Column::Txid).do_nothing().to_owned();
+    super::insert_chunked(conn, models, on_conflict).await
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn find_by_txid<C>(conn: &C, txid: &str) -> AppResult<Option<transaction::Model>>
+where
+    C: ConnectionTrait,
+{
+    let model = transaction::Entity::find_by_id(txid).one(conn).await?;
+    Ok(model)
+}
+
+/// Returns one page of the transactions of the block at `height`, in block order.
+#[tracing::instrument(skip_all)]
+pub async fn find_page_by_height<C>(
+    conn: &C,
+    height: i32,
+    page_num: u64,
+    page_size: u64,
+) -> AppResult<Vec<transaction::Model>>
+where
+    C: ConnectionTrait,
+{
+    let models = transaction::Entity::find()
+        .filter(transaction::Column::BlockHeight.eq(height))
+        .order_by_asc(transaction::Column::BlockIndex)
+        .paginate(conn, page_size)
+        .fetch_page(page_num)
+        .await?;
+    Ok(models)
+}
+
+/// Returns the txids of the block at `height`, in block order.
+#[tracing::instrument(skip_all)]
+pub async fn find_txids_by_height<C>(conn: &C, height: i32) -> AppResult<Vec<String>>
+where
+    C: ConnectionTrait,
+{
+    let txids = transaction::Entity::find()
+        .select_only()
+        .column(transaction::Column::Txid)
+        .filter(transaction::Column::BlockHeight.eq(height))
+        .order_by_asc(transaction::Column::BlockIndex)
+        .into_tuple()
+        .all(conn)
+        .await?;
+    Ok(txids)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn delete_above<C>(conn: &C, height: i32) -> AppResult<u64>
+where
+    C: ConnectionTrait,
+{
+    let result = transaction::Entity::delete_many()
+        .filter(transaction::Column::BlockHeight.gt(height))
+        .exec(conn)
+        .await?;
+    Ok(result.rows_affected)
+}
+
+/// Returns the subset of `txids` that is already stored.
+#[tracing::instrument(skip_all)]
+pub async fn find_existing_txids<C>(conn: &C, txids: &[String]) -> AppResult<Vec<String>>
+where
+    C: ConnectionTrait,
+{
+    let mut existing = Vec::new();
+    for chunk in txids.chunks(super::CHUNK_SIZE) {
+        let found = transaction::Entity::find()
+            .select_only()
+            .column(transaction::Column::Txid)
+            .filter(transaction::Column::Txid.is_in(chunk.iter().cloned()))
+            .into_tuple::<String>()
+            .all(conn)
+            .await?;
+        existing.extend(found);
+    }
+    Ok(existing)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn copy_many(conn: &mut PgConnection, models: &[transaction::Model]) -> AppResult<u64> {
+    let mut rows = CopyRows::default();
+    for model in models {
+        rows.push(&[
+            &model.txid,
+            &model.block_height,
+            &model.block_index,
+            &model.size,
+            &model.vsize,
+            &model.weight,
+            &model.fee,
+            &model.feerate,
+            &model.version,
+            &model.locktime,
+            &model.input_count,
+            &model.output_count,
+            &model.is_segwit,
+            &model.is_taproot,
+        ]);
+    }
+    let statement = "COPY transactions (txid, block_height, block_index, size, vsize, weight, fee, \
+        feerate, version, locktime, input_count, output_count, is_segwit, is_taproot) FROM STDIN";
+    super::copy::copy_in(conn, statement, rows).await
+}
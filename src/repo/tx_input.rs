@@ -0,0 +1,65 @@
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, QueryOrder};
+use sqlx::PgConnection;
+
+use crate::entity::tx_input;
+use crate::error::AppResult;
+use crate::repo::copy::CopyRows;
+
+#[tracing::instrument(skip_all)]
+pub async fn save_many<C>(conn: &C, models: Vec<tx_input::ActiveModel>) -> AppResult
+where
+    C: ConnectionTrait,
+{
+    let on_conflict = OnConflict::columns([tx_input::Column::Txid, tx_input::Column::Vin])
+        .do_nothing()
+        .to_owned();
+    super::insert_chunked(conn, models, on_conflict).await
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn find_by_txid<C>(conn: &C, txid: &str) -> AppResult<Vec<tx_input::Model>>
+where
+    C: ConnectionTrait,
+{
+    let models = tx_input::Entity::find()
+        .filter(tx_input::Column::Txid.eq(txid))
+        .order_by_asc(tx_input::Column::Vin)
+        .all(conn)
+        .await?;
+    Ok(models)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn delete_above<C>(conn: &C, height: i32) -> AppResult<u64>
+where
+    C: ConnectionTrait,
+{
+    let result = tx_input::Entity::delete_many()
+        .filter(tx_input::Column::BlockHeight.gt(height))
+        .exec(conn)
+        .await?;
+    Ok(result.rows_affected)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn copy_many(conn: &mut PgConnection, models: &[tx_input::Model]) -> AppResult<u64> {
+    let mut rows = CopyRows::default();
+    for model in models {
+        rows.push(&[
+            &model.txid,
+            &model.vin,
+            &model.block_height,
+            &model.prev_txid,
+            &model.prev_vout,
+            &model.value,
+            &model.address,
+            &model.script_sig,
+            &model.witness,
+            &model.sequence,
+        ]);
+    }
+    let statement = "COPY tx_inputs (txid, vin, block_height, prev_txid, prev_vout, value, address, \
+        script_sig, witness, sequence) FROM STDIN";
+    super::copy::copy_in(conn, statement, rows).await
+}
@@ -0,0 +1,181 @@
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DbBackend, EntityTrait, QueryFilter, QueryOrder, Statement, Value,
+};
+use sqlx::PgConnection;
+
+use crate::entity::tx_output;
+use crate::error::AppResult;
+use crate::repo::copy::CopyRows;
+
+/// An input spending a stored output: `(txid, vout)` spent by `(spent_by_txid, spent_by_vin)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spend {
+    pub txid: String,
+    pub vout: i32,
+    pub spent_by_txid: String,
+    pub spent_by_vin: i32,
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn save_many<C>(conn: &C, models: Vec<tx_output::ActiveModel>) -> AppResult
+where
+    C: ConnectionTrait,
+{
+    let on_conflict = OnConflict::columns([tx_output::Column::Txid, tx_output::Column::Vout])
+        .do_nothing()
+        .to_owned();
+    super::insert_chunked(conn, models, on_conflict).await
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn find_by_outpoints<C>(conn: &C, outpoints: &[(String, i32)]) -> AppResult<Vec<tx_output::Model>>
+where
+    C: ConnectionTrait,
+{
+    let mut models = Vec::with_capacity(outpoints.len());
+    for chunk in outpoints.chunks(super::CHUNK_SIZE) {
+        let found = tx_output::Entity::find()
+            .filter(
+                Expr::tuple([
+                    Expr::col(tx_output::Column::Txid).into(),
+                    Expr::col(tx_output::Column::Vout).into(),
+                ])
+                .in_tuples(chunk.iter().cloned()),
+            )
+            .all(conn)
+            .await?;
+        models.extend(found);
+    }
+    Ok(models)
+}
+
+/// Links every output in `spends` to the input spending it at `height`.
+#[tracing::instrument(skip_all)]
+pub async fn mark_spent<C>(conn: &C, spends: &[Spend], height: i32) -> AppResult
+where
+    C: ConnectionTrait,
+{
+    for chunk in spends.chunks(super::CHUNK_SIZE) {
+        let mut values: Vec<Value> = Vec::with_capacity(chunk.len() * 4 + 1);
+        values.push(height.into());
+        let rows = chunk
+            .iter()
+            .map(|spend| {
+                let first = values.len() + 1;
+                values.push(spend.txid.clone().into());
+                values.push(spend.vout.into());
+                values.push(spend.spent_by_txid.clone().into());
+                values.push(spend.spent_by_vin.into());
+                format!("(${}, ${}, ${}, ${})", first, first + 1, first + 2, first + 3)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = format!(
+            "UPDATE tx_outputs AS o
+                SET spent_by_txid = s.spent_by_txid, spent_by_vin = s.spent_by_vin, spent_height = $1
+                FROM (VALUES {rows}) AS s (txid, vout, spent_by_txid, spent_by_vin)
+                WHERE o.txid = s.txid AND o.vout = s.vout"
+        );
+        conn.execute(Statement::from_sql_and_values(DbBackend::Postgres, sql, values))
+            .await?;
+    }
+    Ok(())
+}
+
+/// Clears the spent-by link of outputs spent above `height`.
+#[tracing::instrument(skip_all)]
+pub async fn unspend_above<C>(conn: &C, height: i32) -> AppResult<u64>
+where
+    C: ConnectionTrait,
+{
+    let result = tx_output::Entity::update_many()
+        .col_expr(tx_output::Column::SpentByTxid, Expr::value(Option::<String>::None))
+        .col_expr(tx_output::Column::SpentByVin, Expr::value(Option::<i32>::None))
+        .col_expr(tx_output::Column::SpentHeight, Expr::value(Option::<i32>::None))
+        .filter(tx_output::Column::SpentHeight.gt(height))
+        .exec(conn)
+        .await?;
+    Ok(result.rows_affected)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn find_by_txid<C>(conn: &C, txid: &str) -> AppResult<Vec<tx_output::Model>>
+where
+    C: ConnectionTrait,
+{
+    let models = tx_output::Entity::find()
+        .filter(tx_output::Column::Txid.eq(txid))
+        .order_by_asc(tx_output::Column::Vout)
+        .all(conn)
+        .await?;
+    Ok(models)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn delete_above<C>(conn: &C, height: i32) -> AppResult<u64>
+where
+    C: ConnectionTrait,
+{
+    let result = tx_output::Entity::delete_many()
+        .filter(tx_output::Column::BlockHeight.gt(height))
+        .exec(conn)
+        .await?;
+    Ok(result.rows_affected)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn copy_many(conn: &mut PgConnection, models: &[tx_output::Model]) -> AppResult<u64> {
+    let mut rows = CopyRows::default();
+    for model in models {
+        rows.push(&[
+            &model.txid,
+            &model.vout,
+            &model.block_height,
+            &model.value,
+            &model.script_pubkey,
+            &model.script_type,
+            &model.address,
+            &model.spent_by_txid,
+            &model.spent_by_vin,
+            &model.spent_height,
+        ]);
+    }
+    let statement = "COPY tx_outputs (txid, vout, block_height, value, script_pubkey, script_type, \
+        address, spent_by_txid, spent_by_vin, spent_height) FROM STDIN";
+    super::copy::copy_in(conn, statement, rows).await
+}
+
+/// Same as [`mark_spent`] for many blocks at once: `spends` pairs every spend with the
+/// height of the block spending it. The spends are staged with `COPY` and applied in a
+/// single update.
+#[tracing::instrument(skip_all)]
+pub async fn copy_spends(conn: &mut PgConnection, spends: &[(Spend, i32)]) -> AppResult<u64> {
+    if spends.is_empty() {
+        return Ok(0);
+    }
+    super::copy::execute(
+        conn,
+        "CREATE TEMP TABLE spends_staging (
+                txid varchar(64) NOT NULL,
+                vout integer NOT NULL,
+                spent_by_txid varchar(64) NOT NULL,
+                spent_by_vin integer NOT NULL,
+                spent_height integer NOT NULL
+            ) ON COMMIT DROP",
+    )
+    .await?;
+    let mut rows = CopyRows::default();
+    for (spend, height) in spends {
+        rows.push(&[&spend.txid, &spend.vout, &spend.spent_by_txid, &spend.spent_by_vin, height]);
+    }
+    super::copy::copy_in(conn, "COPY spends_staging FROM STDIN", rows).await?;
+    super::copy::execute(
+        conn,
+        "UPDATE tx_outputs AS o
+            SET spent_by_txid = s.spent_by_txid, spent_by_vin = s.spent_by_vin, spent_height = s.spent_height
+            FROM spends_staging AS s
+            WHERE o.txid = s.txid AND o.vout = s.vout",
+    )
+    .await
+}
@@ -1,7 +1,25 @@
 use axum::routing::get;
 
-use crate::{handler::server, server::state::AppState};
+use crate::{handler::{address, block, fee, mempool, mining, server, transaction}, server::state::AppState};
 pub fn add_routers(router: axum::Router<AppState>) -> axum::Router<AppState> {
     router
         .route("/api/v1/server/health_check", get(server::health_check))
+        .route("/api/v1/server/network", get(server::get_network))
+        .route("/api/v1/address/:address", get(address::get_address))
+        .route("/api/v1/address/:address/txs", get(address::get_address_txs))
+        .route("/api/v1/blocks", get(block::get_blocks))
+        // Both block routes share the `:id` segment so the router accepts them side by side.
+        .route("/api/v1/block/:id", get(block::get_block_by_height))
+        .route("/api/v1/block/:id/txs", get(block::get_block_txs))
+        .route("/api/v1/block/hash/:hash", get(block::get_block_by_hash))
+        .route("/api/v1/tx/:txid", get(transaction::get_transaction))
+        .route("/api/v1/tx/:txid/merkle-proof", get(transaction::get_merkle_proof))
+        .route("/api/v1/mempool", get(mempool::get_mempool))
+        .route("/api/v1/mempool/txids", get(mempool::list_mempool_txids))
+        .route("/api/v1/mempool/blocks", get(mempool::list_projected_blocks))
+        .route("/api/v1/mempool/tx/:txid", get(mempool::get_projected_tx))
+        .route("/api/v1/fees/recommended", get(fee::get_recommended_fees))
+        .route("/api/v1/mining/pools", get(mining::get_mining_pools))
+        .route("/api/v1/mining/difficulty-adjustment", get(mining::get_difficulty_adjustment))
+        .route("/api/v1/mining/hashrate", get(mining::get_hashrate))
 }
@@ -1,21 +1,406 @@
-use bitcoincore_rpc::RpcApi;
-use tracing::info;
-use crate::error::AppResult;
+use std::sync::Arc;
+
+use bitcoincore_rpc::bitcoin::blockdata::constants::genesis_block;
+use bitcoincore_rpc::bitcoin::Block;
+use sea_orm::TransactionTrait;
+use sqlx::PgPool;
+use tracing::{error, info, warn};
+use crate::client::blk::BlkIndex;
+use crate::client::source::{self, BlkSource};
+use crate::constant::{BlockHeight, BLOCK_POLL_INTERVAL, BULK_SYNC_MIN_LAG, MAX_REORG_DEPTH};
+use crate::error::{AppError, AppResult};
+use crate::repo;
+use crate::server::event::IndexerEvent;
 use crate::server::state::AppState;
+use crate::service::bulk::BulkWriter;
+use crate::service::{coinbase, header, ingest, mining, pool};
+use crate::service::prefetcher::{PrefetchedBlock, Prefetcher};
 
 
 pub struct BitcoinIndexer {
     pub state: AppState,
+    blk_index: Option<Arc<BlkIndex>>,
+    /// Still backfilling with `COPY` batches. Cleared for good once the tip is reached.
+    bulk: bool,
+    /// The indexes deferred by the bulk writer are known to exist.
+    indexes_ready: bool,
 }
 
 impl BitcoinIndexer {
     pub fn new(state: AppState) -> AppResult<Self> {
-        Ok(Self { state })
+        let bulk = state.config.db.bulk_sync;
+        Ok(Self {
+            state,
+            blk_index: None,
+            bulk,
+            indexes_ready: false,
+        })
+    }
+
+    pub async fn run(mut self) -> AppResult<()> {
+        info!("The bitcoin indexer has started.");
+        let network = self.state.config.bitcoin.network;
+        loop {
+            match source::verify_network(&*self.state.source, network).await {
+                Ok(()) => break,
+                Err(e @ AppError::ConfigError(_)) => return Err(e),
+                Err(e) => error!("Failed to check the node network: {e}."),
+            }
+            tokio::time::sleep(BLOCK_POLL_INTERVAL).await;
+        }
+        info!("The node runs {network}.");
+        let genesis = genesis_block(network).block_hash().to_string();
+        if let Some(stored) = repo::block::find_by_height(&*self.state.db, 0).await? {
+            if stored.hash != genesis {
+                return Err(AppError::ConfigError(config::ConfigError::Message(format!(
+                    "the database holds a chain with genesis block {}, not {network}",
+                    stored.hash
+                ))));
+            }
+        }
+        if let Err(e) = header::backfill(&self.state).await {
+            error!("Failed to read the missing headers from the node: {e}.");
+        }
+        if self.state.config.bitcoin.verify_headers {
+            match header::verify_stored(&self.state).await {
+                Ok(()) => info!("The stored headers are valid."),
+                Err(e @ AppError::InvalidHeaderError(_)) => return Err(e),
+                Err(e) => error!("Failed to verify the stored headers: {e}."),
+            }
+        }
+        if let Err(e) = pool::sync_definitions(&self.state).await {
+            error!("Failed to apply the pool definitions: {e}.");
+        }
+        if let Err(e) = coinbase::backfill(&self.state).await {
+            error!("Failed to decode the stored coinbases: {e}.");
+        }
+        if let Err(e) = mining::repair_difficulty(&self.state).await {
+            error!("Failed to repair the stored difficulties: {e}.");
+        }
+        if let Some(dir) = self.state.config.bitcoin.blocks_dir.clone() {
+            match self.load_blk_index(dir).await {
+                Ok(index) => self.blk_index = Some(Arc::new(index)),
+                Err(e) => error!("Failed to read the block files, syncing over RPC: {e}."),
+            }
+        }
+        loop {
+            if let Err(e) = self.sync().await {
+                error!("Block sync failed: {e}.");
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(BLOCK_POLL_INTERVAL) => {}
+                _ = self.state.block_notify.notified() => {}
+            }
+        }
+    }
+
+    /// Indexes every block between the highest stored height and the node tip,
+    /// rolling back orphaned blocks whenever the stored chain leaves the best chain.
+    async fn sync(&mut self) -> AppResult {
+        self.handle_reorg().await?;
+        let (tip, _) = self.state.source.tip().await?;
+        loop {
+            let start = self.next_height().await?;
+            if start > tip {
+                return Ok(());
+            }
+            let mut prefetcher = self.prefetcher(start, tip);
+            let extended = if self.bulk && tip - start >= BULK_SYNC_MIN_LAG {
+                self.bulk_index(&mut prefetcher).await?
+            } else {
+                self.finish_bulk().await?;
+                let mut extended = true;
+                while let Some(prefetched) = prefetcher.next().await {
+                    if !self.index_block(prefetched?).await? {
+                        extended = false;
+                        break;
+                    }
+                }
+                extended
+            };
+            if extended {
+                continue;
+            }
+            // Blocks fetched past the fork point are stale, so the prefetcher starts over.
+            // The block files may hold the orphaned branch, so only RPC is trusted from now on.
+            drop(prefetcher);
+            self.blk_index = None;
+            self.handle_reorg().await?;
+        }
     }
-    pub async fn run(self) -> AppResult<()> {
-        info!("Start parse block");
-        let best_block_hash = self.state.bitcoin.();
-        println!("best block hash: {:?}", best_block_hash);
+
+    /// Backfills with the bulk writer until the prefetcher is exhausted. Returns `false` once
+    /// a block does not extend the chain, after writing the blocks buffered before it.
+    async fn bulk_index(&mut self, prefetcher: &mut Prefetcher) -> AppResult<bool> {
+        info!("Dropping secondary indexes for the bulk sync.");
+        repo::copy::drop_deferred_indexes(self.pool()).await?;
+        self.indexes_ready = false;
+        let mut writer = BulkWriter::new(
+            self.pool().clone(),
+            self.state.config.db.bulk_batch_txs,
+            self.state.pools.clone(),
+            self.state.config.bitcoin.network,
+        );
+        while let Some(prefetched) = prefetcher.next().await {
+            let PrefetchedBlock { height, block, fees } = prefetched?;
+            let buffered = writer.last_block().map(|parent| parent.hash.clone());
+            if !self.extends_stored_chain(height, &block, buffered).await? {
+                self.flush(&mut writer).await?;
+                return Ok(false);
+            }
+            writer.push(&*self.state.db, height, &block, fees.as_ref()).await?;
+            if writer.is_full() {
+                self.flush(&mut writer).await?;
+            }
+        }
+        self.flush(&mut writer).await?;
+        Ok(true)
+    }
+
+    async fn flush(&self, writer: &mut BulkWriter) -> AppResult {
+        let blocks = writer.flush(&*self.state.db).await?;
+        if let (Some(first), Some(last)) = (blocks.first(), blocks.last()) {
+            info!("Bulk indexed blocks {} to {} {}.", first.height, last.height, last.hash);
+        }
+        for block in blocks {
+            let _ = self.state.events.send(IndexerEvent::BlockConnected {
+                height: block.height as BlockHeight,
+                hash: block.hash,
+            });
+        }
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Leaves bulk mode for good and rebuilds the indexes it deferred.
+    async fn finish_bulk(&mut self) -> AppResult {
+        if self.indexes_ready {
+            return Ok(());
+        }
+        if self.bulk {
+            info!("Bulk sync caught up with the node, switching to per-block writes.");
+            self.bulk = false;
+        }
+        repo::copy::create_deferred_indexes(self.pool()).await?;
+        self.indexes_ready = true;
+        Ok(())
+    }
+
+    fn pool(&self) -> &PgPool {
+        self.state.db.get_postgres_connection_pool()
+    }
+
+    /// Checks that `block` builds on its parent: the last buffered block when given,
+    /// otherwise the stored block below `height`.
+    async fn extends_stored_chain(
+        &self,
+        height: BlockHeight,
+        block: &Block,
+        buffered: Option<String>,
+    ) -> AppResult<bool> {
+        let Some(parent) = height.checked_sub(1) else {
+            return Ok(true);
+        };
+        let parent_hash = match buffered {
+            Some(hash) => Some(hash),
+            None => repo::block::find_by_height(&*self.state.db, parent as i32)
+                .await?
+                .map(|parent| parent.hash),
+        };
+        if parent_hash.is_some_and(|hash| hash != block.header.prev_blockhash.to_string()) {
+            warn!("Block {height} does not extend the stored chain.");
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Reads from the block files while they cover `start`, then switches to the node.
+    fn prefetcher(&self, start: BlockHeight, tip: BlockHeight) -> Prefetcher {
+        let source = self.state.source.clone();
+        let config = &self.state.config.bitcoin;
+        match &self.blk_index {
+            Some(index) if index.tip_height().is_some_and(|end| end >= start) => {
+                let end = index.tip_height().map_or(tip, |end| end.min(tip));
+                let source = Arc::new(BlkSource::new(index.clone(), source));
+                Prefetcher::spawn(source, start..=end, config)
+            }
+            _ => Prefetcher::spawn(source, start..=tip, config),
+        }
+    }
+
+    /// Scans the block files and keeps only the part of their chain the node agrees with.
+    async fn load_blk_index(&self, dir: String) -> AppResult<BlkIndex> {
+        let magic = self.state.config.bitcoin.network.magic();
+        let mut index =
+            tokio::task::spawn_blocking(move || BlkIndex::build(dir, magic))
+                .await
+                .map_err(|e| AppError::UnknownError(e.into()))??;
+        let mut height = index.tip_height();
+        while let Some(current) = height {
+            if self.state.source.block_hash(current).await? == index.hash(current) {
+                break;
+            }
+            height = current.checked_sub(1);
+        }
+        index.truncate(height);
+        Ok(index)
+    }
+
+    async fn next_height(&self) -> AppResult<BlockHeight> {
+        let max_height = repo::block::find_max_height(&*self.state.db).await?;
+        Ok(max_height.map_or(0, |height| height as BlockHeight + 1))
+    }
+
+    /// Commits a prefetched block in its own database transaction.
+    /// Returns `false` without writing anything when the block does not extend the stored tip.
+    async fn index_block(&self, prefetched: PrefetchedBlock) -> AppResult<bool> {
+        let PrefetchedBlock { height, block, fees } = prefetched;
+        if !self.extends_stored_chain(height, &block, None).await? {
+            return Ok(false);
+        }
+
+        let tx = self.state.db.begin().await?;
+        let prevouts = ingest::resolve_prevouts(&tx, &block).await?;
+        let network = self.state.config.bitcoin.network;
+        ingest::index_block(&tx, height, &block, fees.as_ref(), &prevouts, &self.state.pools, network).await?;
+        tx.commit().await?;
+        let hash = block.block_hash().to_string();
+        info!("Indexed block {height} {hash}.");
+        let _ = self.state.events.send(IndexerEvent::BlockConnected { height, hash });
+        Ok(true)
+    }
+
+    /// Walks back from the highest height both the database and the node have until the
+    /// stored hash matches the node's best chain, then removes every block above that fork
+    /// point in a single transaction. Blocks above the node's tip are kept until the node
+    /// catches up, since it may just be reindexing or still syncing.
+    async fn handle_reorg(&self) -> AppResult {
+        let Some(stored_tip) = repo::block::find_max_height(&*self.state.db).await? else {
+            return Ok(());
+        };
+        let stored_tip = stored_tip as BlockHeight;
+        let (node_tip, _) = self.state.source.tip().await?;
+        if node_tip < stored_tip {
+            info!("The node is at height {node_tip}, below the stored tip {stored_tip}, waiting for it.");
+        }
+        let start = stored_tip.min(node_tip);
+        let mut stored = repo::block::find_by_height(&*self.state.db, start as i32)
+            .await?
+            .ok_or_else(|| AppError::ConflictError(format!("block {start} is missing from the database")))?;
+        let mut forked = false;
+        loop {
+            let height = stored.height as BlockHeight;
+            match self.state.source.block_hash(height).await? {
+                Some(hash) if hash.to_string() == stored.hash => break,
+                Some(_) => forked = true,
+                // The node dropped blocks since it reported its tip, check again on the next poll.
+                None => return Ok(()),
+            }
+            if stored_tip - height >= MAX_REORG_DEPTH {
+                return Err(AppError::ConflictError(format!(
+                    "the node chain forks from the stored one more than {MAX_REORG_DEPTH} blocks deep, below height {height}"
+                )));
+            }
+            stored = match height.checked_sub(1) {
+                Some(parent) => repo::block::find_by_height(&*self.state.db, parent as i32).await?,
+                None => None,
+            }
+            .ok_or_else(|| {
+                AppError::ConflictError(format!(
+                    "no common ancestor with the node chain below height {height}"
+                ))
+            })?;
+        }
+        if !forked {
+            return Ok(());
+        }
+
+        let fork_height = stored.height as BlockHeight;
+        let depth = stored_tip - fork_height;
+        let orphaned = repo::block::find_range(&*self.state.db, fork_height as i32 + 1, stored_tip as i32)
+            .await?
+            .into_iter()
+            .rev()
+            .map(|block| block.hash)
+            .collect();
+        let tx = self.state.db.begin().await?;
+        ingest::rollback(&tx, fork_height).await?;
+        tx.commit().await?;
+        warn!("Chain reorganization of depth {depth} detected, rolled back to height {fork_height}.");
+        let _ = self.state.events.send(IndexerEvent::Reorg {
+            depth,
+            fork_height,
+            orphaned,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoincore_rpc::bitcoin::{Amount, OutPoint, TxOut};
+
+    use super::*;
+    use crate::client::database::{drop_database, migrate_database, setup_new_database};
+    use crate::client::mock::{spend, MockBitcoind};
+    use crate::constant::CONFIG;
+
+    #[tokio::test]
+    async fn test_sync_rolls_back_reorged_blocks() {
+        let mock = MockBitcoind::from_fixture("mainnet").await;
+        let mut config = CONFIG.clone();
+        let admin = setup_new_database(&mut config).await.unwrap();
+        let database_name = config.db.database_name.clone();
+        config.bitcoin = mock.config();
+        config.db.bulk_sync = false;
+        let state = AppState::new(config).await.unwrap();
+        migrate_database(&state.db).await.unwrap();
+        let mut events = state.events.subscribe();
+        let mut indexer = BitcoinIndexer::new(state.clone()).unwrap();
+
+        let coinbase = OutPoint::new(mock.block(1).txdata[0].txid(), 0);
+        let tx = spend(
+            &[coinbase],
+            vec![TxOut {
+                value: Amount::from_sat(4_999_000_000),
+                script_pubkey: mock.block(1).txdata[0].output[0].script_pubkey.clone(),
+            }],
+        );
+        mock.add_to_mempool(tx.clone());
+        let spending = mock.mine_mempool();
+        indexer.sync().await.unwrap();
+        let stored = repo::block::find_by_height(&*state.db, 3).await.unwrap().unwrap();
+        assert_eq!(stored.hash, spending.block_hash().to_string());
+        let indexed = repo::transaction::find_by_txid(&*state.db, &tx.txid().to_string()).await.unwrap();
+        assert_eq!(indexed.unwrap().fee, 1_000_000);
+        let outpoints = [(coinbase.txid.to_string(), 0)];
+        let output = repo::tx_output::find_by_outpoints(&*state.db, &outpoints).await.unwrap();
+        assert_eq!(output[0].spent_height, Some(3));
+
+        // Blocks 2 and 3 are replaced by three empty blocks.
+        let orphaned = [mock.block(3), mock.block(2)];
+        mock.reorg(2, 3);
+        indexer.sync().await.unwrap();
+        for height in 0..=4 {
+            let stored = repo::block::find_by_height(&*state.db, height as i32).await.unwrap().unwrap();
+            assert_eq!(stored.hash, mock.block(height).block_hash().to_string());
+        }
+        assert_eq!(repo::block::find_max_height(&*state.db).await.unwrap(), Some(4));
+        let indexed = repo::transaction::find_by_txid(&*state.db, &tx.txid().to_string()).await.unwrap();
+        assert!(indexed.is_none());
+        let output = repo::tx_output::find_by_outpoints(&*state.db, &outpoints).await.unwrap();
+        assert_eq!(output[0].spent_height, None);
+        let reorg = std::iter::from_fn(|| events.try_recv().ok())
+            .find(|event| matches!(event, IndexerEvent::Reorg { .. }));
+        assert_eq!(
+            reorg,
+            Some(IndexerEvent::Reorg {
+                depth: 2,
+                fork_height: 1,
+                orphaned: orphaned.iter().map(|block| block.block_hash().to_string()).collect(),
+            })
+        );
+
+        drop((indexer, state));
+        drop_database(&admin, &database_name).await.unwrap();
+    }
+}
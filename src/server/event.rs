@@ -0,0 +1,30 @@
+use std::sync::Arc;
+
+use bitcoincore_rpc::bitcoin::{Block, BlockHash, Transaction};
+
+use crate::constant::BlockHeight;
+
+/// Chain changes published by the indexer once they are committed to the database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexerEvent {
+    BlockConnected {
+        height: BlockHeight,
+        hash: String,
+    },
+    /// The stored chain diverged from the node's best chain. Every block above
+    /// `fork_height` was rolled back; `orphaned` lists their hashes from the old tip down.
+    Reorg {
+        depth: u32,
+        fork_height: BlockHeight,
+        orphaned: Vec<String>,
+    },
+}
+
+/// Notifications pushed by the node over ZMQ, before anything is indexed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeEvent {
+    BlockHash(BlockHash),
+    Block(Arc<Block>),
+    /// A transaction entered the node's mempool, or was confirmed in a block.
+    Transaction(Arc<Transaction>),
+}
@@ -0,0 +1,120 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use bitcoincore_rpc::bitcoin::{Transaction, Txid};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info, warn};
+
+use crate::constant::{MEMPOOL_SYNC_INTERVAL, PROJECTED_BLOCKS, TEMPLATE_REBUILD_INTERVAL};
+use crate::error::AppResult;
+use crate::server::event::NodeEvent;
+use crate::server::state::AppState;
+use crate::service::template::BlockTemplate;
+
+/// Keeps [`AppState::mempool`] in line with the node: seeded from its mempool entries,
+/// updated live from ZMQ notifications and reconciled periodically, which also covers
+/// setups without ZMQ. The projected blocks are rebuilt here once the mempool changed, so
+/// requests only read [`AppState::template`].
+pub struct MempoolTracker {
+    state: AppState,
+}
+
+impl MempoolTracker {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self) -> AppResult {
+        info!("The mempool tracker has started.");
+        let mut events = self.state.node_events.subscribe();
+        let mut interval = tokio::time::interval(MEMPOOL_SYNC_INTERVAL);
+        let mut rebuild = tokio::time::interval(TEMPLATE_REBUILD_INTERVAL);
+        let mut dirty = false;
+        loop {
+            let result = tokio::select! {
+                _ = rebuild.tick(), if dirty => {
+                    self.rebuild_template().await;
+                    dirty = false;
+                    continue;
+                }
+                _ = interval.tick() => self.reconcile().await,
+                event = events.recv() => match event {
+                    Ok(NodeEvent::Transaction(tx)) => self.add(tx).await,
+                    Ok(NodeEvent::Block(block)) => {
+                        let evicted = self.state.mempool.write().await.confirm_block(&block);
+                        debug!("Block {} evicted {evicted} mempool transactions.", block.block_hash());
+                        Ok(())
+                    }
+                    Ok(NodeEvent::BlockHash(_)) => Ok(()),
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("The mempool tracker skipped {skipped} node events.");
+                        self.reconcile().await
+                    }
+                    Err(RecvError::Closed) => return Ok(()),
+                },
+            };
+            if let Err(e) = result {
+                warn!("Mempool update failed: {e}.");
+            }
+            dirty = true;
+        }
+    }
+
+    async fn rebuild_template(&self) {
+        let template = {
+            let mempool = self.state.mempool.read().await;
+            BlockTemplate::build(&mempool, PROJECTED_BLOCKS)
+        };
+        *self.state.template.write().await = Arc::new(template);
+    }
+
+    /// Tracks a transaction announced by the node. Transactions that are no longer in the
+    /// node's mempool, such as the ones announced as part of a block, are skipped.
+    async fn add(&self, tx: Arc<Transaction>) -> AppResult {
+        let txid = tx.txid();
+        if self.state.mempool.read().await.contains(&txid) {
+            return Ok(());
+        }
+        let entry = self.state.source.mempool_entry(&txid).await?;
+        if let Some(entry) = entry {
+            let replaced = self.state.mempool.write().await.insert(entry);
+            if !replaced.is_empty() {
+                debug!("{txid} replaced {} mempool transactions.", replaced.len());
+            }
+        }
+        Ok(())
+    }
+
+    /// Drops transactions the node no longer has and adds the ones missed so far.
+    async fn reconcile(&self) -> AppResult {
+        if self.state.mempool.read().await.is_empty() {
+            return self.seed().await;
+        }
+        let txids: BTreeSet<Txid> = self.state.source.mempool().await?.into_iter().collect();
+        let missing: Vec<Txid> = {
+            let mempool = self.state.mempool.read().await;
+            txids.iter().filter(|txid| !mempool.contains(txid)).copied().collect()
+        };
+        let entries = self.state.source.mempool_entries_of(&missing).await?;
+        let mut mempool = self.state.mempool.write().await;
+        let removed = mempool.retain(&txids);
+        let added = entries.len();
+        for entry in entries {
+            mempool.insert(entry);
+        }
+        debug!("Mempool reconciled: {added} added, {removed} removed, {} tracked.", mempool.len());
+        Ok(())
+    }
+
+    async fn seed(&self) -> AppResult {
+        let entries = self.state.source.mempool_entries().await?;
+        let mut mempool = self.state.mempool.write().await;
+        for entry in entries {
+            mempool.insert(entry);
+        }
+        if !mempool.is_empty() {
+            info!("Seeded the mempool with {} transactions.", mempool.len());
+        }
+        Ok(())
+    }
+}
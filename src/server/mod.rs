@@ -6,6 +6,9 @@ use crate::server::state::AppState;
 pub mod state;
 pub mod worker;
 pub mod bitcoin_indexer;
+pub mod event;
+pub mod mempool_tracker;
+pub mod zmq_subscriber;
 
 pub struct AppServer {
     pub state: AppState,
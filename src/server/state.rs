@@ -1,27 +1,51 @@
 use std::sync::Arc;
-use tokio::sync::Notify;
-use crate::client::bitcoin::{BitcoinClient, BitcoinClientExt};
+use tokio::sync::{broadcast, Notify, RwLock};
 use crate::client::database::{DatabaseClient, DatabaseClientExt};
+use crate::client::source::{self, BlockSource};
 use crate::configure::AppConfig;
+use crate::constant::EVENT_CHANNEL_CAPACITY;
 use crate::error::AppResult;
+use crate::server::event::{IndexerEvent, NodeEvent};
+use crate::service::mempool::Mempool;
+use crate::service::pool::Pools;
+use crate::service::template::BlockTemplate;
 
 #[derive(Clone)]
 pub struct AppState {
     pub config: Arc<AppConfig>,
     pub db: Arc<DatabaseClient>,
-    pub bitcoin: Arc<BitcoinClient>,
+    pub source: Arc<dyn BlockSource>,
     pub messenger_notify: Arc<Notify>,
+    pub events: broadcast::Sender<IndexerEvent>,
+    pub node_events: broadcast::Sender<NodeEvent>,
+    /// Wakes the indexer before its next poll, for example when the node announces a block.
+    pub block_notify: Arc<Notify>,
+    pub mempool: Arc<RwLock<Mempool>>,
+    /// Projected blocks of [`AppState::mempool`], rebuilt by the mempool tracker after it
+    /// changes. Readers clone the inner `Arc` and release the lock right away.
+    pub template: Arc<RwLock<Arc<BlockTemplate>>>,
+    pub pools: Arc<Pools>,
 }
 
 impl AppState {
     pub async fn new(config: AppConfig) -> AppResult<Self> {
         let db = Arc::new(DatabaseClient::build_from_config(&config).await?);
-        let bitcoin = Arc::new(BitcoinClient::build_from_config(&config.bitcoin).await?);
+        let source = source::build_from_config(&config.bitcoin).await?;
+        let pools = match config.bitcoin.pools_file() {
+            Some(path) => Arc::new(Pools::load(path)?),
+            None => Default::default(),
+        };
         Ok(Self {
             config: Arc::new(config),
             db,
-            bitcoin,
+            source,
             messenger_notify: Default::default(),
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            node_events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            block_notify: Default::default(),
+            mempool: Default::default(),
+            template: Default::default(),
+            pools,
         })
     }
 }
\ No newline at end of file
@@ -1,5 +1,7 @@
-use tracing::info;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
 use crate::error::AppResult;
+use crate::server::event::IndexerEvent;
 use crate::server::state::AppState;
 
 pub struct MessengerTask {
@@ -13,6 +15,20 @@ impl MessengerTask {
 
     pub async fn run(self) -> AppResult {
         info!("The messenger task has started ");
-        Ok(())
+        let mut events = self.state.events.subscribe();
+        loop {
+            match events.recv().await {
+                Ok(IndexerEvent::BlockConnected { height, hash }) => {
+                    info!("New block {height} {hash}.");
+                }
+                Ok(IndexerEvent::Reorg { depth, fork_height, orphaned }) => {
+                    warn!("Reorg of depth {depth} at height {fork_height}, orphaned blocks: {orphaned:?}.");
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("The messenger task skipped {skipped} events.");
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            }
+        }
     }
-}
\ No newline at end of file
+}
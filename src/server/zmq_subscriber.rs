@@ -0,0 +1,242 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use bitcoincore_rpc::bitcoin::consensus::encode;
+use bitcoincore_rpc::bitcoin::hashes::Hash;
+use bitcoincore_rpc::bitcoin::BlockHash;
+use tokio::sync::{broadcast, Notify};
+use tracing::{info, warn};
+use zeromq::{Socket, SocketRecv, SubSocket, ZmqMessage};
+
+use crate::constant::ZMQ_RECONNECT_DELAY;
+use crate::error::{AppError, AppResult};
+use crate::server::event::NodeEvent;
+use crate::server::state::AppState;
+
+const HASHBLOCK: &str = "hashblock";
+const RAWBLOCK: &str = "rawblock";
+const RAWTX: &str = "rawtx";
+
+/// Listens to bitcoind's ZMQ notifications, wakes the indexer on every new block and
+/// republishes blocks and transactions as [`NodeEvent`]s.
+pub struct ZmqSubscriber {
+    /// Topics to subscribe to, grouped by endpoint so topics sharing one endpoint share
+    /// one socket.
+    subscriptions: BTreeMap<String, Vec<&'static str>>,
+    events: broadcast::Sender<NodeEvent>,
+    block_notify: Arc<Notify>,
+}
+
+impl ZmqSubscriber {
+    pub fn new(state: &AppState) -> Self {
+        let config = &state.config.bitcoin;
+        Self::with_endpoints(
+            [
+                (HASHBLOCK, config.zmq_hashblock.clone()),
+                (RAWBLOCK, config.zmq_rawblock.clone()),
+                (RAWTX, config.zmq_rawtx.clone()),
+            ],
+            state.node_events.clone(),
+            state.block_notify.clone(),
+        )
+    }
+
+    fn with_endpoints(
+        endpoints: [(&'static str, Option<String>); 3],
+        events: broadcast::Sender<NodeEvent>,
+        block_notify: Arc<Notify>,
+    ) -> Self {
+        let mut subscriptions: BTreeMap<String, Vec<&'static str>> = BTreeMap::new();
+        for (topic, endpoint) in endpoints {
+            if let Some(endpoint) = endpoint {
+                subscriptions.entry(endpoint).or_default().push(topic);
+            }
+        }
+        Self {
+            subscriptions,
+            events,
+            block_notify,
+        }
+    }
+
+    pub async fn run(self) -> AppResult {
+        if self.subscriptions.is_empty() {
+            info!("ZMQ is not configured, the indexer polls the node for new blocks.");
+            return Ok(());
+        }
+        let listeners = self.subscriptions.into_iter().map(|(endpoint, topics)| {
+            listen(endpoint, topics, self.events.clone(), self.block_notify.clone())
+        });
+        futures::future::join_all(listeners).await;
+        Ok(())
+    }
+}
+
+/// Keeps a subscription alive, reconnecting after errors. The indexer keeps polling in
+/// the meantime, so a lost connection only delays new blocks.
+async fn listen(
+    endpoint: String,
+    topics: Vec<&'static str>,
+    events: broadcast::Sender<NodeEvent>,
+    block_notify: Arc<Notify>,
+) {
+    loop {
+        if let Err(e) = subscribe(&endpoint, &topics, &events, &block_notify).await {
+            warn!("ZMQ subscription to {endpoint} failed: {e}.");
+        }
+        tokio::time::sleep(ZMQ_RECONNECT_DELAY).await;
+    }
+}
+
+async fn subscribe(
+    endpoint: &str,
+    topics: &[&'static str],
+    events: &broadcast::Sender<NodeEvent>,
+    block_notify: &Notify,
+) -> AppResult {
+    let mut socket = SubSocket::new();
+    socket.connect(endpoint).await.map_err(zmq_error)?;
+    for topic in topics {
+        socket.subscribe(topic).await.map_err(zmq_error)?;
+    }
+    info!("Subscribed to {topics:?} on {endpoint}.");
+    let mut sequences: HashMap<String, u32> = HashMap::new();
+    loop {
+        let message = socket.recv().await.map_err(zmq_error)?;
+        let (topic, sequence, event) = parse(message)?;
+        if let Some(previous) = sequences.insert(topic.clone(), sequence) {
+            let missed = sequence.wrapping_sub(previous).wrapping_sub(1);
+            if missed > 0 {
+                warn!("Missed {missed} {topic} notifications from {endpoint}.");
+            }
+        }
+        if matches!(event, NodeEvent::BlockHash(_) | NodeEvent::Block(_)) {
+            block_notify.notify_one();
+        }
+        let _ = events.send(event);
+    }
+}
+
+/// Decodes one bitcoind notification: the topic, the payload and the little-endian
+/// sequence number counted per topic.
+fn parse(message: ZmqMessage) -> AppResult<(String, u32, NodeEvent)> {
+    let frames = message.into_vec();
+    let [topic, body, sequence] = frames.as_slice() else {
+        return Err(invalid(format!("expected 3 frames, got {}", frames.len())));
+    };
+    let topic = String::from_utf8_lossy(topic).into_owned();
+    let sequence: [u8; 4] = sequence[..]
+        .try_into()
+        .map_err(|_| invalid(format!("invalid {topic} sequence number")))?;
+    let decode_error = |e: encode::Error| invalid(format!("invalid {topic} payload: {e}"));
+    let event = match topic.as_str() {
+        HASHBLOCK => {
+            // The hash is sent in display order, the reverse of its internal byte order.
+            let mut bytes: [u8; 32] = body[..]
+                .try_into()
+                .map_err(|_| invalid(format!("invalid {topic} payload")))?;
+            bytes.reverse();
+            NodeEvent::BlockHash(BlockHash::from_byte_array(bytes))
+        }
+        RAWBLOCK => NodeEvent::Block(Arc::new(encode::deserialize(body).map_err(decode_error)?)),
+        RAWTX => NodeEvent::Transaction(Arc::new(encode::deserialize(body).map_err(decode_error)?)),
+        _ => return Err(invalid(format!("unexpected topic {topic}"))),
+    };
+    Ok((topic, u32::from_le_bytes(sequence), event))
+}
+
+fn invalid(message: String) -> AppError {
+    AppError::InvalidPayloadError(format!("ZMQ notification: {message}"))
+}
+
+fn zmq_error(error: zeromq::ZmqError) -> AppError {
+    AppError::UnknownError(error.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bitcoincore_rpc::bitcoin::blockdata::constants::genesis_block;
+    use bitcoincore_rpc::bitcoin::Network;
+    use zeromq::{PubSocket, SocketSend};
+
+    use super::*;
+
+    fn notification(topic: &str, body: Vec<u8>, sequence: u32) -> ZmqMessage {
+        let mut message = ZmqMessage::from(topic.as_bytes().to_vec());
+        message.push_back(body.into());
+        message.push_back(sequence.to_le_bytes().to_vec().into());
+        message
+    }
+
+    #[test]
+    fn test_parse_hashblock_in_display_order() {
+        let hash = genesis_block(Network::Bitcoin).block_hash();
+        let mut body = hash.to_byte_array().to_vec();
+        body.reverse();
+        let (topic, sequence, event) = parse(notification(HASHBLOCK, body, 7)).unwrap();
+        assert_eq!((topic.as_str(), sequence), (HASHBLOCK, 7));
+        assert_eq!(event, NodeEvent::BlockHash(hash));
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_topic() {
+        assert!(parse(notification("sequence", vec![], 0)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_subscriber_receives_published_notifications() {
+        let mut publisher = PubSocket::new();
+        let endpoint = publisher.bind("tcp://127.0.0.1:0").await.unwrap().to_string();
+        let (events, mut received) = broadcast::channel(16);
+        let block_notify = Arc::new(Notify::new());
+        let subscriber = ZmqSubscriber::with_endpoints(
+            [
+                (HASHBLOCK, None),
+                (RAWBLOCK, Some(endpoint.clone())),
+                (RAWTX, Some(endpoint)),
+            ],
+            events,
+            block_notify.clone(),
+        );
+        tokio::spawn(subscriber.run());
+
+        let block = genesis_block(Network::Bitcoin);
+        let tx = block.txdata[0].clone();
+        // Subscriptions take a moment to reach the publisher, so keep publishing until
+        // the first message gets through.
+        let event = tokio::time::timeout(Duration::from_secs(10), async {
+            let mut sequence = 0;
+            loop {
+                publisher
+                    .send(notification(RAWTX, encode::serialize(&tx), sequence))
+                    .await
+                    .unwrap();
+                sequence += 1;
+                if let Ok(Ok(event)) =
+                    tokio::time::timeout(Duration::from_millis(100), received.recv()).await
+                {
+                    return event;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(event, NodeEvent::Transaction(Arc::new(tx)));
+
+        publisher
+            .send(notification(RAWBLOCK, encode::serialize(&block), 0))
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(5), block_notify.notified())
+            .await
+            .unwrap();
+        loop {
+            if let NodeEvent::Block(received) = received.recv().await.unwrap() {
+                assert_eq!(*received, block);
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use garde::Validate;
+use tracing::info;
+
+use crate::dto::request::PageQueryParam;
+use crate::dto::response::{AddressResponse, AddressTxsResponse};
+use crate::error::{AppError, AppResult, ToAppResult};
+use crate::repo;
+use crate::server::state::AppState;
+use crate::service::script::parse_address;
+
+pub async fn get(state: &AppState, address: &str) -> AppResult<AddressResponse> {
+    let address = parse_address(address, state.config.bitcoin.network)?;
+    info!("Get address summary: {address}.");
+    let model = repo::address::find_by_address(&*state.db, &address)
+        .await?
+        .to_result_details(vec![("address".to_string(), address)])?;
+    Ok(AddressResponse::new(model, state.config.bitcoin.network))
+}
+
+pub async fn list_txs(
+    state: &AppState,
+    address: &str,
+    param: PageQueryParam,
+) -> AppResult<AddressTxsResponse> {
+    param
+        .validate(&())
+        .map_err(|e| AppError::InvalidPayloadError(e.to_string()))?;
+    let address = parse_address(address, state.config.bitcoin.network)?;
+    info!("Get address history: {address} page {}.", param.page_num);
+    let (txs, total) =
+        repo::address::find_txs_page(&*state.db, &address, param.page_num, param.page_size).await?;
+    Ok(AddressTxsResponse {
+        network: state.config.bitcoin.network.to_string(),
+        address,
+        page_num: param.page_num,
+        page_size: param.page_size,
+        total,
+        txs: txs.into_iter().map(Into::into).collect(),
+    })
+}
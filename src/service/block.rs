@@ -0,0 +1,77 @@
+use std::str::FromStr;
+
+use bitcoincore_rpc::bitcoin::BlockHash;
+use garde::Validate;
+use tracing::info;
+
+use crate::dto::request::PageQueryParam;
+use crate::dto::response::{BlockResponse, BlockTxsResponse, BlocksResponse};
+use crate::entity::block;
+use crate::error::{AppError, AppResult, ToAppResult};
+use crate::repo;
+use crate::server::state::AppState;
+
+pub async fn list(state: &AppState, param: PageQueryParam) -> AppResult<BlocksResponse> {
+    param
+        .validate(&())
+        .map_err(|e| AppError::InvalidPayloadError(e.to_string()))?;
+    info!("Get blocks page {}.", param.page_num);
+    let (blocks, total) = repo::block::find_page(&*state.db, param.page_num, param.page_size).await?;
+    Ok(BlocksResponse {
+        page_num: param.page_num,
+        page_size: param.page_size,
+        total,
+        blocks: blocks
+            .into_iter()
+            .map(|block| BlockResponse::new(block, state.config.bitcoin.network))
+            .collect(),
+    })
+}
+
+pub async fn get_by_height(state: &AppState, height: i32) -> AppResult<BlockResponse> {
+    info!("Get block by height: {height}.");
+    Ok(BlockResponse::new(find_by_height(state, height).await?, state.config.bitcoin.network))
+}
+
+pub async fn get_by_hash(state: &AppState, hash: &str) -> AppResult<BlockResponse> {
+    info!("Get block by hash: {hash}.");
+    Ok(BlockResponse::new(find_by_hash(state, hash).await?, state.config.bitcoin.network))
+}
+
+/// Lists the transactions of the block identified by `id`, either a height or a block hash.
+pub async fn list_txs(state: &AppState, id: &str, param: PageQueryParam) -> AppResult<BlockTxsResponse> {
+    param
+        .validate(&())
+        .map_err(|e| AppError::InvalidPayloadError(e.to_string()))?;
+    info!("Get transactions of block {id} page {}.", param.page_num);
+    let block = match id.parse::<i32>() {
+        Ok(height) => find_by_height(state, height).await?,
+        Err(_) => find_by_hash(state, id).await?,
+    };
+    let txs = repo::transaction::find_page_by_height(&*state.db, block.height, param.page_num, param.page_size)
+        .await?;
+    Ok(BlockTxsResponse {
+        network: state.config.bitcoin.network.to_string(),
+        height: block.height,
+        hash: block.hash,
+        page_num: param.page_num,
+        page_size: param.page_size,
+        total: block.tx_count as u64,
+        txs: txs.into_iter().map(Into::into).collect(),
+    })
+}
+
+async fn find_by_height(state: &AppState, height: i32) -> AppResult<block::Model> {
+    repo::block::find_by_height(&*state.db, height)
+        .await?
+        .to_result_details(vec![("height".to_string(), height.to_string())])
+}
+
+async fn find_by_hash(state: &AppState, hash: &str) -> AppResult<block::Model> {
+    let hash = BlockHash::from_str(hash)
+        .map_err(|e| AppError::BadRequestError(format!("invalid block hash {hash}: {e}")))?
+        .to_string();
+    repo::block::find_by_hash(&*state.db, &hash)
+        .await?
+        .to_result_details(vec![("hash".to_string(), hash)])
+}
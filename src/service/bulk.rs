@@ -0,0 +1,185 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+use bitcoincore_rpc::bitcoin::{Block, Network};
+use sea_orm::ConnectionTrait;
+use sqlx::PgPool;
+
+use crate::constant::BlockHeight;
+use crate::entity::{address, address_tx, block, transaction, tx_input, tx_output};
+use crate::error::AppResult;
+use crate::repo;
+use crate::repo::copy::sqlx_error;
+use crate::repo::tx_output::Spend;
+use crate::service::ingest::{self, BlockFees, BlockRows, Prevouts};
+use crate::service::pool::Pools;
+
+/// Buffers the rows of many blocks and writes them with `COPY` in one transaction.
+///
+/// Used for the historical backfill only. Spends of outputs created in the same batch are
+/// applied in memory before the rows are written, so only spends of older outputs need an
+/// update, and address deltas are summed per batch instead of per block.
+pub struct BulkWriter {
+    pool: PgPool,
+    max_txs: usize,
+    pools: Arc<Pools>,
+    network: Network,
+    batch: Batch,
+}
+
+#[derive(Default)]
+struct Batch {
+    blocks: Vec<block::Model>,
+    transactions: Vec<transaction::Model>,
+    outputs: Vec<tx_output::Model>,
+    inputs: Vec<tx_input::Model>,
+    address_txs: Vec<address_tx::Model>,
+    addresses: BTreeMap<String, address::Model>,
+    /// Spends of outputs created before this batch, with the height spending them.
+    spends: Vec<(Spend, i32)>,
+    /// Outputs created in this batch, to resolve prevouts without going to the database.
+    created: Prevouts,
+    /// Position in `outputs` of every output created in this batch.
+    positions: HashMap<(String, i32), usize>,
+    coinbases: HashSet<String>,
+}
+
+impl BulkWriter {
+    pub fn new(pool: PgPool, max_txs: usize, pools: Arc<Pools>, network: Network) -> Self {
+        Self {
+            pool,
+            max_txs,
+            pools,
+            network,
+            batch: Batch::default(),
+        }
+    }
+
+    /// The last block waiting to be written.
+    pub fn last_block(&self) -> Option<&block::Model> {
+        self.batch.blocks.last()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.batch.transactions.len() >= self.max_txs
+    }
+
+    /// Adds `block` to the batch. Prevouts are resolved from the batch first and from the
+    /// database for older outputs.
+    pub async fn push<C>(
+        &mut self,
+        conn: &C,
+        height: BlockHeight,
+        block: &Block,
+        fees: Option<&BlockFees>,
+    ) -> AppResult
+    where
+        C: ConnectionTrait,
+    {
+        let batch = &mut self.batch;
+        let created = ingest::block_outputs(block);
+        let mut prevouts = created.clone();
+        let mut missing = Vec::new();
+        for outpoint in ingest::external_spends(block, &prevouts) {
+            match batch.created.get(&outpoint) {
+                Some(output) => {
+                    prevouts.insert(outpoint, output.clone());
+                }
+                None => missing.push(outpoint),
+            }
+        }
+        prevouts.extend(ingest::lookup_prevouts(conn, &missing).await?);
+
+        let mut rows = ingest::block_rows(height, block, fees, &prevouts, &self.pools, self.network)?;
+        // A coinbase repeating one from earlier in the batch (BIP30) is only stored once,
+        // the same way the per-block writer skips it on conflict.
+        if let Some(coinbase) = rows.transactions.first().map(|tx| tx.txid.clone()) {
+            if !batch.coinbases.insert(coinbase.clone()) {
+                retain_other_txs(&mut rows, &coinbase);
+            }
+        }
+        batch.created.extend(created);
+        batch.add(rows, height as i32);
+        Ok(())
+    }
+
+    /// Writes the batch in a single transaction and returns the blocks written.
+    pub async fn flush<C>(&mut self, conn: &C) -> AppResult<Vec<block::Model>>
+    where
+        C: ConnectionTrait,
+    {
+        let mut batch = std::mem::take(&mut self.batch);
+        if batch.blocks.is_empty() {
+            return Ok(vec![]);
+        }
+        let coinbases: Vec<_> = batch.coinbases.iter().cloned().collect();
+        let existing: HashSet<_> = repo::transaction::find_existing_txids(conn, &coinbases)
+            .await?
+            .into_iter()
+            .collect();
+        if !existing.is_empty() {
+            batch.transactions.retain(|tx| !existing.contains(&tx.txid));
+            batch.outputs.retain(|output| !existing.contains(&output.txid));
+            batch.inputs.retain(|input| !existing.contains(&input.txid));
+            batch.address_txs.retain(|row| !existing.contains(&row.txid));
+        }
+        let addresses: Vec<_> = batch.addresses.into_values().collect();
+
+        let mut tx = self.pool.begin().await.map_err(sqlx_error)?;
+        repo::block::copy_many(&mut tx, &batch.blocks).await?;
+        repo::transaction::copy_many(&mut tx, &batch.transactions).await?;
+        repo::tx_output::copy_many(&mut tx, &batch.outputs).await?;
+        repo::tx_input::copy_many(&mut tx, &batch.inputs).await?;
+        repo::tx_output::copy_spends(&mut tx, &batch.spends).await?;
+        repo::address::copy_txs(&mut tx, &batch.address_txs).await?;
+        repo::address::copy_deltas(&mut tx, &addresses).await?;
+        tx.commit().await.map_err(sqlx_error)?;
+        Ok(batch.blocks)
+    }
+}
+
+impl Batch {
+    fn add(&mut self, rows: BlockRows, height: i32) {
+        for output in rows.outputs {
+            self.positions
+                .insert((output.txid.clone(), output.vout), self.outputs.len());
+            self.outputs.push(output);
+        }
+        for spend in rows.spends {
+            match self.positions.get(&(spend.txid.clone(), spend.vout)) {
+                Some(&position) => {
+                    let output = &mut self.outputs[position];
+                    output.spent_by_txid = Some(spend.spent_by_txid);
+                    output.spent_by_vin = Some(spend.spent_by_vin);
+                    output.spent_height = Some(height);
+                }
+                None => self.spends.push((spend, height)),
+            }
+        }
+        for delta in rows.addresses {
+            match self.addresses.get_mut(&delta.address) {
+                Some(total) => {
+                    total.balance += delta.balance;
+                    total.total_received += delta.total_received;
+                    total.total_sent += delta.total_sent;
+                    total.tx_count += delta.tx_count;
+                    total.last_seen_height = delta.last_seen_height;
+                }
+                None => {
+                    self.addresses.insert(delta.address.clone(), delta);
+                }
+            }
+        }
+        self.blocks.push(rows.block);
+        self.transactions.extend(rows.transactions);
+        self.inputs.extend(rows.inputs);
+        self.address_txs.extend(rows.address_txs);
+    }
+}
+
+fn retain_other_txs(rows: &mut BlockRows, txid: &str) {
+    rows.transactions.retain(|tx| tx.txid != txid);
+    rows.outputs.retain(|output| output.txid != txid);
+    rows.inputs.retain(|input| input.txid != txid);
+    rows.address_txs.retain(|row| row.txid != txid);
+}
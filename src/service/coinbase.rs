@@ -0,0 +1,229 @@
+use bitcoincore_rpc::bitcoin::consensus::Params;
+use bitcoincore_rpc::bitcoin::hex::{DisplayHex, FromHex};
+use bitcoincore_rpc::bitcoin::script::{self, Instruction};
+use bitcoincore_rpc::bitcoin::{Block, Network, Script};
+use sea_orm::{ActiveValue, TransactionTrait};
+use tracing::info;
+
+use crate::constant::{BlockHeight, BACKFILL_BATCH_BLOCKS};
+use crate::entity::block;
+use crate::error::AppResult;
+use crate::repo;
+use crate::server::state::AppState;
+
+/// `OP_RETURN`, a 36 byte push and the BIP141 commitment header, then the 32 byte commitment.
+const WITNESS_COMMITMENT_PREFIX: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
+/// Merged mining marker, followed by the 32 byte merkle root of the auxiliary chains.
+const MERGED_MINING_MAGIC: [u8; 4] = [0xfa, 0xbe, b'm', b'm'];
+
+/// Consensus limits on the size of the coinbase script.
+const MIN_SCRIPT_SIG_LEN: usize = 2;
+const MAX_SCRIPT_SIG_LEN: usize = 100;
+
+/// Shorter printable runs are mostly random bytes of the extranonce.
+const MIN_TAG_LEN: usize = 4;
+
+/// What a coinbase transaction says about the block and its miner.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CoinbaseInfo {
+    /// Height pushed first in the script, only read once BIP34 is active.
+    pub height: Option<i32>,
+    /// Printable ASCII runs of the pushed data, such as pool names and messages.
+    pub tags: Vec<String>,
+    /// Hex of the BIP141 witness commitment.
+    pub witness_commitment: Option<String>,
+    /// Hex of the auxiliary chains merkle root of a merge mined block.
+    pub merged_mining_root: Option<String>,
+    /// The script is not a sequence of pushes, has an invalid size, or lacks the height
+    /// BIP34 requires.
+    pub malformed: bool,
+}
+
+impl CoinbaseInfo {
+    pub fn from_block(height: BlockHeight, block: &Block, network: Network) -> Self {
+        let Some(coinbase) = block.coinbase() else {
+            return Self {
+                malformed: true,
+                ..Self::default()
+            };
+        };
+        let script_sig = coinbase
+            .input
+            .first()
+            .map(|input| input.script_sig.as_bytes())
+            .unwrap_or_default();
+        let outputs = coinbase.output.iter().map(|output| output.script_pubkey.as_bytes());
+        Self::decode(height, script_sig, outputs, network)
+    }
+
+    /// Decodes the coinbase script of the block at `height` of `network` and the output
+    /// scripts of its coinbase transaction.
+    pub fn decode<'a>(
+        height: BlockHeight,
+        script_sig: &[u8],
+        outputs: impl IntoIterator<Item = &'a [u8]>,
+        network: Network,
+    ) -> Self {
+        let script = Script::from_bytes(script_sig);
+        let mut malformed = !(MIN_SCRIPT_SIG_LEN..=MAX_SCRIPT_SIG_LEN).contains(&script_sig.len());
+
+        let bip34 = height >= Params::new(network).bip34_height;
+        let pushed_height = match script.instructions_minimal().next() {
+            Some(Ok(Instruction::PushBytes(bytes))) => script::read_scriptint(bytes.as_bytes()).ok(),
+            _ => None,
+        };
+        let coinbase_height = bip34.then_some(pushed_height).flatten();
+        if bip34 && coinbase_height != Some(i64::from(height)) {
+            malformed = true;
+        }
+
+        let mut tags = Vec::new();
+        for instruction in script.instructions() {
+            match instruction {
+                Ok(Instruction::PushBytes(bytes)) => push_tags(bytes.as_bytes(), &mut tags),
+                Ok(Instruction::Op(_)) => {}
+                Err(_) => {
+                    // A truncated push, fall back to the raw bytes.
+                    malformed = true;
+                    tags.clear();
+                    push_tags(script_sig, &mut tags);
+                    break;
+                }
+            }
+        }
+
+        let merged_mining_root = script_sig
+            .windows(MERGED_MINING_MAGIC.len())
+            .position(|window| window == MERGED_MINING_MAGIC)
+            .and_then(|index| script_sig.get(index + MERGED_MINING_MAGIC.len()..)?.get(..32))
+            .map(|root| root.to_lower_hex_string());
+        // Only the last matching output counts as the commitment.
+        let witness_commitment = outputs
+            .into_iter()
+            .filter(|output| output.starts_with(&WITNESS_COMMITMENT_PREFIX))
+            .filter_map(|output| output.get(WITNESS_COMMITMENT_PREFIX.len()..)?.get(..32))
+            .last()
+            .map(|commitment| commitment.to_lower_hex_string());
+
+        Self {
+            height: coinbase_height.and_then(|height| i32::try_from(height).ok()),
+            tags,
+            witness_commitment,
+            merged_mining_root,
+            malformed,
+        }
+    }
+}
+
+/// Decodes the coinbase of blocks indexed before the coinbase columns existed. The
+/// commitment is read back from the stored outputs.
+pub async fn backfill(state: &AppState) -> AppResult {
+    let network = state.config.bitcoin.network;
+    let mut after = -1;
+    let mut decoded = 0;
+    loop {
+        let rows =
+            repo::block::find_undecoded_coinbases(&*state.db, after, BACKFILL_BATCH_BLOCKS as u64).await?;
+        let Some(last) = rows.last() else {
+            break;
+        };
+        after = last.height;
+        let models = rows
+            .iter()
+            .map(|row| {
+                let script_sig = row
+                    .coinbase_raw
+                    .as_deref()
+                    .and_then(|raw| Vec::<u8>::from_hex(raw).ok())
+                    .unwrap_or_default();
+                let outputs: Vec<Vec<u8>> = row
+                    .commitments
+                    .as_deref()
+                    .unwrap_or_default()
+                    .split(',')
+                    .filter_map(|script| Vec::<u8>::from_hex(script).ok())
+                    .collect();
+                let outputs = outputs.iter().map(Vec::as_slice);
+                let info = CoinbaseInfo::decode(row.height as BlockHeight, &script_sig, outputs, network);
+                block::ActiveModel {
+                    height: ActiveValue::Unchanged(row.height),
+                    coinbase_height: ActiveValue::Set(info.height),
+                    coinbase_tags: ActiveValue::Set(Some(serde_json::json!(info.tags))),
+                    witness_commitment: ActiveValue::Set(info.witness_commitment),
+                    merged_mining_root: ActiveValue::Set(info.merged_mining_root),
+                    coinbase_malformed: ActiveValue::Set(info.malformed),
+                    ..Default::default()
+                }
+            })
+            .collect();
+        let tx = state.db.begin().await?;
+        repo::block::update_each(&tx, models).await?;
+        tx.commit().await?;
+        decoded += rows.len();
+        info!("Decoded the coinbase of {decoded} stored blocks.");
+    }
+    Ok(())
+}
+
+/// Appends the printable ASCII runs of `bytes` to `tags`, skipping duplicates.
+fn push_tags(bytes: &[u8], tags: &mut Vec<String>) {
+    for run in bytes.split(|byte| !(0x20..=0x7e).contains(byte)) {
+        let tag = String::from_utf8_lossy(run).trim().to_string();
+        if tag.len() >= MIN_TAG_LEN && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoincore_rpc::bitcoin::blockdata::constants::genesis_block;
+
+    use super::*;
+
+    #[test]
+    fn test_decode_genesis() {
+        let info = CoinbaseInfo::from_block(0, &genesis_block(Network::Bitcoin), Network::Bitcoin);
+        assert_eq!(info.height, None);
+        assert_eq!(
+            info.tags,
+            vec!["The Times 03/Jan/2009 Chancellor on brink of second bailout for banks"]
+        );
+        assert!(!info.malformed);
+    }
+
+    #[test]
+    fn test_decode_bip34_coinbase() {
+        // Height 840000, a pool tag, merged mining data and a witness commitment.
+        let mut script_sig = vec![0x03, 0x40, 0xd1, 0x0c, 0x0d];
+        script_sig.extend_from_slice(b"/Test Pool/\x00\x01");
+        script_sig.push(0x2c);
+        script_sig.extend_from_slice(&MERGED_MINING_MAGIC);
+        script_sig.extend_from_slice(&[0x11; 32]);
+        script_sig.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+        let mut commitment = WITNESS_COMMITMENT_PREFIX.to_vec();
+        commitment.extend_from_slice(&[0x22; 32]);
+        let payout = [0x00, 0x14].iter().chain(&[0x33; 20]).copied().collect::<Vec<_>>();
+
+        let info = CoinbaseInfo::decode(
+            840_000,
+            &script_sig,
+            [payout.as_slice(), commitment.as_slice()],
+            Network::Bitcoin,
+        );
+        assert_eq!(info.height, Some(840_000));
+        assert_eq!(info.tags, vec!["/Test Pool/"]);
+        assert_eq!(info.merged_mining_root, Some("11".repeat(32)));
+        assert_eq!(info.witness_commitment, Some("22".repeat(32)));
+        assert!(!info.malformed);
+
+        // Wrong height.
+        assert!(CoinbaseInfo::decode(840_001, &script_sig, [], Network::Bitcoin).malformed);
+        // Truncated push.
+        let script_sig = [0x03, 0x40, 0xd1, 0x0c, 0x20, b'a', b'b', b'c', b'd', b'e'];
+        let info = CoinbaseInfo::decode(840_000, &script_sig, [], Network::Bitcoin);
+        assert!(info.malformed);
+        assert_eq!(info.tags, vec!["abcde"]);
+    }
+}
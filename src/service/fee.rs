@@ -0,0 +1,182 @@
+use bitcoincore_rpc::bitcoin::Amount;
+use tracing::{info, warn};
+
+use crate::constant::{PROJECTED_BLOCKS, RECENT_FEE_BLOCKS};
+use crate::dto::response::{FeeEstimate, FeeSource, RecommendedFeesResponse};
+use crate::error::AppResult;
+use crate::repo;
+use crate::server::state::AppState;
+use crate::service::template::{BlockTemplate, MAX_BLOCK_WEIGHT};
+
+/// Confirmation targets in blocks: fastest, half hour, hour and economy.
+const TARGETS: [u16; 4] = [1, 3, 6, 144];
+
+/// Default minimum relay feerate in sat/vB.
+const MIN_RELAY_FEERATE: f64 = 1.0;
+
+/// A projected block lighter than this is not full, so everything in the mempool gets
+/// mined by then.
+const FULL_BLOCK_WEIGHT: u64 = MAX_BLOCK_WEIGHT / 100 * 95;
+
+/// Feerates reported by the node in sat/vB, `None` where it has no estimate.
+#[derive(Debug, Default)]
+struct NodeFees {
+    estimates: [Option<f64>; TARGETS.len()],
+    min_fee: Option<f64>,
+}
+
+/// Recommends feerates for the usual confirmation targets. The projected mempool blocks
+/// are preferred as they follow the mempool live, then the node's `estimatesmartfee`, then
+/// the median feerates of recent blocks. The node and database being unreachable only
+/// makes the answer less precise.
+pub async fn recommended(state: &AppState) -> AppResult<RecommendedFeesResponse> {
+    info!("Get recommended fees.");
+    let template = state.template.read().await.clone();
+    let template = (!template.blocks.is_empty()).then_some(template);
+    let node = node_fees(state).await;
+    let recent = repo::block::find_recent_median_fees(&*state.db, RECENT_FEE_BLOCKS)
+        .await
+        .unwrap_or_else(|e| {
+            warn!("Failed to get recent block fees: {e}.");
+            vec![]
+        });
+    Ok(recommend(template.as_deref(), &node, &recent))
+}
+
+/// Asks the node for what it has, sources without fee estimates still report the
+/// mempool minimum.
+async fn node_fees(state: &AppState) -> NodeFees {
+    let mut fees = NodeFees::default();
+    match state.source.mempool_min_fee().await {
+        Ok(min_fee) => fees.min_fee = Some(per_vbyte(min_fee)),
+        Err(e) => warn!("Failed to get the mempool minimum fee from the node: {e}."),
+    }
+    for (estimate, target) in fees.estimates.iter_mut().zip(TARGETS) {
+        match state.source.estimate_fee(target).await {
+            Ok(feerate) => *estimate = feerate.map(per_vbyte),
+            Err(e) => {
+                warn!("Failed to get fee estimates from the node: {e}.");
+                break;
+            }
+        }
+    }
+    fees
+}
+
+fn recommend(
+    template: Option<&BlockTemplate>,
+    node: &NodeFees,
+    recent: &[f64],
+) -> RecommendedFeesResponse {
+    let minimum = match node.min_fee {
+        Some(feerate) => estimate(feerate.max(MIN_RELAY_FEERATE), FeeSource::Node),
+        None => estimate(MIN_RELAY_FEERATE, FeeSource::Default),
+    };
+    let mut estimates = TARGETS.iter().zip(node.estimates).map(|(target, node)| {
+        let target = usize::from(*target);
+        template
+            .filter(|_| target <= PROJECTED_BLOCKS)
+            .map(|template| estimate(from_template(template, target, minimum.feerate), FeeSource::Mempool))
+            .or(node.map(|feerate| estimate(feerate, FeeSource::Node)))
+            .or(from_blocks(recent, target).map(|feerate| estimate(feerate, FeeSource::Blocks)))
+            .unwrap_or_else(|| minimum.clone())
+    });
+    let mut next = |ceiling: f64| {
+        let mut fee = estimates.next().unwrap_or_else(|| minimum.clone());
+        // Sources can disagree, a longer target never costs more than a shorter one.
+        fee.feerate = fee.feerate.max(minimum.feerate).min(ceiling);
+        fee
+    };
+    let fastest = next(f64::INFINITY);
+    let half_hour = next(fastest.feerate);
+    let hour = next(half_hour.feerate);
+    let economy = next(hour.feerate);
+    RecommendedFeesResponse {
+        fastest,
+        half_hour,
+        hour,
+        economy,
+        minimum,
+    }
+}
+
+/// The median feerate of the projected block `target`, or the minimum when the mempool
+/// clears before it.
+fn from_template(template: &BlockTemplate, target: usize, minimum: f64) -> f64 {
+    match template.blocks.get(target - 1) {
+        Some(block) if block.weight >= FULL_BLOCK_WEIGHT => block.median_feerate().unwrap_or(minimum),
+        _ => minimum,
+    }
+}
+
+/// The average median feerate of recent blocks for short targets, the lowest one for
+/// longer targets.
+fn from_blocks(recent: &[f64], target: usize) -> Option<f64> {
+    if recent.is_empty() {
+        return None;
+    }
+    if target <= recent.len() {
+        Some(recent.iter().sum::<f64>() / recent.len() as f64)
+    } else {
+        recent.iter().copied().min_by(f64::total_cmp)
+    }
+}
+
+fn estimate(feerate: f64, source: FeeSource) -> FeeEstimate {
+    FeeEstimate { feerate, source }
+}
+
+/// Converts a BTC/kvB feerate as returned by the node to sat/vB.
+fn per_vbyte(feerate: Amount) -> f64 {
+    feerate.to_sat() as f64 / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use bitcoincore_rpc::bitcoin::hashes::Hash;
+    use bitcoincore_rpc::bitcoin::Txid;
+
+    use super::*;
+    use crate::service::mempool::{Mempool, MempoolEntry};
+
+    #[test]
+    fn test_recommend_falls_back_by_source() {
+        // Two full projected blocks at 50 and 20 sat/vB.
+        let mut mempool = Mempool::default();
+        for (n, fee) in [(1u8, 50 * 990_000), (2, 20 * 990_000)] {
+            mempool.insert(MempoolEntry {
+                txid: Txid::from_byte_array([n; 32]),
+                fee,
+                vsize: 990_000,
+                weight: 3_960_000,
+                first_seen: 0,
+                parents: BTreeSet::new(),
+                spends: vec![],
+            });
+        }
+        let template = BlockTemplate::build(&mempool, PROJECTED_BLOCKS);
+        let node = NodeFees {
+            estimates: [Some(60.0), Some(30.0), Some(40.0), Some(8.0)],
+            min_fee: Some(2.0),
+        };
+        let fees = recommend(Some(&template), &node, &[10.0, 12.0]);
+        assert_eq!(fees.fastest, estimate(50.0, FeeSource::Mempool));
+        // The third projected block is empty, so the mempool clears within 3 blocks.
+        assert_eq!(fees.half_hour, estimate(2.0, FeeSource::Mempool));
+        assert_eq!(fees.hour, estimate(2.0, FeeSource::Mempool));
+        assert_eq!(fees.economy, estimate(2.0, FeeSource::Node));
+        assert_eq!(fees.minimum, estimate(2.0, FeeSource::Node));
+
+        let fees = recommend(None, &node, &[]);
+        assert_eq!(fees.half_hour, estimate(30.0, FeeSource::Node));
+        // Capped by the half hour estimate.
+        assert_eq!(fees.hour, estimate(30.0, FeeSource::Node));
+
+        let fees = recommend(None, &NodeFees::default(), &[10.0, 12.0]);
+        assert_eq!(fees.fastest, estimate(11.0, FeeSource::Blocks));
+        assert_eq!(fees.hour, estimate(10.0, FeeSource::Blocks));
+        assert_eq!(fees.minimum, estimate(1.0, FeeSource::Default));
+    }
+}
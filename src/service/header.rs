@@ -0,0 +1,452 @@
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+use bitcoincore_rpc::bitcoin::block::{Header, Version};
+use bitcoincore_rpc::bitcoin::blockdata::constants::genesis_block;
+use bitcoincore_rpc::bitcoin::consensus::Params;
+use bitcoincore_rpc::bitcoin::{BlockHash, CompactTarget, Network, Target, TxMerkleNode};
+use futures::{StreamExt, TryStreamExt};
+use sea_orm::ActiveValue;
+use tracing::info;
+
+use crate::constant::{BlockHeight, BACKFILL_BATCH_BLOCKS};
+use crate::entity::block;
+use crate::error::{AppError, AppResult, Resource, ResourceType};
+use crate::repo;
+use crate::server::state::AppState;
+
+/// Number of previous blocks whose median time a new block must exceed.
+const MEDIAN_TIME_SPAN: usize = 11;
+
+/// A stored header that breaks the consensus rules of the chain.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum HeaderError {
+    #[error("block {height} is {found}, the genesis block is {expected}")]
+    UnexpectedGenesis {
+        height: BlockHeight,
+        expected: BlockHash,
+        found: BlockHash,
+    },
+    #[error("block {height} is stored as {stored} but its header hashes to {computed}")]
+    HashMismatch {
+        height: BlockHeight,
+        stored: String,
+        computed: BlockHash,
+    },
+    #[error("block {height} has bits {bits:#010x}, above the proof-of-work limit")]
+    TargetAboveLimit { height: BlockHeight, bits: u32 },
+    #[error("block {height} hash {hash} does not meet its bits {bits:#010x}")]
+    InsufficientProofOfWork {
+        height: BlockHeight,
+        hash: BlockHash,
+        bits: u32,
+    },
+    #[error("block {height} is missing from the stored chain")]
+    MissingBlock { height: BlockHeight },
+    #[error("block {height} builds on {found} instead of {expected}")]
+    BrokenLink {
+        height: BlockHeight,
+        expected: BlockHash,
+        found: BlockHash,
+    },
+    #[error("block {height} time {time} is not after the median time past {median_time_past}")]
+    TimeTooOld {
+        height: BlockHeight,
+        time: u32,
+        median_time_past: u32,
+    },
+    #[error("block {height} has bits {found:#010x} instead of {expected:#010x}")]
+    UnexpectedBits {
+        height: BlockHeight,
+        expected: u32,
+        found: u32,
+    },
+}
+
+/// Checks headers one after the other, lowest first, remembering what the next header
+/// depends on. Starting above the genesis block trusts the first header's context.
+pub struct HeaderChain {
+    params: Params,
+    genesis: BlockHash,
+    /// Last verified block.
+    tip: Option<(BlockHeight, Header)>,
+    /// Times of the last [`MEDIAN_TIME_SPAN`] verified blocks, oldest first.
+    times: VecDeque<u32>,
+    /// Time of the first block of the current epoch, unless verification started after it.
+    epoch_start_time: Option<u32>,
+    /// Bits of the last block that is not a minimum difficulty block or starts an epoch,
+    /// unless verification started after it.
+    last_bits: Option<CompactTarget>,
+}
+
+impl HeaderChain {
+    pub fn new(network: Network) -> Self {
+        Self {
+            params: Params::new(network),
+            genesis: genesis_block(network).block_hash(),
+            tip: None,
+            times: VecDeque::with_capacity(MEDIAN_TIME_SPAN),
+            epoch_start_time: None,
+            last_bits: None,
+        }
+    }
+
+    /// Verifies the header stored at `height` under `hash` and extends the chain with it.
+    pub fn verify(&mut self, height: BlockHeight, hash: &str, header: &Header) -> Result<(), HeaderError> {
+        let computed = header.block_hash();
+        if computed.to_string() != hash {
+            return Err(HeaderError::HashMismatch {
+                height,
+                stored: hash.to_string(),
+                computed,
+            });
+        }
+        if height == 0 && computed != self.genesis {
+            return Err(HeaderError::UnexpectedGenesis {
+                height,
+                expected: self.genesis,
+                found: computed,
+            });
+        }
+        let bits = header.bits.to_consensus();
+        let target = header.target();
+        if target > self.params.pow_limit {
+            return Err(HeaderError::TargetAboveLimit { height, bits });
+        }
+        if !target.is_met_by(computed) {
+            return Err(HeaderError::InsufficientProofOfWork {
+                height,
+                hash: computed,
+                bits,
+            });
+        }
+        if let Some((tip_height, tip)) = &self.tip {
+            self.check_context(height, header, *tip_height, tip)?;
+        }
+        let interval = self.params.difficulty_adjustment_interval() as BlockHeight;
+        if height.is_multiple_of(interval) {
+            self.epoch_start_time = Some(header.time);
+        }
+        if height.is_multiple_of(interval) || header.bits != self.params.pow_limit.to_compact_lossy() {
+            self.last_bits = Some(header.bits);
+        }
+        if self.times.len() == MEDIAN_TIME_SPAN {
+            self.times.pop_front();
+        }
+        self.times.push_back(header.time);
+        self.tip = Some((height, *header));
+        Ok(())
+    }
+
+    fn check_context(
+        &self,
+        height: BlockHeight,
+        header: &Header,
+        tip_height: BlockHeight,
+        tip: &Header,
+    ) -> Result<(), HeaderError> {
+        if height != tip_height + 1 {
+            return Err(HeaderError::MissingBlock { height: tip_height + 1 });
+        }
+        let expected = tip.block_hash();
+        if header.prev_blockhash != expected {
+            return Err(HeaderError::BrokenLink {
+                height,
+                expected,
+                found: header.prev_blockhash,
+            });
+        }
+        let median_time_past = self.median_time_past();
+        if header.time <= median_time_past {
+            return Err(HeaderError::TimeTooOld {
+                height,
+                time: header.time,
+                median_time_past,
+            });
+        }
+        // As Bitcoin Core's `GetNextWorkRequired`.
+        let interval = self.params.difficulty_adjustment_interval() as BlockHeight;
+        let expected = if height.is_multiple_of(interval) {
+            match self.epoch_start_time {
+                Some(start) if !self.params.no_pow_retargeting => {
+                    next_work_required(tip.bits, i64::from(tip.time) - i64::from(start), &self.params)
+                }
+                Some(_) => tip.bits,
+                None => return Ok(()),
+            }
+        } else if !self.params.allow_min_difficulty_blocks {
+            tip.bits
+        } else if u64::from(header.time) > u64::from(tip.time) + 2 * self.params.pow_target_spacing {
+            // Test networks require minimum difficulty after two target spacings without a
+            // block, and go back to the difficulty of the epoch after it.
+            self.params.pow_limit.to_compact_lossy()
+        } else {
+            match self.last_bits {
+                Some(bits) => bits,
+                None => return Ok(()),
+            }
+        };
+        if header.bits != expected {
+            return Err(HeaderError::UnexpectedBits {
+                height,
+                expected: expected.to_consensus(),
+                found: header.bits.to_consensus(),
+            });
+        }
+        Ok(())
+    }
+
+    fn median_time_past(&self) -> u32 {
+        let mut times: Vec<_> = self.times.iter().copied().collect();
+        times.sort_unstable();
+        times.get(times.len() / 2).copied().unwrap_or_default()
+    }
+}
+
+/// The bits of the first block of an epoch, as Bitcoin Core's `CalculateNextWorkRequired`.
+/// `timespan` runs from the first to the last block of the previous epoch.
+pub fn next_work_required(last_bits: CompactTarget, timespan: i64, params: &Params) -> CompactTarget {
+    let target_timespan = params.pow_target_timespan;
+    let timespan = (timespan.max(0) as u64).clamp(target_timespan / 4, target_timespan * 4);
+    let target = mul_div(Target::from(last_bits), timespan, target_timespan);
+    target.min(params.pow_limit).to_compact_lossy()
+}
+
+/// `target * mul / div`. The product must fit in 256 bits, which holds for every target
+/// under the proof-of-work limits and factors up to four.
+fn mul_div(target: Target, mul: u64, div: u64) -> Target {
+    // Little endian 64-bit limbs.
+    let mut limbs = [0u64; 4];
+    for (index, byte) in target.to_be_bytes().iter().enumerate() {
+        let limb = &mut limbs[3 - index / 8];
+        *limb = (*limb << 8) | u64::from(*byte);
+    }
+    let mut carry = 0u128;
+    for limb in &mut limbs {
+        let product = u128::from(*limb) * u128::from(mul) + carry;
+        *limb = product as u64;
+        carry = product >> 64;
+    }
+    let mut remainder = carry;
+    for limb in limbs.iter_mut().rev() {
+        let dividend = (remainder << 64) | u128::from(*limb);
+        *limb = (dividend / u128::from(div)) as u64;
+        remainder = dividend % u128::from(div);
+    }
+    let mut bytes = [0u8; 32];
+    for (index, byte) in bytes.iter_mut().enumerate() {
+        *byte = (limbs[3 - index / 8] >> (8 * (7 - index % 8))) as u8;
+    }
+    Target::from_be_bytes(bytes)
+}
+
+/// Reads the headers of blocks stored before headers were kept from the node. A header is
+/// only stored when it hashes to the stored block hash.
+pub async fn backfill(state: &AppState) -> AppResult {
+    let mut after = -1;
+    let mut filled = 0;
+    loop {
+        let models = repo::block::find_missing_headers(&*state.db, after, BACKFILL_BATCH_BLOCKS as u64).await?;
+        let Some(last) = models.last() else {
+            break;
+        };
+        after = last.height;
+        let fetched = fetch_missing(state, &models).await?;
+        let mut updates = Vec::with_capacity(fetched.len());
+        for (height, header) in &fetched {
+            let model = models.iter().find(|model| model.height == *height);
+            if model.is_some_and(|model| header.block_hash().to_string() != model.hash) {
+                return Err(AppError::UnknownError(anyhow::anyhow!(
+                    "the node header of block {height} does not hash to the stored block"
+                )));
+            }
+            updates.push(header_model(*height, header));
+        }
+        repo::block::update_each(&*state.db, updates).await?;
+        filled += models.len();
+        info!("Read the headers of {filled} stored blocks from the node.");
+    }
+    Ok(())
+}
+
+/// Verifies every stored header from the lowest height up, without trusting the node.
+/// Runs after [`backfill`], so every stored block has its header.
+pub async fn verify_stored(state: &AppState) -> AppResult {
+    let Some(tip) = repo::block::find_max_height(&*state.db).await? else {
+        return Ok(());
+    };
+    info!("Verifying the stored headers up to block {tip}.");
+    let mut chain = HeaderChain::new(state.config.bitcoin.network);
+    for from in (0..=tip).step_by(BACKFILL_BATCH_BLOCKS as usize) {
+        let to = from.saturating_add(BACKFILL_BATCH_BLOCKS - 1).min(tip);
+        let models = repo::block::find_range(&*state.db, from, to).await?;
+        for model in &models {
+            let header = stored_header(model)
+                .ok_or_else(|| AppError::UnknownError(anyhow::anyhow!("no stored header for block {}", model.height)))?;
+            chain.verify(model.height as BlockHeight, &model.hash, &header)?;
+        }
+        info!("Verified the stored headers up to block {to}.");
+    }
+    Ok(())
+}
+
+/// Reads the headers the stored blocks in `models` are missing from the node.
+async fn fetch_missing(state: &AppState, models: &[block::Model]) -> AppResult<Vec<(i32, Header)>> {
+    let missing = models
+        .iter()
+        .filter(|model| stored_header(model).is_none())
+        .map(|model| {
+            BlockHash::from_str(&model.hash)
+                .map(|hash| (model.height, hash))
+                .map_err(|e| AppError::InvalidPayloadError(format!("invalid block hash {}: {e}", model.hash)))
+        })
+        .collect::<AppResult<Vec<_>>>()?;
+    futures::stream::iter(missing)
+        .map(|(height, hash)| async move {
+            let header = state.source.header(&hash).await?.ok_or_else(|| {
+                AppError::NotFoundError(Resource {
+                    details: vec![("hash".to_string(), hash.to_string())],
+                    resource_type: ResourceType::Block,
+                })
+            })?;
+            Ok((height, header.header))
+        })
+        .buffered(state.config.bitcoin.prefetch_concurrency.max(1))
+        .try_collect()
+        .await
+}
+
+/// The header kept with a stored block, `None` for blocks stored before headers were kept.
+pub fn stored_header(model: &block::Model) -> Option<Header> {
+    Some(Header {
+        version: Version::from_consensus(model.version?),
+        prev_blockhash: BlockHash::from_str(model.previous_hash.as_deref()?).ok()?,
+        merkle_root: TxMerkleNode::from_str(model.merkle_root.as_deref()?).ok()?,
+        time: u32::try_from(model.block_created_at.and_utc().timestamp()).ok()?,
+        bits: CompactTarget::from_consensus(u32::try_from(model.bits?).ok()?),
+        nonce: u32::try_from(model.nonce?).ok()?,
+    })
+}
+
+fn header_model(height: i32, header: &Header) -> block::ActiveModel {
+    block::ActiveModel {
+        height: ActiveValue::Unchanged(height),
+        version: ActiveValue::Set(Some(header.version.to_consensus())),
+        previous_hash: ActiveValue::Set(Some(header.prev_blockhash.to_string())),
+        merkle_root: ActiveValue::Set(Some(header.merkle_root.to_string())),
+        bits: ActiveValue::Set(Some(header.bits.to_consensus().into())),
+        nonce: ActiveValue::Set(Some(header.nonce.into())),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_one() -> Header {
+        Header {
+            version: Version::ONE,
+            prev_blockhash: genesis_block(Network::Bitcoin).block_hash(),
+            merkle_root: TxMerkleNode::from_str("0e3e2357e806b6cdb1f70b54c3a3a17b6714ee1f0e68bebb44a74b1efd512098")
+                .unwrap(),
+            time: 1_231_469_665,
+            bits: CompactTarget::from_consensus(0x1d00ffff),
+            nonce: 2_573_394_689,
+        }
+    }
+
+    #[test]
+    fn test_verify_mainnet_headers() {
+        let genesis = genesis_block(Network::Bitcoin).header;
+        let one = block_one();
+        let hash = "00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048";
+
+        let mut chain = HeaderChain::new(Network::Bitcoin);
+        chain.verify(0, &genesis.block_hash().to_string(), &genesis).unwrap();
+        chain.verify(1, hash, &one).unwrap();
+
+        let mut chain = HeaderChain::new(Network::Bitcoin);
+        chain.verify(0, &genesis.block_hash().to_string(), &genesis).unwrap();
+        assert!(matches!(chain.verify(1, "00", &one), Err(HeaderError::HashMismatch { .. })));
+        assert_eq!(chain.verify(2, hash, &one), Err(HeaderError::MissingBlock { height: 1 }));
+
+        let tampered = Header { nonce: 0, ..one };
+        let tampered_hash = tampered.block_hash().to_string();
+        assert!(matches!(
+            chain.verify(1, &tampered_hash, &tampered),
+            Err(HeaderError::InsufficientProofOfWork { .. })
+        ));
+
+        let mut chain = HeaderChain::new(Network::Bitcoin);
+        assert!(matches!(
+            chain.verify(0, hash, &one),
+            Err(HeaderError::UnexpectedGenesis { .. })
+        ));
+    }
+
+    #[test]
+    fn test_next_work_required() {
+        let params = Params::new(Network::Bitcoin);
+        let limit = CompactTarget::from_consensus(0x1d00ffff);
+        let half = params.pow_target_timespan as i64 / 2;
+        assert_eq!(next_work_required(limit, half, &params).to_consensus(), 0x1c7fff80);
+        // Slower blocks cannot lower the difficulty below the limit.
+        assert_eq!(next_work_required(limit, half * 20, &params), limit);
+        // At most a factor of four in one step.
+        let bits = CompactTarget::from_consensus(0x1b0404cb);
+        assert_eq!(next_work_required(bits, 0, &params).to_consensus(), 0x1b010132);
+    }
+
+    #[test]
+    fn test_min_difficulty_blocks() {
+        let params = Params::new(Network::Testnet);
+        let limit = params.pow_limit.to_compact_lossy();
+        let bits = CompactTarget::from_consensus(0x1c0ffff0);
+        let tip = Header { bits, ..block_one() };
+        let mut chain = HeaderChain::new(Network::Testnet);
+        chain.last_bits = Some(bits);
+        let next = |time: u32, bits: CompactTarget| Header {
+            prev_blockhash: tip.block_hash(),
+            time: tip.time + time,
+            bits,
+            ..tip
+        };
+        let unexpected = |expected: CompactTarget, found: CompactTarget| {
+            Err(HeaderError::UnexpectedBits {
+                height: 101,
+                expected: expected.to_consensus(),
+                found: found.to_consensus(),
+            })
+        };
+
+        // Slow blocks must be minimum difficulty blocks, others keep the difficulty.
+        assert_eq!(chain.check_context(101, &next(1_201, limit), 100, &tip), Ok(()));
+        assert_eq!(chain.check_context(101, &next(1_201, bits), 100, &tip), unexpected(limit, bits));
+        assert_eq!(chain.check_context(101, &next(600, bits), 100, &tip), Ok(()));
+        assert_eq!(chain.check_context(101, &next(600, limit), 100, &tip), unexpected(bits, limit));
+
+        // After a minimum difficulty block the difficulty goes back to the epoch's.
+        let min = Header { bits: limit, ..tip };
+        let after_min = Header {
+            prev_blockhash: min.block_hash(),
+            time: min.time + 600,
+            ..tip
+        };
+        assert_eq!(chain.check_context(102, &after_min, 101, &min), Ok(()));
+
+        // Epoch boundaries are always retargeted.
+        chain.epoch_start_time = Some(tip.time - params.pow_target_timespan as u32 / 2);
+        let retarget = next_work_required(bits, params.pow_target_timespan as i64 / 2, &params);
+        let boundary = |bits: CompactTarget| Header { time: tip.time + 1, ..next(0, bits) };
+        assert_eq!(chain.check_context(2_016, &boundary(retarget), 2_015, &tip), Ok(()));
+        assert_eq!(
+            chain.check_context(2_016, &boundary(limit), 2_015, &tip),
+            Err(HeaderError::UnexpectedBits {
+                height: 2_016,
+                expected: retarget.to_consensus(),
+                found: limit.to_consensus(),
+            })
+        );
+    }
+}
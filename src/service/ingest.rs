@@ -0,0 +1,596 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::anyhow;
+use bitcoincore_rpc::bitcoin::hex::DisplayHex;
+use bitcoincore_rpc::bitcoin::{Amount, Block, Network, OutPoint, ScriptBuf, Transaction, TxOut, Txid};
+use bitcoincore_rpc::json::GetBlockStatsResult;
+use chrono::DateTime;
+use sea_orm::ConnectionTrait;
+
+use crate::constant::BlockHeight;
+use crate::entity::{address, address_tx, block, transaction, tx_input, tx_output};
+use crate::error::{AppError, AppResult};
+use crate::repo;
+use crate::repo::tx_output::Spend;
+use crate::service::coinbase::CoinbaseInfo;
+use crate::service::pool::Pools;
+use crate::service::script::{script_address, ScriptType};
+
+/// Percentiles of the feerate span between the minimum and the maximum.
+const FEE_PERCENTILES: [f64; 5] = [0.10, 0.25, 0.50, 0.75, 0.90];
+
+/// Fee summary of a block: total fees in sats, the feerate span
+/// `[min, 10th, 25th, 50th, 75th, 90th, max]` and the median feerate in sat/vB.
+/// Percentiles are weighted by vsize and the coinbase is left out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlockFees {
+    pub total: f64,
+    pub span: [f64; 7],
+    pub median: f64,
+}
+
+impl BlockFees {
+    /// Computes the summary from the transaction rows of a block, coinbase first.
+    pub fn from_transactions(transactions: &[transaction::Model]) -> Self {
+        let fees = transactions
+            .iter()
+            .skip(1)
+            .map(|tx| (tx.fee as u64, tx.vsize as u64));
+        Self::from_fees(fees)
+    }
+
+    /// `fees` holds the fee and vsize of every transaction except the coinbase. A
+    /// percentile is the feerate of the transaction holding that share of the block's
+    /// vsize once sorted by feerate, the same way `getblockstats` picks them.
+    pub(crate) fn from_fees(fees: impl IntoIterator<Item = (u64, u64)>) -> Self {
+        let mut total = 0;
+        let mut feerates = Vec::new();
+        for (fee, vsize) in fees {
+            total += fee;
+            feerates.push((fee as f64 / vsize.max(1) as f64, vsize));
+        }
+        feerates.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let (Some(min), Some(max)) = (feerates.first(), feerates.last()) else {
+            return Self::default();
+        };
+        let mut span = [min.0, 0.0, 0.0, 0.0, 0.0, 0.0, max.0];
+        let total_vsize: u64 = feerates.iter().map(|(_, vsize)| vsize).sum();
+        let mut next = 0;
+        let mut cumulative = 0;
+        for (feerate, vsize) in &feerates {
+            cumulative += vsize;
+            while next < FEE_PERCENTILES.len()
+                && cumulative as f64 >= total_vsize as f64 * FEE_PERCENTILES[next]
+            {
+                span[next + 1] = *feerate;
+                next += 1;
+            }
+        }
+        Self {
+            total: total as f64,
+            span,
+            median: span[3],
+        }
+    }
+}
+
+impl From<&GetBlockStatsResult> for BlockFees {
+    fn from(stats: &GetBlockStatsResult) -> Self {
+        let percentiles = &stats.fee_rate_percentiles;
+        let span = [
+            stats.min_fee_rate,
+            percentiles.fr_10th,
+            percentiles.fr_25th,
+            percentiles.fr_50th,
+            percentiles.fr_75th,
+            percentiles.fr_90th,
+            stats.max_fee_rate,
+        ]
+        .map(|rate| rate.to_sat() as f64);
+        Self {
+            total: stats.total_fee.to_sat() as f64,
+            span,
+            median: percentiles.fr_50th.to_sat() as f64,
+        }
+    }
+}
+
+/// Previous outputs spent by a block, keyed by the outpoint that spends them.
+pub type Prevouts = HashMap<OutPoint, TxOut>;
+
+/// Fee and script flags of a transaction, shared by the indexed rows and the transactions
+/// read from the node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxSummary {
+    /// `None` unless the prevout of every input is known. Coinbase transactions pay none.
+    pub fee: Option<u64>,
+    pub is_segwit: bool,
+    /// Whether the transaction spends a P2TR output. Paying to one does not count.
+    pub is_taproot: bool,
+}
+
+impl TxSummary {
+    /// `spent` holds the prevout of every input of `tx`, in order, `None` where unknown.
+    pub fn new(tx: &Transaction, spent: &[Option<&TxOut>]) -> Self {
+        let fee = if tx.is_coinbase() {
+            Some(0)
+        } else {
+            spent.iter().copied().collect::<Option<Vec<_>>>().map(|spent| {
+                let input_value: u64 = spent.iter().map(|prevout| prevout.value.to_sat()).sum();
+                let output_value: u64 = tx.output.iter().map(|output| output.value.to_sat()).sum();
+                input_value.saturating_sub(output_value)
+            })
+        };
+        Self {
+            fee,
+            is_segwit: tx.input.iter().any(|input| !input.witness.is_empty()),
+            is_taproot: spent.iter().flatten().any(|prevout| prevout.script_pubkey.is_p2tr()),
+        }
+    }
+}
+
+/// Outputs created inside `block`, used to resolve spends of outputs from the same block.
+pub fn block_outputs(block: &Block) -> Prevouts {
+    block
+        .txdata
+        .iter()
+        .flat_map(|tx| {
+            let txid = tx.txid();
+            tx.output
+                .iter()
+                .enumerate()
+                .map(move |(vout, output)| (OutPoint::new(txid, vout as u32), output.clone()))
+        })
+        .collect()
+}
+
+/// Outpoints spent by `block` that are missing from `outputs`.
+pub fn external_spends(block: &Block, outputs: &Prevouts) -> Vec<OutPoint> {
+    block
+        .txdata
+        .iter()
+        .filter(|tx| !tx.is_coinbase())
+        .flat_map(|tx| tx.input.iter().map(|input| input.previous_output))
+        .filter(|outpoint| !outputs.contains_key(outpoint))
+        .collect()
+}
+
+/// Resolves the outputs spent by `block`. Outputs created in the block itself are taken
+/// directly, the rest are read back from `tx_outputs`.
+pub async fn resolve_prevouts<C>(conn: &C, block: &Block) -> AppResult<Prevouts>
+where
+    C: ConnectionTrait,
+{
+    let mut prevouts = block_outputs(block);
+    let missing = external_spends(block, &prevouts);
+    prevouts.extend(lookup_prevouts(conn, &missing).await?);
+    Ok(prevouts)
+}
+
+/// Reads the stored outputs for `outpoints`. Outpoints that were never indexed are left out.
+pub async fn lookup_prevouts<C>(conn: &C, outpoints: &[OutPoint]) -> AppResult<Prevouts>
+where
+    C: ConnectionTrait,
+{
+    let keys: Vec<_> = outpoints
+        .iter()
+        .map(|outpoint| (outpoint.txid.to_string(), outpoint.vout as i32))
+        .collect();
+    let mut prevouts = Prevouts::new();
+    for output in repo::tx_output::find_by_outpoints(conn, &keys).await? {
+        let txid: Txid = output
+            .txid
+            .parse()
+            .map_err(|e| anyhow!("invalid stored txid {}: {e}", output.txid))?;
+        let script_pubkey = ScriptBuf::from_hex(&output.script_pubkey)
+            .map_err(|e| anyhow!("invalid stored script {}:{}: {e}", output.txid, output.vout))?;
+        prevouts.insert(
+            OutPoint::new(txid, output.vout as u32),
+            TxOut {
+                value: Amount::from_sat(output.value as u64),
+                script_pubkey,
+            },
+        );
+    }
+    Ok(prevouts)
+}
+
+/// Rows derived from one block. Both the per-block writer and the bulk writer store these.
+#[derive(Debug)]
+pub struct BlockRows {
+    pub block: block::Model,
+    pub transactions: Vec<transaction::Model>,
+    pub outputs: Vec<tx_output::Model>,
+    pub inputs: Vec<tx_input::Model>,
+    pub spends: Vec<Spend>,
+    pub address_txs: Vec<address_tx::Model>,
+    pub addresses: Vec<address::Model>,
+}
+
+/// Builds the rows of `block`. Without `fees` the fee summary is computed from the
+/// resolved prevouts.
+pub fn block_rows(
+    height: BlockHeight,
+    block: &Block,
+    fees: Option<&BlockFees>,
+    prevouts: &Prevouts,
+    pools: &Pools,
+    network: Network,
+) -> AppResult<BlockRows> {
+    let header = &block.header;
+    let block_created_at = DateTime::from_timestamp(header.time.into(), 0)
+        .ok_or_else(|| AppError::InvalidPayloadError(format!("invalid block time {}", header.time)))?
+        .naive_utc();
+    let coinbase_raw = block
+        .coinbase()
+        .and_then(|tx| tx.input.first())
+        .map(|input| input.script_sig.as_bytes().to_lower_hex_string());
+    let transactions = block
+        .txdata
+        .iter()
+        .enumerate()
+        .map(|(index, tx)| transaction_model(height, index, tx, prevouts))
+        .collect::<AppResult<Vec<_>>>()?;
+    let fees = match fees {
+        Some(fees) => fees.clone(),
+        None => BlockFees::from_transactions(&transactions),
+    };
+    let coinbase = CoinbaseInfo::from_block(height, block, network);
+    let model = block::Model {
+        height: height as i32,
+        hash: block.block_hash().to_string(),
+        block_created_at,
+        size: block.total_size() as i32,
+        weight: block.weight().to_wu() as i32,
+        tx_count: block.txdata.len() as i32,
+        coinbase_raw,
+        difficulty: header.difficulty_float(),
+        pool_id: Some(pools.identify(block, network)),
+        fees: fees.total,
+        fee_span: serde_json::json!(fees.span),
+        median_fee: fees.median,
+        coinbase_height: coinbase.height,
+        coinbase_tags: Some(serde_json::json!(coinbase.tags)),
+        witness_commitment: coinbase.witness_commitment,
+        merged_mining_root: coinbase.merged_mining_root,
+        coinbase_malformed: coinbase.malformed,
+        version: Some(header.version.to_consensus()),
+        previous_hash: Some(header.prev_blockhash.to_string()),
+        merkle_root: Some(header.merkle_root.to_string()),
+        bits: Some(header.bits.to_consensus().into()),
+        nonce: Some(header.nonce.into()),
+    };
+    let outputs = block
+        .txdata
+        .iter()
+        .flat_map(|tx| output_models(height, tx, network))
+        .collect();
+
+    let mut inputs = Vec::new();
+    let mut spends = Vec::new();
+    for tx in &block.txdata {
+        let txid = tx.txid().to_string();
+        for (vin, input) in tx.input.iter().enumerate() {
+            let prevout = (!tx.is_coinbase()).then_some(&input.previous_output);
+            let spent = prevout.and_then(|outpoint| prevouts.get(outpoint));
+            if let Some(outpoint) = prevout {
+                spends.push(Spend {
+                    txid: outpoint.txid.to_string(),
+                    vout: outpoint.vout as i32,
+                    spent_by_txid: txid.clone(),
+                    spent_by_vin: vin as i32,
+                });
+            }
+            let witness: Vec<_> = input.witness.iter().map(|item| item.to_lower_hex_string()).collect();
+            inputs.push(tx_input::Model {
+                txid: txid.clone(),
+                vin: vin as i32,
+                block_height: height as i32,
+                prev_txid: prevout.map(|outpoint| outpoint.txid.to_string()),
+                prev_vout: prevout.map(|outpoint| outpoint.vout as i32),
+                value: spent.map_or(0, |output| output.value.to_sat() as i64),
+                address: spent.and_then(|output| script_address(&output.script_pubkey, network)),
+                script_sig: input.script_sig.as_bytes().to_lower_hex_string(),
+                witness: serde_json::json!(witness),
+                sequence: input.sequence.0.into(),
+            });
+        }
+    }
+    let (address_txs, addresses) = address_rows(height, block, prevouts, network);
+    Ok(BlockRows {
+        block: model,
+        transactions,
+        outputs,
+        inputs,
+        spends,
+        address_txs,
+        addresses,
+    })
+}
+
+pub async fn index_block<C>(
+    conn: &C,
+    height: BlockHeight,
+    block: &Block,
+    fees: Option<&BlockFees>,
+    prevouts: &Prevouts,
+    pools: &Pools,
+    network: Network,
+) -> AppResult<block::Model>
+where
+    C: ConnectionTrait,
+{
+    let rows = block_rows(height, block, fees, prevouts, pools, network)?;
+    let model = repo::block::save(conn, rows.block.into()).await?;
+    repo::transaction::save_many(conn, active(rows.transactions)).await?;
+    repo::tx_output::save_many(conn, active(rows.outputs)).await?;
+    repo::tx_input::save_many(conn, active(rows.inputs)).await?;
+    repo::tx_output::mark_spent(conn, &rows.spends, height as i32).await?;
+    repo::address::save_txs(conn, active(rows.address_txs)).await?;
+    repo::address::apply_deltas(conn, active(rows.addresses)).await?;
+    Ok(model)
+}
+
+fn active<M, A>(models: Vec<M>) -> Vec<A>
+where
+    A: From<M>,
+{
+    models.into_iter().map(A::from).collect()
+}
+
+#[derive(Debug, Default)]
+struct AddressEffect {
+    block_index: usize,
+    received: i64,
+    sent: i64,
+}
+
+/// Works out what every transaction of `block` received and sent per address, together
+/// with the per-address totals to add to the running balances.
+fn address_rows(
+    height: BlockHeight,
+    block: &Block,
+    prevouts: &Prevouts,
+    network: Network,
+) -> (Vec<address_tx::Model>, Vec<address::Model>) {
+    let mut effects: BTreeMap<(String, Txid), AddressEffect> = BTreeMap::new();
+    for (block_index, tx) in block.txdata.iter().enumerate() {
+        let txid = tx.txid();
+        for output in &tx.output {
+            if let Some(address) = script_address(&output.script_pubkey, network) {
+                let effect = effects.entry((address, txid)).or_default();
+                effect.block_index = block_index;
+                effect.received += output.value.to_sat() as i64;
+            }
+        }
+        if tx.is_coinbase() {
+            continue;
+        }
+        for input in &tx.input {
+            let Some(spent) = prevouts.get(&input.previous_output) else {
+                continue;
+            };
+            if let Some(address) = script_address(&spent.script_pubkey, network) {
+                let effect = effects.entry((address, txid)).or_default();
+                effect.block_index = block_index;
+                effect.sent += spent.value.to_sat() as i64;
+            }
+        }
+    }
+
+    let mut totals: BTreeMap<&str, address::Model> = BTreeMap::new();
+    for ((address, _), effect) in &effects {
+        let total = totals.entry(address).or_insert_with(|| address::Model {
+            address: address.clone(),
+            balance: 0,
+            total_received: 0,
+            total_sent: 0,
+            tx_count: 0,
+            first_seen_height: height as i32,
+            last_seen_height: height as i32,
+        });
+        total.balance += effect.received - effect.sent;
+        total.total_received += effect.received;
+        total.total_sent += effect.sent;
+        total.tx_count += 1;
+    }
+    let totals = totals.into_values().collect();
+
+    let address_txs = effects
+        .into_iter()
+        .map(|((address, txid), effect)| address_tx::Model {
+            address,
+            txid: txid.to_string(),
+            block_height: height as i32,
+            block_index: effect.block_index as i32,
+            received: effect.received,
+            sent: effect.sent,
+        })
+        .collect();
+    (address_txs, totals)
+}
+
+fn output_models(height: BlockHeight, tx: &Transaction, network: Network) -> Vec<tx_output::Model> {
+    let txid = tx.txid().to_string();
+    tx.output
+        .iter()
+        .enumerate()
+        .map(|(vout, output)| tx_output::Model {
+            txid: txid.clone(),
+            vout: vout as i32,
+            block_height: height as i32,
+            value: output.value.to_sat() as i64,
+            script_pubkey: output.script_pubkey.as_bytes().to_lower_hex_string(),
+            script_type: ScriptType::of(&output.script_pubkey).to_string(),
+            address: script_address(&output.script_pubkey, network),
+            spent_by_txid: None,
+            spent_by_vin: None,
+            spent_height: None,
+        })
+        .collect()
+}
+
+fn transaction_model(
+    height: BlockHeight,
+    index: usize,
+    tx: &Transaction,
+    prevouts: &Prevouts,
+) -> AppResult<transaction::Model> {
+    let spent = if tx.is_coinbase() {
+        vec![]
+    } else {
+        tx.input
+            .iter()
+            .map(|input| {
+                prevouts
+                    .get(&input.previous_output)
+                    .map(Some)
+                    .ok_or_else(|| anyhow!("missing prevout {}", input.previous_output).into())
+            })
+            .collect::<AppResult<Vec<_>>>()?
+    };
+    let summary = TxSummary::new(tx, &spent);
+    let fee = summary.fee.unwrap_or_default();
+    let vsize = tx.vsize();
+    Ok(transaction::Model {
+        txid: tx.txid().to_string(),
+        block_height: height as i32,
+        block_index: index as i32,
+        size: tx.total_size() as i32,
+        vsize: vsize as i32,
+        weight: tx.weight().to_wu() as i32,
+        fee: fee as i64,
+        feerate: fee as f64 / vsize as f64,
+        version: tx.version.0,
+        locktime: tx.lock_time.to_consensus_u32().into(),
+        input_count: tx.input.len() as i32,
+        output_count: tx.output.len() as i32,
+        is_segwit: summary.is_segwit,
+        is_taproot: summary.is_taproot,
+    })
+}
+
+/// Removes every block above `fork_height` together with the rows derived from it.
+pub async fn rollback<C>(conn: &C, fork_height: BlockHeight) -> AppResult<u64>
+where
+    C: ConnectionTrait,
+{
+    let height = fork_height as i32;
+    repo::address::revert_above(conn, height).await?;
+    repo::tx_input::delete_above(conn, height).await?;
+    repo::tx_output::delete_above(conn, height).await?;
+    repo::tx_output::unspend_above(conn, height).await?;
+    repo::transaction::delete_above(conn, height).await?;
+    repo::block::delete_above(conn, height).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use bitcoincore_rpc::bitcoin::consensus::encode::deserialize;
+    use bitcoincore_rpc::bitcoin::hex::FromHex;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+
+    /// Layout of `fixtures/fees/*.json`, written by `scripts/fetch_fee_fixture.sh`.
+    #[derive(Deserialize)]
+    struct FeeFixture {
+        height: BlockHeight,
+        hash: String,
+        block: String,
+        prevouts: Vec<FixturePrevout>,
+        /// The fee fields of `getblockstats`.
+        getblockstats: serde_json::Value,
+    }
+
+    #[derive(Deserialize)]
+    struct FixturePrevout {
+        txid: Txid,
+        vout: u32,
+        value: u64,
+        script_pubkey: String,
+    }
+
+    /// A full `getblockstats` answer around the fee fields of a fixture. The other fields
+    /// are not read by [`BlockFees`].
+    fn block_stats(fixture: &FeeFixture) -> GetBlockStatsResult {
+        let mut stats = json!({
+            "avgfee": 0, "avgfeerate": 0, "avgtxsize": 0, "blockhash": fixture.hash, "height": fixture.height,
+            "ins": 0, "maxfee": 0, "maxtxsize": 0, "mediantime": 0, "mediantxsize": 0, "minfee": 0,
+            "mintxsize": 0, "outs": 0, "subsidy": 0, "swtotal_size": 0, "swtotal_weight": 0, "swtxs": 0,
+            "time": 0, "total_out": 0, "total_size": 0, "total_weight": 0, "txs": 0, "utxo_increase": 0,
+            "utxo_size_inc": 0,
+        });
+        let fees = fixture.getblockstats.as_object().unwrap().clone();
+        stats.as_object_mut().unwrap().extend(fees);
+        serde_json::from_value(stats).unwrap()
+    }
+
+    #[test]
+    fn test_fees_match_mainnet_block_stats() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/fees");
+        let mut checked = 0;
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let fixture: FeeFixture = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+            let block: Block = deserialize(&Vec::<u8>::from_hex(&fixture.block).unwrap()).unwrap();
+            assert_eq!(block.block_hash().to_string(), fixture.hash, "{}", path.display());
+            let mut prevouts = block_outputs(&block);
+            for prevout in &fixture.prevouts {
+                let output = TxOut {
+                    value: Amount::from_sat(prevout.value),
+                    script_pubkey: ScriptBuf::from_hex(&prevout.script_pubkey).unwrap(),
+                };
+                prevouts.insert(OutPoint::new(prevout.txid, prevout.vout), output);
+            }
+            let rows = block_rows(fixture.height, &block, None, &prevouts, &Pools::default(), Network::Bitcoin).unwrap();
+
+            // Bitcoin Core reports whole sat/vB, the index keeps the fraction.
+            let expected = BlockFees::from(&block_stats(&fixture));
+            let indexed = BlockFees::from_transactions(&rows.transactions);
+            assert_eq!(indexed.total, expected.total, "{}", path.display());
+            assert_eq!(indexed.span.map(f64::floor), expected.span, "{}", path.display());
+            assert_eq!(indexed.median.floor(), expected.median, "{}", path.display());
+            checked += 1;
+        }
+        assert!(checked > 0);
+    }
+
+    #[test]
+    fn test_fee_span_weighted_by_vsize() {
+        // (fee, vsize): feerates 10, 2, 30, 5 and 1 sat/vB over 1000 vbytes.
+        let fees = BlockFees::from_fees([(1000, 100), (500, 250), (3000, 100), (2000, 400), (150, 150)]);
+        assert_eq!(fees.total, 6650.0);
+        assert_eq!(fees.span, [1.0, 1.0, 2.0, 5.0, 5.0, 10.0, 30.0]);
+        assert_eq!(fees.median, 5.0);
+
+        assert_eq!(BlockFees::from_fees([]), BlockFees::default());
+    }
+
+    #[test]
+    fn test_taproot_counts_spent_prevouts_only() {
+        use bitcoincore_rpc::bitcoin::hashes::Hash;
+
+        use crate::client::mock::spend;
+
+        let p2tr = ScriptBuf::from_bytes([&[0x51, 0x20][..], &[7; 32]].concat());
+        let p2wpkh = ScriptBuf::from_bytes([&[0x00, 0x14][..], &[7; 20]].concat());
+        let output = |script: &ScriptBuf| TxOut {
+            value: Amount::from_sat(1_000),
+            script_pubkey: script.clone(),
+        };
+        let outpoint = OutPoint::new(Txid::all_zeros(), 0);
+
+        let pays_p2tr = spend(&[outpoint], vec![output(&p2tr)]);
+        let summary = TxSummary::new(&pays_p2tr, &[Some(&output(&p2wpkh))]);
+        assert_eq!(summary.fee, Some(0));
+        assert!(!summary.is_taproot);
+
+        let spends_p2tr = spend(&[outpoint], vec![output(&p2wpkh)]);
+        assert!(TxSummary::new(&spends_p2tr, &[Some(&output(&p2tr))]).is_taproot);
+        assert_eq!(TxSummary::new(&spends_p2tr, &[None]).fee, None);
+    }
+}
@@ -0,0 +1,364 @@
+use std::collections::{BTreeSet, HashMap};
+use std::str::FromStr;
+
+use bitcoincore_rpc::bitcoin::{Block, OutPoint, Transaction, Txid};
+use bitcoincore_rpc::json::GetMempoolEntryResult;
+use tracing::info;
+
+use crate::dto::response::{
+    FeeHistogramBucket, MempoolResponse, ProjectedBlockResponse, ProjectedTxResponse,
+};
+use crate::error::{AppError, AppResult, Resource, ResourceType};
+use crate::server::state::AppState;
+
+/// Lower bounds of the fee histogram buckets in sat/vB.
+const FEE_HISTOGRAM_BUCKETS: [f64; 30] = [
+    0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 8.0, 10.0, 12.0, 15.0, 20.0, 25.0, 30.0, 40.0, 50.0, 60.0,
+    70.0, 80.0, 90.0, 100.0, 125.0, 150.0, 200.0, 250.0, 300.0, 400.0, 500.0, 750.0, 1000.0,
+];
+
+/// An unconfirmed transaction as tracked by [`Mempool`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MempoolEntry {
+    pub txid: Txid,
+    /// Fee in sats.
+    pub fee: u64,
+    pub vsize: u64,
+    pub weight: u64,
+    /// Unix time the node first saw the transaction.
+    pub first_seen: u64,
+    /// Unconfirmed transactions this one spends from.
+    pub parents: BTreeSet<Txid>,
+    /// Outpoints spent by the transaction.
+    pub spends: Vec<OutPoint>,
+}
+
+impl MempoolEntry {
+    pub fn from_rpc(txid: Txid, entry: &GetMempoolEntryResult, tx: &Transaction) -> Self {
+        Self {
+            txid,
+            fee: entry.fees.base.to_sat(),
+            vsize: entry.vsize,
+            weight: entry.weight.unwrap_or(entry.vsize * 4),
+            first_seen: entry.time,
+            parents: entry.depends.iter().copied().collect(),
+            spends: tx.input.iter().map(|input| input.previous_output).collect(),
+        }
+    }
+
+    pub fn feerate(&self) -> f64 {
+        self.fee as f64 / self.vsize as f64
+    }
+}
+
+/// In-memory view of the node's mempool with the unconfirmed parent/child graph.
+#[derive(Debug, Default)]
+pub struct Mempool {
+    entries: HashMap<Txid, MempoolEntry>,
+    /// Children of every transaction, including parents that are not tracked yet.
+    children: HashMap<Txid, BTreeSet<Txid>>,
+    /// The tracked transaction spending each outpoint, to detect replacements.
+    spenders: HashMap<OutPoint, Txid>,
+}
+
+impl Mempool {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, txid: &Txid) -> bool {
+        self.entries.contains_key(txid)
+    }
+
+    pub fn get(&self, txid: &Txid) -> Option<&MempoolEntry> {
+        self.entries.get(txid)
+    }
+
+    /// The tracked transaction spending `outpoint` and the input spending it.
+    pub fn spender(&self, outpoint: &OutPoint) -> Option<(Txid, u32)> {
+        let txid = self.spenders.get(outpoint)?;
+        let vin = self.entries.get(txid)?.spends.iter().position(|spent| spent == outpoint)?;
+        Some((*txid, vin as u32))
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &MempoolEntry> {
+        self.entries.values()
+    }
+
+    pub fn txids(&self) -> impl Iterator<Item = &Txid> {
+        self.entries.keys()
+    }
+
+    /// Adds `entry`. Tracked transactions spending the same outpoints were replaced, so
+    /// they are evicted together with their descendants. Returns the evicted txids.
+    pub fn insert(&mut self, entry: MempoolEntry) -> Vec<Txid> {
+        if self.entries.contains_key(&entry.txid) {
+            return vec![];
+        }
+        let mut replaced = Vec::new();
+        for outpoint in &entry.spends {
+            if let Some(spender) = self.spenders.get(outpoint).copied() {
+                if spender != entry.txid {
+                    replaced.extend(self.remove_with_descendants(&spender));
+                }
+            }
+        }
+        for outpoint in &entry.spends {
+            self.spenders.insert(*outpoint, entry.txid);
+        }
+        for parent in &entry.parents {
+            self.children.entry(*parent).or_default().insert(entry.txid);
+        }
+        self.entries.insert(entry.txid, entry);
+        replaced
+    }
+
+    /// Removes a single transaction. Its descendants stay, for example when it confirmed.
+    pub fn remove(&mut self, txid: &Txid) -> Option<MempoolEntry> {
+        let entry = self.entries.remove(txid)?;
+        for outpoint in &entry.spends {
+            if self.spenders.get(outpoint) == Some(txid) {
+                self.spenders.remove(outpoint);
+            }
+        }
+        for parent in &entry.parents {
+            if let Some(children) = self.children.get_mut(parent) {
+                children.remove(txid);
+                if children.is_empty() {
+                    self.children.remove(parent);
+                }
+            }
+        }
+        if let Some(children) = self.children.get(txid) {
+            for child in children {
+                if let Some(child) = self.entries.get_mut(child) {
+                    child.parents.remove(txid);
+                }
+            }
+        }
+        self.children.remove(txid);
+        Some(entry)
+    }
+
+    /// Removes a transaction and everything spending from it. Returns the removed txids.
+    pub fn remove_with_descendants(&mut self, txid: &Txid) -> Vec<Txid> {
+        let mut removed = Vec::new();
+        let mut pending: Vec<Txid> = self.descendants(txid).into_iter().collect();
+        pending.push(*txid);
+        for txid in pending {
+            if self.remove(&txid).is_some() {
+                removed.push(txid);
+            }
+        }
+        removed
+    }
+
+    /// Evicts the transactions confirmed by `block` and everything conflicting with them.
+    /// Returns the number of evicted transactions.
+    pub fn confirm_block(&mut self, block: &Block) -> usize {
+        let mut evicted = 0;
+        for tx in &block.txdata {
+            let txid = tx.txid();
+            for input in &tx.input {
+                if let Some(spender) = self.spenders.get(&input.previous_output).copied() {
+                    if spender != txid {
+                        evicted += self.remove_with_descendants(&spender).len();
+                    }
+                }
+            }
+            evicted += usize::from(self.remove(&txid).is_some());
+        }
+        evicted
+    }
+
+    /// Keeps only the transactions in `txids`, the node's current mempool.
+    pub fn retain(&mut self, txids: &BTreeSet<Txid>) -> usize {
+        let stale: Vec<_> = self
+            .entries
+            .keys()
+            .filter(|txid| !txids.contains(*txid))
+            .copied()
+            .collect();
+        for txid in &stale {
+            self.remove(txid);
+        }
+        stale.len()
+    }
+
+    /// Every tracked transaction `txid` depends on, directly or not.
+    pub fn ancestors(&self, txid: &Txid) -> BTreeSet<Txid> {
+        self.walk(txid, |entry| entry.parents.iter().copied().collect())
+    }
+
+    /// Every tracked transaction depending on `txid`, directly or not.
+    pub fn descendants(&self, txid: &Txid) -> BTreeSet<Txid> {
+        self.walk(txid, |entry| {
+            self.children
+                .get(&entry.txid)
+                .map(|children| children.iter().copied().collect())
+                .unwrap_or_default()
+        })
+    }
+
+    fn walk(&self, txid: &Txid, next: impl Fn(&MempoolEntry) -> Vec<Txid>) -> BTreeSet<Txid> {
+        let mut found = BTreeSet::new();
+        let mut pending = self.entries.get(txid).map(&next).unwrap_or_default();
+        while let Some(txid) = pending.pop() {
+            let Some(entry) = self.entries.get(&txid) else {
+                continue;
+            };
+            if found.insert(txid) {
+                pending.extend(next(entry));
+            }
+        }
+        found
+    }
+
+    pub fn summary(&self) -> MempoolResponse {
+        let mut buckets: Vec<FeeHistogramBucket> = FEE_HISTOGRAM_BUCKETS
+            .iter()
+            .enumerate()
+            .map(|(i, min_feerate)| FeeHistogramBucket {
+                min_feerate: *min_feerate,
+                max_feerate: FEE_HISTOGRAM_BUCKETS.get(i + 1).copied(),
+                count: 0,
+                vsize: 0,
+            })
+            .collect();
+        for entry in self.entries.values() {
+            let feerate = entry.feerate();
+            let index = FEE_HISTOGRAM_BUCKETS.partition_point(|min| *min <= feerate).max(1) - 1;
+            buckets[index].count += 1;
+            buckets[index].vsize += entry.vsize;
+        }
+        MempoolResponse {
+            count: self.entries.len(),
+            vsize: self.entries.values().map(|entry| entry.vsize).sum(),
+            total_fee: self.entries.values().map(|entry| entry.fee).sum(),
+            fee_histogram: buckets.into_iter().filter(|bucket| bucket.count > 0).collect(),
+        }
+    }
+}
+
+pub async fn get(state: &AppState) -> MempoolResponse {
+    info!("Get mempool summary.");
+    state.mempool.read().await.summary()
+}
+
+pub async fn list_txids(state: &AppState) -> Vec<String> {
+    info!("List mempool txids.");
+    let mempool = state.mempool.read().await;
+    mempool.txids().map(Txid::to_string).collect()
+}
+
+pub async fn projected_blocks(state: &AppState) -> Vec<ProjectedBlockResponse> {
+    info!("Get projected blocks.");
+    let template = state.template.read().await.clone();
+    template
+        .blocks
+        .iter()
+        .enumerate()
+        .map(|(index, block)| ProjectedBlockResponse {
+            index,
+            tx_count: block.txs.len(),
+            vsize: block.vsize(),
+            weight: block.weight,
+            total_fee: block.total_fee(),
+            median_feerate: block.median_feerate().unwrap_or_default(),
+            min_feerate: block.min_feerate().unwrap_or_default(),
+            max_feerate: block.max_feerate().unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// Finds the projected block an unconfirmed transaction is expected in.
+pub async fn projected_tx(state: &AppState, txid: &str) -> AppResult<ProjectedTxResponse> {
+    let txid = Txid::from_str(txid)
+        .map_err(|e| AppError::BadRequestError(format!("invalid txid {txid}: {e}")))?;
+    info!("Get projected block of transaction: {txid}.");
+    let template = state.template.read().await.clone();
+    let (block, tx) = template.position(&txid).ok_or_else(|| {
+        AppError::NotFoundError(Resource {
+            details: vec![("txid".to_string(), txid.to_string())],
+            resource_type: ResourceType::Transaction,
+        })
+    })?;
+    Ok(ProjectedTxResponse {
+        txid: txid.to_string(),
+        block,
+        effective_feerate: tx.effective_feerate,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoincore_rpc::bitcoin::hashes::Hash;
+
+    use super::*;
+
+    fn txid(n: u8) -> Txid {
+        Txid::from_byte_array([n; 32])
+    }
+
+    fn entry(n: u8, fee: u64, parents: &[u8], spends: &[(u8, u32)]) -> MempoolEntry {
+        MempoolEntry {
+            txid: txid(n),
+            fee,
+            vsize: 100,
+            weight: 400,
+            first_seen: 0,
+            parents: parents.iter().map(|n| txid(*n)).collect(),
+            spends: spends.iter().map(|(n, vout)| OutPoint::new(txid(*n), *vout)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_replacement_evicts_descendants() {
+        let mut mempool = Mempool::default();
+        mempool.insert(entry(1, 100, &[], &[(0, 0)]));
+        mempool.insert(entry(2, 200, &[1], &[(1, 0)]));
+        mempool.insert(entry(3, 300, &[2], &[(2, 0)]));
+        assert_eq!(mempool.ancestors(&txid(3)), [txid(1), txid(2)].into());
+        assert_eq!(mempool.descendants(&txid(1)), [txid(2), txid(3)].into());
+        assert_eq!(mempool.spender(&OutPoint::new(txid(1), 0)), Some((txid(2), 0)));
+        assert_eq!(mempool.spender(&OutPoint::new(txid(1), 1)), None);
+
+        let mut replaced = mempool.insert(entry(4, 1000, &[], &[(0, 0)]));
+        replaced.sort();
+        assert_eq!(replaced, vec![txid(1), txid(2), txid(3)]);
+        assert_eq!(mempool.txids().collect::<Vec<_>>(), vec![&txid(4)]);
+    }
+
+    #[test]
+    fn test_confirmation_keeps_children() {
+        let mut mempool = Mempool::default();
+        mempool.insert(entry(2, 200, &[1], &[(1, 0)]));
+        // The parent arrives after its child, as it can while seeding.
+        mempool.insert(entry(1, 100, &[], &[(0, 0)]));
+        assert_eq!(mempool.descendants(&txid(1)), [txid(2)].into());
+
+        mempool.remove(&txid(1));
+        assert!(mempool.get(&txid(2)).unwrap().parents.is_empty());
+        assert_eq!(mempool.ancestors(&txid(2)), BTreeSet::new());
+    }
+
+    #[test]
+    fn test_summary_histogram() {
+        let mut mempool = Mempool::default();
+        mempool.insert(entry(1, 150, &[], &[]));
+        mempool.insert(entry(2, 190, &[], &[]));
+        mempool.insert(entry(3, 5000, &[], &[]));
+        let summary = mempool.summary();
+        assert_eq!((summary.count, summary.vsize, summary.total_fee), (3, 300, 5340));
+        let buckets: Vec<_> = summary
+            .fee_histogram
+            .iter()
+            .map(|bucket| (bucket.min_feerate, bucket.count))
+            .collect();
+        assert_eq!(buckets, vec![(1.0, 2), (50.0, 1)]);
+    }
+}
@@ -0,0 +1,169 @@
+use std::str::FromStr;
+
+use bitcoincore_rpc::bitcoin::consensus::encode::{deserialize, serialize};
+use bitcoincore_rpc::bitcoin::hashes::{Hash, HashEngine};
+use bitcoincore_rpc::bitcoin::hex::DisplayHex;
+use bitcoincore_rpc::bitcoin::merkle_tree::{self, MerkleBlock, PartialMerkleTree};
+use bitcoincore_rpc::bitcoin::{TxMerkleNode, Txid};
+use tracing::info;
+
+use crate::dto::request::{MerkleProofFormat, MerkleProofQueryParam};
+use crate::dto::response::{ElectrumMerkleProofResponse, MerkleProofResponse, TxOutProofResponse};
+use crate::error::{AppError, AppResult, Resource, ResourceType};
+use crate::repo;
+use crate::server::state::AppState;
+use crate::service::header::stored_header;
+
+/// Proves that a confirmed transaction is part of its block, from the indexed transaction
+/// order and the stored header.
+pub async fn proof(state: &AppState, txid: &str, param: MerkleProofQueryParam) -> AppResult<MerkleProofResponse> {
+    let txid = Txid::from_str(txid)
+        .map_err(|e| AppError::BadRequestError(format!("invalid txid {txid}: {e}")))?;
+    info!("Get merkle proof of transaction: {txid}.");
+    let tx = repo::transaction::find_by_txid(&*state.db, &txid.to_string())
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFoundError(Resource {
+                details: vec![("txid".to_string(), txid.to_string())],
+                resource_type: ResourceType::Transaction,
+            })
+        })?;
+    let not_available = || {
+        AppError::NotAvailableError(Resource {
+            details: vec![("height".to_string(), tx.block_height.to_string())],
+            resource_type: ResourceType::Block,
+        })
+    };
+    let block = repo::block::find_by_height(&*state.db, tx.block_height)
+        .await?
+        .ok_or_else(not_available)?;
+    // Blocks stored before headers were kept get theirs from the node on startup.
+    let header = stored_header(&block).ok_or_else(not_available)?;
+    let txids = repo::transaction::find_txids_by_height(&*state.db, block.height)
+        .await?
+        .iter()
+        .map(|txid| Txid::from_str(txid).map_err(|e| AppError::UnknownError(e.into())))
+        .collect::<AppResult<Vec<_>>>()?;
+    // The duplicate BIP30 coinbases are only stored with their first block.
+    if txids.len() != block.tx_count as usize {
+        return Err(not_available());
+    }
+    let root = merkle_tree::calculate_root(txids.iter().map(|txid| node(*txid)));
+    if root != Some(header.merkle_root) {
+        return Err(AppError::UnknownError(anyhow::anyhow!(
+            "the indexed transactions of block {} do not match its merkle root",
+            block.height
+        )));
+    }
+    let pos = tx.block_index as usize;
+    let resp = match param.format {
+        MerkleProofFormat::Electrum => MerkleProofResponse::Electrum(ElectrumMerkleProofResponse {
+            block_height: block.height,
+            block_hash: block.hash,
+            pos: pos as u32,
+            merkle: branch(&txids, pos).iter().map(ToString::to_string).collect(),
+            header: serialize(&header).to_lower_hex_string(),
+        }),
+        MerkleProofFormat::Txoutproof => {
+            let matches: Vec<_> = (0..txids.len()).map(|index| index == pos).collect();
+            let merkle_block = MerkleBlock {
+                header,
+                txn: PartialMerkleTree::from_txids(&txids, &matches),
+            };
+            MerkleProofResponse::TxOutProof(TxOutProofResponse {
+                block_height: block.height,
+                block_hash: block.hash,
+                proof: serialize(&merkle_block).to_lower_hex_string(),
+            })
+        }
+    };
+    Ok(resp)
+}
+
+/// The sibling hashes from the transaction at `pos` up to the root, the last hash of an odd
+/// level paired with itself.
+pub fn branch(txids: &[Txid], pos: usize) -> Vec<TxMerkleNode> {
+    let mut level: Vec<_> = txids.iter().map(|txid| node(*txid)).collect();
+    let mut index = pos;
+    let mut branch = Vec::new();
+    while level.len() > 1 {
+        branch.push(*level.get(index ^ 1).unwrap_or(&level[index]));
+        level = level
+            .chunks(2)
+            .map(|pair| parent(pair[0], *pair.get(1).unwrap_or(&pair[0])))
+            .collect();
+        index /= 2;
+    }
+    branch
+}
+
+/// Whether `branch` leads from `txid` at `pos` to `merkle_root`.
+pub fn verify_branch(txid: Txid, pos: usize, branch: &[TxMerkleNode], merkle_root: TxMerkleNode) -> bool {
+    let mut hash = node(txid);
+    let mut index = pos;
+    for sibling in branch {
+        hash = match index % 2 {
+            0 => parent(hash, *sibling),
+            _ => parent(*sibling, hash),
+        };
+        index /= 2;
+    }
+    index == 0 && hash == merkle_root
+}
+
+/// The transactions a serialized merkle block proves, when its tree matches its header.
+pub fn verify_txoutproof(proof: &[u8]) -> Option<Vec<Txid>> {
+    let merkle_block: MerkleBlock = deserialize(proof).ok()?;
+    let mut matches = Vec::new();
+    let mut indexes = Vec::new();
+    merkle_block.extract_matches(&mut matches, &mut indexes).ok()?;
+    Some(matches)
+}
+
+fn node(txid: Txid) -> TxMerkleNode {
+    TxMerkleNode::from_byte_array(txid.to_byte_array())
+}
+
+fn parent(left: TxMerkleNode, right: TxMerkleNode) -> TxMerkleNode {
+    let mut engine = TxMerkleNode::engine();
+    engine.input(left.as_byte_array());
+    engine.input(right.as_byte_array());
+    TxMerkleNode::from_engine(engine)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoincore_rpc::bitcoin::blockdata::constants::genesis_block;
+    use bitcoincore_rpc::bitcoin::Network;
+
+    use super::*;
+
+    #[test]
+    fn test_proofs_round_trip() {
+        for count in 1..=9u8 {
+            let txids: Vec<_> = (0..count).map(|byte| Txid::from_byte_array([byte; 32])).collect();
+            let root = merkle_tree::calculate_root(txids.iter().map(|txid| node(*txid))).unwrap();
+            let header = bitcoincore_rpc::bitcoin::block::Header {
+                merkle_root: root,
+                ..genesis_block(Network::Bitcoin).header
+            };
+            for (pos, txid) in txids.iter().enumerate() {
+                let branch = branch(&txids, pos);
+                assert!(verify_branch(*txid, pos, &branch, root));
+                assert!(!verify_branch(Txid::from_byte_array([0xff; 32]), pos, &branch, root));
+                assert!(!verify_branch(*txid, pos + (2 << branch.len()), &branch, root));
+
+                let matches: Vec<_> = (0..txids.len()).map(|index| index == pos).collect();
+                let merkle_block = MerkleBlock {
+                    header,
+                    txn: PartialMerkleTree::from_txids(&txids, &matches),
+                };
+                assert_eq!(verify_txoutproof(&serialize(&merkle_block)), Some(vec![*txid]));
+            }
+        }
+
+        let genesis = genesis_block(Network::Bitcoin);
+        let coinbase = genesis.txdata[0].txid();
+        assert!(verify_branch(coinbase, 0, &branch(&[coinbase], 0), genesis.header.merkle_root));
+    }
+}
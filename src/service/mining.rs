@@ -0,0 +1,219 @@
+use bitcoincore_rpc::bitcoin::consensus::Params;
+use bitcoincore_rpc::bitcoin::{CompactTarget, Target};
+use chrono::{Duration, NaiveDateTime};
+use tracing::info;
+
+use crate::dto::request::{MiningPeriod, MiningPeriodQueryParam};
+use crate::dto::response::{DifficultyAdjustmentResponse, HashratePoint, HashrateResponse};
+use crate::error::{AppError, AppResult, Resource, ResourceType};
+use crate::repo;
+use crate::server::state::AppState;
+
+/// A retarget never moves the difficulty by more than this factor either way.
+const MAX_ADJUSTMENT_FACTOR: f64 = 4.0;
+
+/// Progress of the current difficulty epoch and the change expected at its end if blocks
+/// keep coming at the pace of the epoch so far.
+pub async fn difficulty_adjustment(state: &AppState) -> AppResult<DifficultyAdjustmentResponse> {
+    info!("Get difficulty adjustment.");
+    let params = Params::new(state.config.bitcoin.network);
+    let interval = params.difficulty_adjustment_interval() as i32;
+    let tip = repo::block::find_tip(&*state.db)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFoundError(Resource {
+                details: vec![],
+                resource_type: ResourceType::Block,
+            })
+        })?;
+    let epoch_start_height = (tip.height + 1) / interval * interval;
+    let epoch_start = match epoch_start_height {
+        height if height > tip.height => None,
+        height => repo::block::find_by_height(&*state.db, height).await?,
+    };
+    let previous = match epoch_start {
+        Some(_) if epoch_start_height > 0 => repo::block::find_by_height(&*state.db, epoch_start_height - 1).await?,
+        _ => None,
+    };
+    let epoch = Epoch {
+        interval,
+        target_spacing: params.pow_target_spacing as f64,
+        start_height: epoch_start_height,
+        start_time: epoch_start.as_ref().map(|block| block.block_created_at),
+        tip_height: tip.height,
+        tip_time: tip.block_created_at,
+    };
+    let average_block_time = epoch.average_block_time();
+    Ok(DifficultyAdjustmentResponse {
+        current_difficulty: tip.difficulty,
+        epoch_start_height,
+        retarget_height: epoch_start_height + interval,
+        progress_percent: epoch.mined() as f64 / interval as f64 * 100.0,
+        remaining_blocks: epoch.remaining(),
+        average_block_time,
+        estimated_retarget_date: tip.block_created_at
+            + Duration::milliseconds((average_block_time * epoch.remaining() as f64 * 1000.0) as i64),
+        // Regtest never retargets.
+        difficulty_change_percent: match params.no_pow_retargeting {
+            true => 0.0,
+            false => epoch.expected_change_percent(),
+        },
+        previous_change_percent: previous
+            .zip(epoch_start)
+            .filter(|(previous, _)| previous.difficulty > 0.0)
+            .map(|(previous, start)| (start.difficulty / previous.difficulty - 1.0) * 100.0),
+    })
+}
+
+/// Network hashrate over the requested period, counted back from the last indexed block.
+pub async fn hashrate(state: &AppState, param: MiningPeriodQueryParam) -> AppResult<HashrateResponse> {
+    info!("Get hashrate for period {:?}.", param.period);
+    let Some(tip) = repo::block::find_tip(&*state.db).await? else {
+        return Ok(HashrateResponse {
+            period: param.period,
+            current_hashrate: 0.0,
+            current_difficulty: 0.0,
+            hashrates: vec![],
+        });
+    };
+    let since = param.period.duration().map(|duration| tip.block_created_at - duration);
+    let (unit, width) = bucket(param.period);
+    let hashrates: Vec<_> = repo::block::find_hashrate_buckets(&*state.db, unit, since)
+        .await?
+        .into_iter()
+        .map(|bucket| {
+            // The first and the last bucket are cut by the period and by the tip.
+            let start = since.map_or(bucket.bucket_start, |since| bucket.bucket_start.max(since));
+            let end = (bucket.bucket_start + width).min(tip.block_created_at);
+            HashratePoint {
+                timestamp: bucket.bucket_start,
+                hashrate: estimate_hashrate(bucket.block_count, bucket.average_difficulty, start, end),
+                difficulty: bucket.average_difficulty,
+                block_count: bucket.block_count,
+            }
+        })
+        .collect();
+    Ok(HashrateResponse {
+        period: param.period,
+        current_hashrate: hashrates.last().map_or(0.0, |point| point.hashrate),
+        current_difficulty: tip.difficulty,
+        hashrates,
+    })
+}
+
+/// Recomputes the difficulty stored while the column was an integer from the `bits` of
+/// every block, which also holds for the min-difficulty blocks of testnet.
+pub async fn repair_difficulty(state: &AppState) -> AppResult {
+    let truncated = repo::block::find_truncated_bits(&*state.db).await?;
+    if truncated.is_empty() {
+        return Ok(());
+    }
+    info!("Recomputing the difficulty of {} targets.", truncated.len());
+    for bits in truncated {
+        repo::block::update_difficulty(&*state.db, bits, bits_difficulty(bits)).await?;
+    }
+    info!("Stored difficulties repaired.");
+    Ok(())
+}
+
+/// The difficulty of a block from its stored `bits`, as bitcoind reports it.
+fn bits_difficulty(bits: i64) -> f64 {
+    Target::from_compact(CompactTarget::from_consensus(bits as u32)).difficulty_float()
+}
+
+/// The `date_trunc` unit and width of the points of a hashrate series.
+fn bucket(period: MiningPeriod) -> (&'static str, Duration) {
+    match period {
+        MiningPeriod::Day | MiningPeriod::ThreeDays => ("hour", Duration::hours(1)),
+        MiningPeriod::Year | MiningPeriod::All => ("week", Duration::weeks(1)),
+        _ => ("day", Duration::days(1)),
+    }
+}
+
+/// Every block takes `difficulty * 2^32` hashes on average.
+fn estimate_hashrate(block_count: i64, difficulty: f64, start: NaiveDateTime, end: NaiveDateTime) -> f64 {
+    let seconds = (end - start).num_seconds();
+    if seconds <= 0 {
+        return 0.0;
+    }
+    block_count as f64 * difficulty * 2f64.powi(32) / seconds as f64
+}
+
+/// The current difficulty epoch as far as it is indexed.
+struct Epoch {
+    interval: i32,
+    target_spacing: f64,
+    start_height: i32,
+    /// Time of the first block of the epoch, unless it is not mined yet.
+    start_time: Option<NaiveDateTime>,
+    tip_height: i32,
+    tip_time: NaiveDateTime,
+}
+
+impl Epoch {
+    fn mined(&self) -> i32 {
+        self.tip_height + 1 - self.start_height
+    }
+
+    fn remaining(&self) -> i32 {
+        self.interval - self.mined()
+    }
+
+    /// Seconds between the blocks of the epoch, the target spacing until two are mined.
+    fn average_block_time(&self) -> f64 {
+        match self.start_time {
+            Some(start) if self.mined() > 1 && self.tip_time > start => {
+                (self.tip_time - start).num_seconds() as f64 / (self.mined() - 1) as f64
+            }
+            _ => self.target_spacing,
+        }
+    }
+
+    fn expected_change_percent(&self) -> f64 {
+        let factor = (self.target_spacing / self.average_block_time())
+            .clamp(1.0 / MAX_ADJUSTMENT_FACTOR, MAX_ADJUSTMENT_FACTOR);
+        (factor - 1.0) * 100.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+
+    fn epoch(mined: i32, seconds: i64) -> Epoch {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap().naive_utc();
+        Epoch {
+            interval: 2016,
+            target_spacing: 600.0,
+            start_height: 838_656,
+            start_time: Some(start),
+            tip_height: 838_656 + mined - 1,
+            tip_time: start + Duration::seconds(seconds),
+        }
+    }
+
+    #[test]
+    fn test_expected_change() {
+        // 100 blocks in 99 intervals of 500 seconds.
+        let fast = epoch(100, 99 * 500);
+        assert_eq!(fast.remaining(), 1916);
+        assert_eq!(fast.average_block_time(), 500.0);
+        assert!((fast.expected_change_percent() - 20.0).abs() < 1e-9);
+
+        let slow = epoch(10, 9 * 6000);
+        assert!((slow.expected_change_percent() + 75.0).abs() < 1e-9);
+
+        let first = epoch(1, 0);
+        assert_eq!(first.average_block_time(), 600.0);
+        assert_eq!(first.expected_change_percent(), 0.0);
+    }
+
+    #[test]
+    fn test_bits_difficulty() {
+        assert_eq!(bits_difficulty(0x1d00ffff), 1.0);
+        // Mainnet block 32256, the first retarget.
+        assert!((bits_difficulty(0x1d00d86a) - 1.182899534312841).abs() < 1e-12);
+    }
+}
@@ -1,2 +1,16 @@
 
-pub mod prefetcher;
\ No newline at end of file
+pub mod prefetcher;
+pub mod ingest;
+pub mod bulk;
+pub mod script;
+pub mod address;
+pub mod block;
+pub mod transaction;
+pub mod mempool;
+pub mod template;
+pub mod fee;
+pub mod pool;
+pub mod coinbase;
+pub mod mining;
+pub mod header;
+pub mod merkle;
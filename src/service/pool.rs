@@ -0,0 +1,280 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use bitcoincore_rpc::bitcoin::hex::FromHex;
+use bitcoincore_rpc::bitcoin::{Block, Network};
+use serde::Deserialize;
+use sea_orm::TransactionTrait;
+use tracing::info;
+
+use crate::configure::get_setting_dir;
+use crate::constant::{BACKFILL_BATCH_BLOCKS, UNKNOWN_POOL_ID};
+use crate::dto::request::MiningPeriodQueryParam;
+use crate::dto::response::{MiningPoolResponse, MiningPoolsResponse};
+use crate::entity::pool;
+use crate::error::{AppError, AppResult};
+use crate::repo;
+use crate::server::state::AppState;
+use crate::service::script::script_address;
+
+/// One entry of the pools definition file.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct PoolDefinition {
+    pub id: i32,
+    pub name: String,
+    #[serde(default)]
+    pub link: String,
+    /// Strings the pool writes in its coinbase scripts.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Addresses the pool pays its coinbase outputs to.
+    #[serde(default)]
+    pub addresses: Vec<String>,
+}
+
+/// Matches blocks to mining pools. Payout addresses are checked first, coinbase tags
+/// second, in the order of the definitions. The bundled definitions list no addresses,
+/// they match on coinbase tags only.
+#[derive(Debug, Default)]
+pub struct Pools {
+    definitions: Vec<PoolDefinition>,
+    by_address: HashMap<String, i32>,
+    tags: Vec<(Vec<u8>, i32)>,
+}
+
+impl Pools {
+    pub fn new(mut definitions: Vec<PoolDefinition>) -> Self {
+        definitions.sort_by_key(|pool| pool.id);
+        let mut by_address = HashMap::new();
+        let mut tags = Vec::new();
+        for pool in &definitions {
+            for address in &pool.addresses {
+                by_address.entry(address.clone()).or_insert(pool.id);
+            }
+            tags.extend(pool.tags.iter().map(|tag| (tag.as_bytes().to_vec(), pool.id)));
+        }
+        Self {
+            definitions,
+            by_address,
+            tags,
+        }
+    }
+
+    /// Reads a JSON array of [`PoolDefinition`]. Relative paths are resolved against the
+    /// settings directory.
+    pub fn load(path: impl AsRef<Path>) -> AppResult<Self> {
+        let path = match path.as_ref() {
+            path if path.is_absolute() => path.to_path_buf(),
+            path => get_setting_dir()
+                .map_err(|e| AppError::UnknownError(e.into()))?
+                .join(path),
+        };
+        let definitions: Vec<PoolDefinition> = serde_json::from_slice(&std::fs::read(&path)?)
+            .map_err(|e| AppError::UnknownError(anyhow::anyhow!("invalid pools file {}: {e}", path.display())))?;
+        check_definitions(&definitions)
+            .map_err(|e| AppError::ConfigError(config::ConfigError::Message(format!("{}: {e}", path.display()))))?;
+        info!("Loaded {} pool definitions from {}.", definitions.len(), path.display());
+        Ok(Self::new(definitions))
+    }
+
+    pub fn definitions(&self) -> &[PoolDefinition] {
+        &self.definitions
+    }
+
+    pub fn get(&self, id: i32) -> Option<&PoolDefinition> {
+        self.definitions
+            .binary_search_by_key(&id, |pool| pool.id)
+            .ok()
+            .map(|index| &self.definitions[index])
+    }
+
+    /// The pool that mined `block` on `network`, or [`UNKNOWN_POOL_ID`].
+    pub fn identify(&self, block: &Block, network: Network) -> i32 {
+        let Some(coinbase) = block.coinbase() else {
+            return UNKNOWN_POOL_ID;
+        };
+        let script_sig = coinbase
+            .input
+            .first()
+            .map(|input| input.script_sig.as_bytes())
+            .unwrap_or_default();
+        let addresses = coinbase
+            .output
+            .iter()
+            .filter_map(|output| script_address(&output.script_pubkey, network));
+        self.identify_coinbase(script_sig, addresses)
+    }
+
+    pub fn identify_coinbase<S>(&self, script_sig: &[u8], addresses: impl IntoIterator<Item = S>) -> i32
+    where
+        S: AsRef<str>,
+    {
+        addresses
+            .into_iter()
+            .find_map(|address| self.by_address.get(address.as_ref()).copied())
+            .or_else(|| {
+                self.tags
+                    .iter()
+                    .find(|(tag, _)| script_sig.windows(tag.len()).any(|window| window == tag.as_slice()))
+                    .map(|(_, id)| *id)
+            })
+            .unwrap_or(UNKNOWN_POOL_ID)
+    }
+
+    fn models(&self) -> Vec<pool::Model> {
+        self.definitions
+            .iter()
+            .map(|pool| pool::Model {
+                id: pool.id,
+                name: pool.name.clone(),
+                link: pool.link.clone(),
+                tags: serde_json::json!(pool.tags),
+                addresses: serde_json::json!(pool.addresses),
+            })
+            .collect()
+    }
+}
+
+/// Rejects definitions that would match every block, like an empty tag, and ids that are
+/// reused or taken by [`UNKNOWN_POOL_ID`].
+fn check_definitions(definitions: &[PoolDefinition]) -> Result<(), String> {
+    let mut ids = HashSet::new();
+    for pool in definitions {
+        if pool.id == UNKNOWN_POOL_ID {
+            return Err(format!("pool {} uses the id of unknown pools", pool.name));
+        }
+        if !ids.insert(pool.id) {
+            return Err(format!("pool id {} is used twice", pool.id));
+        }
+        if pool.tags.iter().any(String::is_empty) {
+            return Err(format!("pool {} has an empty tag", pool.id));
+        }
+        if pool.addresses.iter().any(String::is_empty) {
+            return Err(format!("pool {} has an empty address", pool.id));
+        }
+    }
+    Ok(())
+}
+
+/// Stores the loaded pool definitions. When they differ from the stored ones every block
+/// is tagged again first, so an interrupted run is picked up on the next start.
+pub async fn sync_definitions(state: &AppState) -> AppResult {
+    let models = state.pools.models();
+    if repo::pool::find_all(&*state.db).await? == models {
+        return Ok(());
+    }
+    info!("Pool definitions changed, tagging the stored blocks again.");
+    let tagged = retag(state).await?;
+    let tx = state.db.begin().await?;
+    repo::pool::replace_all(&tx, models).await?;
+    tx.commit().await?;
+    info!("Pool definitions updated, {tagged} blocks changed pool.");
+    Ok(())
+}
+
+async fn retag(state: &AppState) -> AppResult<usize> {
+    let Some(tip) = repo::block::find_max_height(&*state.db).await? else {
+        return Ok(0);
+    };
+    let mut tagged = 0;
+    for from in (0..=tip).step_by(BACKFILL_BATCH_BLOCKS as usize) {
+        let to = from.saturating_add(BACKFILL_BATCH_BLOCKS - 1).min(tip);
+        let mut changed = Vec::new();
+        for row in repo::block::find_coinbases(&*state.db, from, to).await? {
+            let script_sig = row
+                .coinbase_raw
+                .as_deref()
+                .and_then(|raw| Vec::<u8>::from_hex(raw).ok())
+                .unwrap_or_default();
+            let addresses = row.addresses.as_deref().unwrap_or_default().split(',');
+            let pool_id = state.pools.identify_coinbase(&script_sig, addresses);
+            if row.pool_id != Some(pool_id) {
+                changed.push((row.height, pool_id));
+            }
+        }
+        repo::block::update_pool_ids(&*state.db, &changed).await?;
+        tagged += changed.len();
+    }
+    Ok(tagged)
+}
+
+/// Block share and estimated hashrate of every pool over the requested period, counted
+/// back from the last indexed block.
+pub async fn list_stats(state: &AppState, param: MiningPeriodQueryParam) -> AppResult<MiningPoolsResponse> {
+    info!("Get mining pools for period {:?}.", param.period);
+    let since = match (repo::block::find_tip(&*state.db).await?, param.period.duration()) {
+        (Some(tip), Some(duration)) => Some(tip.block_created_at - duration),
+        _ => None,
+    };
+    let window = repo::block::find_window(&*state.db, since).await?;
+    // Every block takes `difficulty * 2^32` hashes on average. The time between the first
+    // and the last block covers all blocks but the first.
+    let hashrate = match (window.total_difficulty, window.first_block_at, window.last_block_at) {
+        (Some(difficulty), Some(first), Some(last)) if last > first => {
+            let intervals = (window.block_count - 1) as f64 / window.block_count as f64;
+            difficulty * intervals * 2f64.powi(32) / (last - first).num_seconds() as f64
+        }
+        _ => 0.0,
+    };
+    let mut pools: Vec<_> = repo::block::count_by_pool(&*state.db, since)
+        .await?
+        .into_iter()
+        .map(|(pool_id, block_count)| {
+            let id = pool_id.unwrap_or(UNKNOWN_POOL_ID);
+            let pool = state.pools.get(id);
+            let share = block_count as f64 / window.block_count.max(1) as f64;
+            MiningPoolResponse {
+                id,
+                name: pool.map_or_else(|| "Unknown".to_string(), |pool| pool.name.clone()),
+                link: pool.map(|pool| pool.link.clone()).unwrap_or_default(),
+                block_count,
+                share,
+                hashrate: hashrate * share,
+            }
+        })
+        .collect();
+    pools.sort_by(|a, b| b.block_count.cmp(&a.block_count).then(a.id.cmp(&b.id)));
+    Ok(MiningPoolsResponse {
+        period: param.period,
+        block_count: window.block_count,
+        hashrate,
+        pools,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(id: i32, tags: &[&str], addresses: &[&str]) -> PoolDefinition {
+        PoolDefinition {
+            id,
+            name: format!("pool {id}"),
+            link: String::new(),
+            tags: tags.iter().map(ToString::to_string).collect(),
+            addresses: addresses.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn test_identify_prefers_payout_address() {
+        let pools = Pools::new(vec![
+            definition(2, &["/Second/"], &["bc1qsecond"]),
+            definition(1, &["/First/"], &[]),
+        ]);
+        let script_sig = b"\x03\x10\x20\x30/First/mined";
+        assert_eq!(pools.identify_coinbase(script_sig, ["bc1qother"]), 1);
+        assert_eq!(pools.identify_coinbase(script_sig, ["bc1qother", "bc1qsecond"]), 2);
+        assert_eq!(pools.identify_coinbase(b"\x03\x10\x20\x30", [""]), UNKNOWN_POOL_ID);
+        assert_eq!(pools.get(2).map(|pool| pool.name.as_str()), Some("pool 2"));
+    }
+
+    #[test]
+    fn test_rejects_empty_tags_and_addresses() {
+        assert!(check_definitions(&[definition(1, &["/First/"], &["bc1qfirst"])]).is_ok());
+        assert!(check_definitions(&[definition(1, &["/First/", ""], &[])]).is_err());
+        assert!(check_definitions(&[definition(1, &[], &[""])]).is_err());
+        assert!(check_definitions(&[definition(1, &["/First/"], &[]), definition(1, &["/Second/"], &[])]).is_err());
+        assert!(check_definitions(&[definition(UNKNOWN_POOL_ID, &["/First/"], &[])]).is_err());
+    }
+}
@@ -0,0 +1,194 @@
+use std::future::Future;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+use bitcoincore_rpc::bitcoin::Block;
+use futures::{stream, StreamExt};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::client::source::BlockSource;
+use crate::configure::bitcoin::{BitcoinConfig, BlockFeesSource};
+use crate::constant::BlockHeight;
+use crate::error::{AppError, AppResult, Resource, ResourceType};
+use crate::service::ingest::BlockFees;
+
+/// A block fetched ahead of the writer together with the fee statistics it is indexed with.
+pub struct PrefetchedBlock {
+    pub height: BlockHeight,
+    pub block: Block,
+    /// `None` when the statistics are computed from the prevouts while indexing.
+    pub fees: Option<BlockFees>,
+}
+
+/// Fetches a range of blocks ahead of the writer and yields them in height order.
+///
+/// At most `prefetch_concurrency` requests are in flight and at most `prefetch_depth`
+/// decoded blocks wait for the writer, so memory stays bounded however far behind the
+/// index is. Fetching stops after the first error, which is yielded in place of the block.
+pub struct Prefetcher {
+    blocks: mpsc::Receiver<AppResult<PrefetchedBlock>>,
+    task: JoinHandle<()>,
+}
+
+impl Prefetcher {
+    pub fn spawn(
+        source: Arc<dyn BlockSource>,
+        range: RangeInclusive<BlockHeight>,
+        config: &BitcoinConfig,
+    ) -> Self {
+        let fees = config.block_fees;
+        let (blocks, task) = ordered(
+            range,
+            config.prefetch_concurrency,
+            config.prefetch_depth,
+            move |height| fetch(source.clone(), height, fees),
+        );
+        Self { blocks, task }
+    }
+
+    /// Waits for the next block in height order, or `None` once the range is exhausted.
+    pub async fn next(&mut self) -> Option<AppResult<PrefetchedBlock>> {
+        self.blocks.recv().await
+    }
+}
+
+impl Drop for Prefetcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Runs `fetch` for every height with bounded concurrency and sends the results in order
+/// through a channel of `depth` slots. A full channel pauses fetching until the receiver
+/// catches up.
+fn ordered<T, F, Fut>(
+    range: RangeInclusive<BlockHeight>,
+    concurrency: usize,
+    depth: usize,
+    fetch: F,
+) -> (mpsc::Receiver<AppResult<T>>, JoinHandle<()>)
+where
+    T: Send + 'static,
+    F: Fn(BlockHeight) -> Fut + Send + 'static,
+    Fut: Future<Output = AppResult<T>> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel(depth.max(1));
+    let task = tokio::spawn(async move {
+        let mut fetched = stream::iter(range).map(fetch).buffered(concurrency.max(1));
+        while let Some(result) = fetched.next().await {
+            let failed = result.is_err();
+            if sender.send(result).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+    (receiver, task)
+}
+
+async fn fetch(
+    source: Arc<dyn BlockSource>,
+    height: BlockHeight,
+    fees: BlockFeesSource,
+) -> AppResult<PrefetchedBlock> {
+    let block = source.block_at(height).await?.ok_or_else(|| {
+        AppError::NotFoundError(Resource {
+            details: vec![("height".to_string(), height.to_string())],
+            resource_type: ResourceType::Block,
+        })
+    })?;
+    // A coinbase-only block pays no fees, so there is nothing to ask the node for.
+    let fees = match fees {
+        BlockFeesSource::Rpc if block.txdata.len() > 1 => Some(source.block_fees(height).await?),
+        BlockFeesSource::Rpc => Some(BlockFees::default()),
+        BlockFeesSource::Index => None,
+    };
+    Ok(PrefetchedBlock {
+        height,
+        block,
+        fees,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use bitcoincore_rpc::bitcoin::{Amount, OutPoint, ScriptBuf, TxOut};
+
+    use super::*;
+    use crate::client::mock::{self, MockBitcoind, RPC_INVALID_PARAMETER};
+
+    #[tokio::test]
+    async fn test_ordered_yields_heights_in_order_with_bounded_concurrency() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let (counter, max) = (in_flight.clone(), peak.clone());
+        let (mut blocks, _task) = ordered(0..=49, 4, 2, move |height| {
+            let (counter, max) = (counter.clone(), max.clone());
+            async move {
+                let now = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                max.fetch_max(now, Ordering::SeqCst);
+                // Later heights finish first to make sure the output is reordered.
+                tokio::time::sleep(Duration::from_millis(u64::from(50 - height) % 7)).await;
+                counter.fetch_sub(1, Ordering::SeqCst);
+                Ok(height)
+            }
+        });
+        let mut heights = Vec::new();
+        while let Some(height) = blocks.recv().await {
+            heights.push(height.unwrap());
+        }
+        assert_eq!(heights, (0..=49).collect::<Vec<_>>());
+        assert!(peak.load(Ordering::SeqCst) <= 4);
+    }
+
+    #[tokio::test]
+    async fn test_ordered_stops_after_error() {
+        let (mut blocks, _task) = ordered(0..=9, 2, 2, |height| async move {
+            if height == 3 {
+                Err(AppError::BadRequestError("boom".to_string()))
+            } else {
+                Ok(height)
+            }
+        });
+        let mut results = Vec::new();
+        while let Some(result) = blocks.recv().await {
+            results.push(result.is_ok());
+        }
+        assert_eq!(results, vec![true, true, true, false]);
+    }
+
+    #[tokio::test]
+    async fn test_prefetches_from_node() {
+        let mock = MockBitcoind::from_fixture("mainnet").await;
+        let funding = mock.mine(vec![]);
+        let tx = mock::spend(
+            &[OutPoint::new(funding.txdata[0].txid(), 0)],
+            vec![TxOut {
+                value: Amount::from_sat(5_000_000_000 - 20_000),
+                script_pubkey: ScriptBuf::new(),
+            }],
+        );
+        mock.add_to_mempool(tx);
+        mock.mine_mempool();
+        mock.delay("getblock", Duration::from_millis(20));
+
+        let config = mock.config();
+        let mut prefetcher = Prefetcher::spawn(mock.source().await, 0..=4, &config);
+        let mut fetched = Vec::new();
+        while let Some(block) = prefetcher.next().await {
+            let block = block.unwrap();
+            assert_eq!(block.block, mock.block(block.height));
+            fetched.push((block.height, block.fees.unwrap().total));
+        }
+        assert_eq!(fetched, vec![(0, 0.0), (1, 0.0), (2, 0.0), (3, 0.0), (4, 20_000.0)]);
+        assert_eq!(mock.calls("getblockstats"), 1);
+
+        mock.fail("getblockhash", RPC_INVALID_PARAMETER, "Block height out of range", 5);
+        let mut prefetcher = Prefetcher::spawn(mock.source().await, 0..=4, &config);
+        assert!(prefetcher.next().await.unwrap().is_err());
+        assert!(prefetcher.next().await.is_none());
+    }
+}
@@ -0,0 +1,98 @@
+use std::str::FromStr;
+
+use bitcoincore_rpc::bitcoin::{Address, Network, Script};
+
+use crate::error::{AppError, AppResult};
+
+/// Output script templates, named the way Bitcoin Core reports them in `scriptPubKey.type`.
+#[derive(Debug, strum::Display, strum::EnumString, Copy, Clone, PartialEq, Eq)]
+pub enum ScriptType {
+    #[strum(serialize = "pubkey")]
+    P2pk,
+    #[strum(serialize = "pubkeyhash")]
+    P2pkh,
+    #[strum(serialize = "scripthash")]
+    P2sh,
+    #[strum(serialize = "multisig")]
+    Multisig,
+    #[strum(serialize = "witness_v0_keyhash")]
+    P2wpkh,
+    #[strum(serialize = "witness_v0_scripthash")]
+    P2wsh,
+    #[strum(serialize = "witness_v1_taproot")]
+    P2tr,
+    #[strum(serialize = "witness_unknown")]
+    WitnessUnknown,
+    #[strum(serialize = "nulldata")]
+    NullData,
+    #[strum(serialize = "nonstandard")]
+    NonStandard,
+}
+
+impl ScriptType {
+    pub fn of(script: &Script) -> Self {
+        if script.is_p2pk() {
+            Self::P2pk
+        } else if script.is_p2pkh() {
+            Self::P2pkh
+        } else if script.is_p2sh() {
+            Self::P2sh
+        } else if script.is_multisig() {
+            Self::Multisig
+        } else if script.is_p2wpkh() {
+            Self::P2wpkh
+        } else if script.is_p2wsh() {
+            Self::P2wsh
+        } else if script.is_p2tr() {
+            Self::P2tr
+        } else if script.is_witness_program() {
+            Self::WitnessUnknown
+        } else if script.is_op_return() {
+            Self::NullData
+        } else {
+            Self::NonStandard
+        }
+    }
+}
+
+/// Renders the address paying to `script` on `network`, if the script has an address form.
+pub fn script_address(script: &Script, network: Network) -> Option<String> {
+    Address::from_script(script, network)
+        .ok()
+        .map(|address| address.to_string())
+}
+
+/// Parses a user supplied address of `network` and returns it in its canonical form.
+pub fn parse_address(address: &str, network: Network) -> AppResult<String> {
+    let invalid = |e: &dyn std::fmt::Display| AppError::BadRequestError(format!("invalid address {address}: {e}"));
+    let parsed = Address::from_str(address).map_err(|e| invalid(&e))?;
+    let parsed = parsed.require_network(network).map_err(|e| invalid(&e))?;
+    Ok(parsed.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoincore_rpc::bitcoin::ScriptBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_script_type_and_address() {
+        let p2wpkh = ScriptBuf::from_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        assert_eq!(ScriptType::of(&p2wpkh), ScriptType::P2wpkh);
+        assert_eq!(
+            script_address(&p2wpkh, Network::Bitcoin).as_deref(),
+            Some("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4")
+        );
+        assert_eq!(
+            script_address(&p2wpkh, Network::Testnet).as_deref(),
+            Some("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx")
+        );
+        assert!(parse_address("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", Network::Bitcoin).is_ok());
+        assert!(parse_address("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", Network::Testnet).is_err());
+
+        let op_return = ScriptBuf::from_hex("6a0568656c6c6f").unwrap();
+        assert_eq!(ScriptType::of(&op_return), ScriptType::NullData);
+        assert_eq!(script_address(&op_return, Network::Bitcoin), None);
+    }
+}
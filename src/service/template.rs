@@ -0,0 +1,326 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use bitcoincore_rpc::bitcoin::Txid;
+
+use crate::service::mempool::Mempool;
+
+/// Consensus limit on the weight of a block.
+pub const MAX_BLOCK_WEIGHT: u64 = 4_000_000;
+
+/// Weight Bitcoin Core keeps free for the coinbase transaction.
+const COINBASE_RESERVED_WEIGHT: u64 = 4_000;
+
+/// Like Bitcoin Core, a block is closed once this many packages in a row did not fit and
+/// it is within [`BLOCK_FULL_MARGIN`] of the limit.
+const MAX_CONSECUTIVE_FAILURES: usize = 1_000;
+
+const BLOCK_FULL_MARGIN: u64 = 4_000;
+
+/// A mempool transaction placed in a projected block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TemplateTx {
+    pub txid: Txid,
+    pub fee: u64,
+    pub vsize: u64,
+    pub weight: u64,
+    /// Feerate of the package the transaction was selected with, in sat/vB. Higher than
+    /// its own feerate when a child pays for it (CPFP).
+    pub effective_feerate: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProjectedBlock {
+    /// Transactions in selection order, parents before their children.
+    pub txs: Vec<TemplateTx>,
+    pub weight: u64,
+}
+
+impl ProjectedBlock {
+    pub fn vsize(&self) -> u64 {
+        self.txs.iter().map(|tx| tx.vsize).sum()
+    }
+
+    pub fn total_fee(&self) -> u64 {
+        self.txs.iter().map(|tx| tx.fee).sum()
+    }
+
+    /// Median effective feerate, weighted by vsize so it reflects the block space sold.
+    pub fn median_feerate(&self) -> Option<f64> {
+        let mut feerates: Vec<_> = self.txs.iter().map(|tx| (tx.effective_feerate, tx.vsize)).collect();
+        feerates.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let half = self.vsize().div_ceil(2);
+        let mut seen = 0;
+        feerates.into_iter().find_map(|(feerate, vsize)| {
+            seen += vsize;
+            (seen >= half).then_some(feerate)
+        })
+    }
+
+    pub fn min_feerate(&self) -> Option<f64> {
+        self.txs.iter().map(|tx| tx.effective_feerate).min_by(f64::total_cmp)
+    }
+
+    pub fn max_feerate(&self) -> Option<f64> {
+        self.txs.iter().map(|tx| tx.effective_feerate).max_by(f64::total_cmp)
+    }
+}
+
+/// The mempool split into the blocks a miner would most likely build next.
+///
+/// This is a simplified version of Bitcoin Core's block assembly: transactions are selected
+/// by ancestor score, so a transaction is always mined together with its unconfirmed
+/// ancestors and a high-fee child pulls its parents forward. A package that does not fit in
+/// the current block is skipped so smaller ones can fill it, and is left for the next
+/// block. The last block takes whatever is left, so it can be heavier than the weight limit.
+#[derive(Debug, Default)]
+pub struct BlockTemplate {
+    pub blocks: Vec<ProjectedBlock>,
+    positions: HashMap<Txid, (usize, usize)>,
+}
+
+impl BlockTemplate {
+    pub fn build(mempool: &Mempool, max_blocks: usize) -> Self {
+        let mut template = Self::default();
+        let mut included = HashSet::new();
+        while included.len() < mempool.len() {
+            let limit = match template.blocks.len() + 1 < max_blocks {
+                true => MAX_BLOCK_WEIGHT - COINBASE_RESERVED_WEIGHT,
+                false => u64::MAX,
+            };
+            let packages = select(mempool, &mut included, limit);
+            template.push(mempool, packages);
+        }
+        template
+    }
+
+    /// The projected block holding `txid`, `0` being the next block, and the transaction.
+    pub fn position(&self, txid: &Txid) -> Option<(usize, &TemplateTx)> {
+        let (block, index) = *self.positions.get(txid)?;
+        Some((block, &self.blocks[block].txs[index]))
+    }
+
+    fn push(&mut self, mempool: &Mempool, packages: Vec<Package>) {
+        let index = self.blocks.len();
+        let mut block = ProjectedBlock::default();
+        for package in packages {
+            let effective_feerate = package.feerate();
+            for txid in package.txids {
+                let Some(entry) = mempool.get(&txid) else {
+                    continue;
+                };
+                self.positions.insert(txid, (index, block.txs.len()));
+                block.weight += entry.weight;
+                block.txs.push(TemplateTx {
+                    txid,
+                    fee: entry.fee,
+                    vsize: entry.vsize,
+                    weight: entry.weight,
+                    effective_feerate,
+                });
+            }
+        }
+        self.blocks.push(block);
+    }
+}
+
+/// Selects the packages of the next block out of the transactions not `included` yet, by
+/// ancestor score, and marks them included. Packages that would push the block past
+/// `limit` are skipped, except into an empty block so that every call makes progress.
+fn select(mempool: &Mempool, included: &mut HashSet<Txid>, limit: u64) -> Vec<Package> {
+    let mut scores = HashMap::new();
+    let mut candidates = BinaryHeap::new();
+    for entry in mempool.entries().filter(|entry| !included.contains(&entry.txid)) {
+        let score = Package::collect(mempool, &entry.txid, included).feerate();
+        scores.insert(entry.txid, score);
+        candidates.push(Candidate { score, txid: entry.txid });
+    }
+    let mut packages = Vec::new();
+    let mut weight = 0;
+    let mut failures = 0;
+    while let Some(Candidate { score, txid }) = candidates.pop() {
+        // Scores change as ancestors get selected, outdated candidates are skipped.
+        if scores.get(&txid) != Some(&score) {
+            continue;
+        }
+        let package = Package::collect(mempool, &txid, included);
+        if weight > 0 && weight + package.weight > limit {
+            scores.remove(&txid);
+            failures += 1;
+            if failures > MAX_CONSECUTIVE_FAILURES && weight > limit.saturating_sub(BLOCK_FULL_MARGIN) {
+                break;
+            }
+            continue;
+        }
+        failures = 0;
+        for txid in &package.txids {
+            included.insert(*txid);
+            scores.remove(txid);
+        }
+        let descendants: HashSet<Txid> = package
+            .txids
+            .iter()
+            .flat_map(|txid| mempool.descendants(txid))
+            .filter(|txid| !included.contains(txid))
+            .collect();
+        for descendant in descendants {
+            let score = Package::collect(mempool, &descendant, included).feerate();
+            scores.insert(descendant, score);
+            candidates.push(Candidate { score, txid: descendant });
+        }
+        weight += package.weight;
+        packages.push(package);
+    }
+    packages
+}
+
+/// A transaction with its ancestors that are not in the template yet.
+struct Package {
+    /// Ancestors first, so every parent comes before its children.
+    txids: Vec<Txid>,
+    fee: u64,
+    vsize: u64,
+    weight: u64,
+}
+
+impl Package {
+    fn collect(mempool: &Mempool, txid: &Txid, included: &HashSet<Txid>) -> Self {
+        let mut txids: Vec<Txid> = mempool
+            .ancestors(txid)
+            .into_iter()
+            .filter(|ancestor| !included.contains(ancestor))
+            .collect();
+        txids.push(*txid);
+        // A transaction always has more ancestors than any of its own ancestors.
+        txids.sort_by_cached_key(|txid| mempool.ancestors(txid).len());
+        let mut package = Self {
+            txids,
+            fee: 0,
+            vsize: 0,
+            weight: 0,
+        };
+        for entry in package.txids.iter().filter_map(|txid| mempool.get(txid)) {
+            package.fee += entry.fee;
+            package.vsize += entry.vsize;
+            package.weight += entry.weight;
+        }
+        package
+    }
+
+    fn feerate(&self) -> f64 {
+        self.fee as f64 / self.vsize.max(1) as f64
+    }
+}
+
+struct Candidate {
+    score: f64,
+    txid: Txid,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Ties go to the lower txid so the template does not depend on hash map order.
+        self.score
+            .total_cmp(&other.score)
+            .then_with(|| other.txid.cmp(&self.txid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use bitcoincore_rpc::bitcoin::hashes::Hash;
+
+    use super::*;
+    use crate::service::mempool::MempoolEntry;
+
+    fn txid(n: u8) -> Txid {
+        Txid::from_byte_array([n; 32])
+    }
+
+    fn entry(n: u8, fee: u64, vsize: u64, parents: &[u8]) -> MempoolEntry {
+        MempoolEntry {
+            txid: txid(n),
+            fee,
+            vsize,
+            weight: vsize * 4,
+            first_seen: 0,
+            parents: parents.iter().map(|n| txid(*n)).collect::<BTreeSet<_>>(),
+            spends: vec![],
+        }
+    }
+
+    #[test]
+    fn test_child_pays_for_parent() {
+        let mut mempool = Mempool::default();
+        mempool.insert(entry(1, 100, 100, &[]));
+        mempool.insert(entry(2, 4900, 100, &[1]));
+        mempool.insert(entry(3, 2000, 100, &[]));
+        let template = BlockTemplate::build(&mempool, 8);
+        let order: Vec<_> = template.blocks[0].txs.iter().map(|tx| tx.txid).collect();
+        assert_eq!(order, vec![txid(1), txid(2), txid(3)]);
+
+        let (block, parent) = template.position(&txid(1)).unwrap();
+        assert_eq!((block, parent.effective_feerate), (0, 25.0));
+        assert_eq!(template.blocks[0].median_feerate(), Some(25.0));
+        assert_eq!(template.blocks[0].min_feerate(), Some(20.0));
+    }
+
+    #[test]
+    fn test_split_by_weight() {
+        let mut mempool = Mempool::default();
+        // Three transactions of 400k vbytes each, only two fit in a block.
+        for (n, fee) in [(1, 3_000_000), (2, 2_000_000), (3, 1_000_000), (4, 200)] {
+            mempool.insert(entry(n, fee, if n == 4 { 100 } else { 400_000 }, &[]));
+        }
+        let template = BlockTemplate::build(&mempool, 8);
+        let blocks: Vec<Vec<_>> = template
+            .blocks
+            .iter()
+            .map(|block| block.txs.iter().map(|tx| tx.txid).collect())
+            .collect();
+        // The small one fills the space the third large one does not fit in.
+        assert_eq!(blocks, vec![vec![txid(1), txid(2), txid(4)], vec![txid(3)]]);
+
+        // With a single block everything ends up in it.
+        let template = BlockTemplate::build(&mempool, 1);
+        assert_eq!(template.blocks.len(), 1);
+        assert_eq!(template.blocks[0].txs.len(), 4);
+        assert_eq!(template.position(&txid(4)).unwrap().0, 0);
+    }
+
+    #[test]
+    fn test_small_packages_fill_the_block() {
+        let mut mempool = Mempool::default();
+        // A high-fee package of 900k vbytes leaves room for a 100k vbyte package only.
+        mempool.insert(entry(1, 9_000_000, 900_000, &[]));
+        mempool.insert(entry(2, 5_000_000, 500_000, &[]));
+        mempool.insert(entry(3, 200_000, 50_000, &[]));
+        mempool.insert(entry(4, 150_000, 30_000, &[3]));
+        mempool.insert(entry(5, 40_000, 10_000, &[]));
+        let template = BlockTemplate::build(&mempool, 8);
+        let blocks: Vec<Vec<_>> = template
+            .blocks
+            .iter()
+            .map(|block| block.txs.iter().map(|tx| tx.txid).collect())
+            .collect();
+        assert_eq!(blocks, vec![vec![txid(1), txid(3), txid(4), txid(5)], vec![txid(2)]]);
+        assert_eq!(template.blocks[0].vsize(), 990_000);
+        assert_eq!(template.position(&txid(2)).unwrap().0, 1);
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use bitcoincore_rpc::bitcoin::hex::DisplayHex;
+use bitcoincore_rpc::bitcoin::{OutPoint, Transaction, Txid};
+use tracing::info;
+
+use crate::dto::response::{TransactionResponse, TxInputResponse, TxOutputResponse};
+use crate::entity::transaction;
+use crate::error::{AppError, AppResult, Resource, ResourceType};
+use crate::repo;
+use crate::server::state::AppState;
+use crate::service::ingest::{self, Prevouts, TxSummary};
+use crate::service::script::{script_address, ScriptType};
+
+/// Returns a decoded transaction. Indexed transactions are served from the database,
+/// anything else (mempool or not yet indexed) is fetched from the node.
+pub async fn get(state: &AppState, txid: &str) -> AppResult<TransactionResponse> {
+    let txid = Txid::from_str(txid)
+        .map_err(|e| AppError::BadRequestError(format!("invalid txid {txid}: {e}")))?;
+    info!("Get transaction: {txid}.");
+    match repo::transaction::find_by_txid(&*state.db, &txid.to_string()).await? {
+        Some(model) => from_index(state, model).await,
+        None => from_node(state, txid).await,
+    }
+}
+
+async fn from_index(state: &AppState, tx: transaction::Model) -> AppResult<TransactionResponse> {
+    let inputs = repo::tx_input::find_by_txid(&*state.db, &tx.txid).await?;
+    let outputs = repo::tx_output::find_by_txid(&*state.db, &tx.txid).await?;
+    let block = repo::block::find_by_height(&*state.db, tx.block_height).await?;
+    let tip = repo::block::find_max_height(&*state.db)
+        .await?
+        .unwrap_or(tx.block_height);
+    Ok(TransactionResponse {
+        network: state.config.bitcoin.network.to_string(),
+        txid: tx.txid,
+        block_height: Some(tx.block_height),
+        block_hash: block.map(|block| block.hash),
+        block_index: Some(tx.block_index),
+        confirmations: (tip - tx.block_height + 1) as u32,
+        size: tx.size,
+        vsize: tx.vsize,
+        weight: tx.weight,
+        fee: Some(tx.fee),
+        feerate: Some(tx.feerate),
+        version: tx.version,
+        locktime: tx.locktime as u32,
+        is_segwit: tx.is_segwit,
+        is_taproot: tx.is_taproot,
+        inputs: inputs.into_iter().map(Into::into).collect(),
+        outputs: outputs.into_iter().map(Into::into).collect(),
+    })
+}
+
+async fn from_node(state: &AppState, txid: Txid) -> AppResult<TransactionResponse> {
+    let found = state.source.transaction(&txid).await?.ok_or_else(|| {
+        AppError::NotFoundError(Resource {
+            details: vec![("txid".to_string(), txid.to_string())],
+            resource_type: ResourceType::Transaction,
+        })
+    })?;
+    let tx = found.tx;
+    let block_height = match &found.block_hash {
+        Some(hash) => state.source.header(hash).await?.map(|header| header.height),
+        None => None,
+    };
+    let confirmations = match block_height {
+        Some(height) => state.source.tip().await?.0.saturating_sub(height) + 1,
+        None => 0,
+    };
+    let prevouts = resolve_prevouts(state, &tx).await?;
+    let network = state.config.bitcoin.network;
+
+    let spent: Vec<_> = tx
+        .input
+        .iter()
+        .map(|input| (!tx.is_coinbase()).then(|| prevouts.get(&input.previous_output)).flatten())
+        .collect();
+    let summary = TxSummary::new(&tx, &spent);
+    let fee = summary.fee.map(|fee| fee as i64);
+    let vsize = tx.vsize();
+    let inputs = tx
+        .input
+        .iter()
+        .zip(&spent)
+        .map(|(input, prevout)| TxInputResponse {
+            is_coinbase: tx.is_coinbase(),
+            prev_txid: (!tx.is_coinbase()).then(|| input.previous_output.txid.to_string()),
+            prev_vout: (!tx.is_coinbase()).then_some(input.previous_output.vout),
+            value: prevout.map(|prevout| prevout.value.to_sat() as i64),
+            address: prevout.and_then(|prevout| script_address(&prevout.script_pubkey, network)),
+            script_sig: input.script_sig.as_bytes().to_lower_hex_string(),
+            witness: input.witness.iter().map(|item| item.to_lower_hex_string()).collect(),
+            sequence: input.sequence.0,
+        })
+        .collect();
+    // Not indexed yet, so the spends come from the node's UTXO set and the tracked mempool.
+    // Spenders confirmed in blocks that are not indexed yet stay unknown.
+    let outpoints: Vec<_> = (0..tx.output.len() as u32).map(|vout| OutPoint::new(txid, vout)).collect();
+    let unspent = state.source.unspent(&outpoints).await?;
+    let spenders: Vec<_> = {
+        let mempool = state.mempool.read().await;
+        outpoints.iter().map(|outpoint| mempool.spender(outpoint)).collect()
+    };
+    let outputs = tx
+        .output
+        .iter()
+        .zip(unspent.into_iter().zip(spenders))
+        .enumerate()
+        .map(|(vout, (output, (unspent, spender)))| TxOutputResponse {
+            vout: vout as u32,
+            value: output.value.to_sat() as i64,
+            script_pubkey: output.script_pubkey.as_bytes().to_lower_hex_string(),
+            script_type: ScriptType::of(&output.script_pubkey).to_string(),
+            address: script_address(&output.script_pubkey, network),
+            // Unspendable outputs never enter the UTXO set.
+            spent: !unspent && !output.script_pubkey.is_op_return(),
+            spent_by_txid: spender.map(|(txid, _)| txid.to_string()),
+            spent_by_vin: spender.map(|(_, vin)| vin),
+        })
+        .collect();
+    Ok(TransactionResponse {
+        network: network.to_string(),
+        txid: txid.to_string(),
+        block_height: block_height.map(|height| height as i32),
+        block_hash: found.block_hash.map(|hash| hash.to_string()),
+        block_index: None,
+        confirmations,
+        size: tx.total_size() as i32,
+        vsize: vsize as i32,
+        weight: tx.weight().to_wu() as i32,
+        fee,
+        feerate: fee.map(|fee| fee as f64 / vsize as f64),
+        version: tx.version.0,
+        locktime: tx.lock_time.to_consensus_u32(),
+        is_segwit: summary.is_segwit,
+        is_taproot: summary.is_taproot,
+        inputs,
+        outputs,
+    })
+}
+
+/// Resolves the prevouts of `tx` from the index first and asks the node only for
+/// parents that are not indexed yet, such as unconfirmed ones.
+async fn resolve_prevouts(state: &AppState, tx: &Transaction) -> AppResult<Prevouts> {
+    if tx.is_coinbase() {
+        return Ok(Prevouts::new());
+    }
+    let outpoints: Vec<OutPoint> = tx.input.iter().map(|input| input.previous_output).collect();
+    let mut prevouts = ingest::lookup_prevouts(&*state.db, &outpoints).await?;
+    let parents: HashSet<Txid> = outpoints
+        .iter()
+        .filter(|outpoint| !prevouts.contains_key(outpoint))
+        .map(|outpoint| outpoint.txid)
+        .collect();
+    for txid in parents {
+        let Some(parent) = state.source.transaction(&txid).await?.map(|found| found.tx) else {
+            continue;
+        };
+        for (vout, output) in parent.output.into_iter().enumerate() {
+            let outpoint = OutPoint::new(txid, vout as u32);
+            if outpoints.contains(&outpoint) {
+                prevouts.insert(outpoint, output);
+            }
+        }
+    }
+    Ok(prevouts)
+}